@@ -0,0 +1,198 @@
+//! Python bindings for the game engine and the learning environment. Build and install them
+//! into the current virtual environment with `maturin develop --release` in this directory.
+
+use numpy::ndarray::Array2;
+use numpy::{IntoPyArray, PyArray1, PyArray2, PyReadonlyArray1};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use engine::card::Card;
+use engine::encoding::{encode, FEATURE_COUNT};
+use engine::env::{self, Observation, ACTION_COUNT, MAX_PLAYERS};
+
+type Batch<'py> = (Bound<'py, PyArray2<f32>>, Bound<'py, PyArray2<bool>>, Bound<'py, PyArray1<i64>>);
+type BatchStep<'py> = (Bound<'py, PyArray2<f32>>, Bound<'py, PyArray2<bool>>, Bound<'py, PyArray2<f32>>, Bound<'py, PyArray1<bool>>, Bound<'py, PyArray1<i64>>);
+
+fn card_value(card: Option<Card>) -> Option<usize> {
+    card.map(|card| card.value())
+}
+
+/// One game. Observations are the `FEATURE_COUNT` features of the player to act and the mask of
+/// their legal actions, rewards are the tokens every player won in the step.
+#[pyclass(name = "Env")]
+struct PyEnv {
+    env: env::Env,
+    observation: Observation,
+}
+
+#[pymethods]
+impl PyEnv {
+    #[new]
+    fn new(players: usize) -> PyResult<PyEnv> {
+        if !(2..=MAX_PLAYERS).contains(&players) {
+            return Err(PyValueError::new_err(format!("{} is not a valid number of players. Must be between 2 and {}.", players, MAX_PLAYERS)));
+        }
+        let mut env = env::Env::new(players);
+        let observation = env.reset(0);
+        Ok(PyEnv { env, observation })
+    }
+
+    /// Starts a new game. Returns `(features, mask)`.
+    fn reset<'py>(&mut self, py: Python<'py>, seed: u64) -> (Bound<'py, PyArray1<f32>>, Bound<'py, PyArray1<bool>>) {
+        self.observation = self.env.reset(seed);
+        (encode(&self.observation).into_pyarray(py), self.observation.mask.clone().into_pyarray(py))
+    }
+
+    /// Takes an action for the player to act. Returns `(features, mask, rewards, done)`.
+    #[allow(clippy::type_complexity)]
+    fn step<'py>(&mut self, py: Python<'py>, action: usize) -> PyResult<(Bound<'py, PyArray1<f32>>, Bound<'py, PyArray1<bool>>, Bound<'py, PyArray1<f64>>, bool)> {
+        let (observation, rewards, done) = self.env.step(action).map_err(|error| PyValueError::new_err(format!("Illegal action {}: {:?}", action, error)))?;
+        self.observation = observation;
+        Ok((encode(&self.observation).into_pyarray(py), self.observation.mask.clone().into_pyarray(py), rewards.into_pyarray(py), done))
+    }
+
+    /// The player to act, numbered by their seat in the first round.
+    #[getter]
+    fn player(&self) -> usize {
+        self.observation.player
+    }
+
+    /// The seat of the player to act in this round.
+    #[getter]
+    fn seat(&self) -> usize {
+        self.observation.seat
+    }
+
+    /// The indices of the legal actions.
+    fn legal_actions<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<usize>> {
+        self.observation.legal().into_pyarray(py)
+    }
+
+    /// The whole state of the round, hidden cards included, as card values by seat.
+    #[allow(clippy::type_complexity)]
+    fn state(&self) -> (Vec<usize>, Vec<Option<usize>>, Vec<Vec<usize>>, Vec<usize>, Vec<i32>, usize) {
+        let state = self.env.state();
+        (
+            state.deck.iter().map(|card| card.value()).collect(),
+            state.hands.iter().map(|card| card_value(*card)).collect(),
+            state.discard.iter().map(|pile| pile.iter().map(|card| card.value()).collect()).collect(),
+            state.out.clone(),
+            state.tokens.clone(),
+            state.turn,
+        )
+    }
+}
+
+/// `count` games stepped together. Games that end are started again with the next seed.
+#[pyclass(name = "VecEnv")]
+struct PyVecEnv {
+    envs: Vec<env::Env>,
+    observations: Vec<Observation>,
+    players: usize,
+    next_seed: u64,
+}
+
+impl PyVecEnv {
+    fn restart(&mut self) {
+        self.observations = self.envs.iter_mut().map(|env| {
+            self.next_seed += 1;
+            env.reset(self.next_seed - 1)
+        }).collect();
+    }
+
+    fn batch<'py>(&self, py: Python<'py>) -> Batch<'py> {
+        let count = self.envs.len();
+        let features: Vec<f32> = self.observations.iter().flat_map(encode).collect();
+        let masks: Vec<bool> = self.observations.iter().flat_map(|observation| observation.mask.iter().cloned()).collect();
+        let players: Vec<i64> = self.observations.iter().map(|observation| observation.player as i64).collect();
+        (
+            Array2::from_shape_vec((count, FEATURE_COUNT), features).unwrap().into_pyarray(py),
+            Array2::from_shape_vec((count, ACTION_COUNT), masks).unwrap().into_pyarray(py),
+            players.into_pyarray(py),
+        )
+    }
+}
+
+#[pymethods]
+impl PyVecEnv {
+    #[new]
+    #[pyo3(signature = (count, players, seed = 0))]
+    fn new(count: usize, players: usize, seed: u64) -> PyResult<PyVecEnv> {
+        if !(2..=MAX_PLAYERS).contains(&players) {
+            return Err(PyValueError::new_err(format!("{} is not a valid number of players. Must be between 2 and {}.", players, MAX_PLAYERS)));
+        }
+        let envs = (0..count).map(|_| env::Env::new(players)).collect();
+        let mut vec_env = PyVecEnv { envs, observations: Vec::new(), players, next_seed: seed };
+        vec_env.restart();
+        Ok(vec_env)
+    }
+
+    /// Starts every game again. Returns `(features, masks, players)` with one row per game.
+    fn reset<'py>(&mut self, py: Python<'py>) -> Batch<'py> {
+        self.restart();
+        self.batch(py)
+    }
+
+    /// Takes one action in every game. Returns `(features, masks, rewards, dones, players)`,
+    /// where `rewards[i][p]` is what player `p` of game `i` won and the features of finished
+    /// games are those of the game that replaced them.
+    fn step<'py>(&mut self, py: Python<'py>, actions: PyReadonlyArray1<'py, i64>) -> PyResult<BatchStep<'py>> {
+        let actions = actions.as_slice()?.to_vec();
+        if actions.len() != self.envs.len() {
+            return Err(PyValueError::new_err(format!("Expected {} actions, got {}.", self.envs.len(), actions.len())));
+        }
+        // Check every action first so an illegal one leaves the whole batch where it was
+        for (i, action) in actions.iter().enumerate() {
+            if !usize::try_from(*action).is_ok_and(|action| self.observations[i].mask.get(action) == Some(&true)) {
+                return Err(PyValueError::new_err(format!("Illegal action {} in game {}.", action, i)));
+            }
+        }
+        let players = self.players;
+        let count = self.envs.len();
+        let (rewards, dones) = py.detach(|| -> PyResult<(Vec<f32>, Vec<bool>)> {
+            let mut rewards = vec![0.0; count * players];
+            let mut dones = vec![false; count];
+            for (i, action) in actions.iter().enumerate() {
+                let (observation, game_rewards, done) = self.envs[i].step(*action as usize).map_err(|error| PyValueError::new_err(format!("Illegal action {} in game {}: {:?}", action, i, error)))?;
+                for (player, reward) in game_rewards.iter().enumerate() {
+                    rewards[i * players + player] = *reward as f32;
+                }
+                dones[i] = done;
+                self.observations[i] = if done {
+                    self.next_seed += 1;
+                    self.envs[i].reset(self.next_seed - 1)
+                } else {
+                    observation
+                };
+            }
+            Ok((rewards, dones))
+        })?;
+        let (features, masks, to_act) = self.batch(py);
+        Ok((features, masks, Array2::from_shape_vec((count, players), rewards).unwrap().into_pyarray(py), dones.into_pyarray(py), to_act))
+    }
+}
+
+/// The name of the card of value `value`.
+#[pyfunction]
+fn card_name(value: usize) -> PyResult<String> {
+    Card::from_value(value).map(|card| card.name()).ok_or(PyValueError::new_err(format!("No card has value {}.", value)))
+}
+
+/// The `(card, target, guess)` behind an action index for the player in `seat`, as card values
+/// and a seat, or `None` for Executioner choices.
+#[pyfunction]
+fn decode_action(index: usize, seat: usize, players: usize) -> Option<(usize, Option<usize>, Option<usize>)> {
+    env::decode_action(index, seat, players).map(|action| (action.card.value(), action.target, card_value(action.guess)))
+}
+
+#[pymodule]
+fn love_letter(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add("ACTION_COUNT", ACTION_COUNT)?;
+    module.add("FEATURE_COUNT", FEATURE_COUNT)?;
+    module.add("MAX_PLAYERS", MAX_PLAYERS)?;
+    module.add_class::<PyEnv>()?;
+    module.add_class::<PyVecEnv>()?;
+    module.add_function(wrap_pyfunction!(card_name, module)?)?;
+    module.add_function(wrap_pyfunction!(decode_action, module)?)?;
+    Ok(())
+}
@@ -0,0 +1,50 @@
+import numpy as np
+
+import love_letter
+
+
+def test_random_games():
+    env = love_letter.Env(4)
+    features, mask = env.reset(1)
+    assert features.shape == (love_letter.FEATURE_COUNT,)
+    assert mask.shape == (love_letter.ACTION_COUNT,)
+    rng = np.random.default_rng(1)
+    tokens = np.zeros(4)
+    done = False
+    while not done:
+        features, mask, rewards, done = env.step(int(rng.choice(np.flatnonzero(mask))))
+        tokens += rewards
+    assert tokens.max() >= 3
+
+
+def test_batched_steps():
+    envs = love_letter.VecEnv(8, 3, seed=5)
+    features, masks, players = envs.reset()
+    assert features.shape == (8, love_letter.FEATURE_COUNT)
+    rng = np.random.default_rng(2)
+    finished = 0
+    for _ in range(2000):
+        actions = np.array([rng.choice(np.flatnonzero(mask)) for mask in masks], dtype=np.int64)
+        features, masks, rewards, dones, players = envs.step(actions)
+        assert rewards.shape == (8, 3)
+        finished += dones.sum()
+    assert finished > 0
+
+
+def test_illegal_action_leaves_the_batch():
+    envs = love_letter.VecEnv(2, 2, seed=3)
+    twins = love_letter.VecEnv(2, 2, seed=3)
+    features, masks, players = envs.reset()
+    twins.reset()
+    legal = np.array([np.flatnonzero(mask)[0] for mask in masks], dtype=np.int64)
+    illegal = np.array([legal[0], np.flatnonzero(~masks[1])[0]], dtype=np.int64)
+    try:
+        envs.step(illegal)
+        assert False, "an illegal action was taken"
+    except ValueError:
+        pass
+    # The first game did not step, so both batches go on alike
+    features, _, rewards, _, _ = envs.step(legal)
+    twin_features, _, twin_rewards, _, _ = twins.step(legal)
+    assert (features == twin_features).all()
+    assert (rewards == twin_rewards).all()
@@ -0,0 +1,42 @@
+use std::time::Duration;
+
+use crate::card::Card;
+use crate::game::{Action, Event, ExecutionerChoice, PlayError, State};
+
+/// Something that makes the decisions for a seat, be it a human at the terminal or a bot.
+pub trait Agent {
+    /// Called when a round is dealt, with the seat the agent plays this round.
+    fn start_round(&mut self, _state: &State, _seat: usize) {}
+
+    /// Picks one of `legal` while holding the two cards in `hand` (held card first, drawn card second).
+    fn choose_action(&mut self, state: &State, hand: [Card; 2], legal: &[Action]) -> Action;
+
+    /// Picks which of the `drawn` cards to keep after playing the Executioner while holding `hand`.
+    fn choose_executioner(&mut self, state: &State, hand: Card, drawn: &[Card]) -> ExecutionerChoice;
+
+    /// Called when the engine rejected an action, before the agent is asked again.
+    fn invalid_action(&mut self, _state: &State, _action: &Action, _error: &PlayError) {}
+
+    /// Called before a decision at a timed table with how long the agent has to make it and what
+    /// is left of its bank of time, if it has one. Agents waiting on someone should stop once the
+    /// time is up, as the default action is played for them anyway.
+    fn start_clock(&mut self, _left: Duration, _bank: Option<Duration>) {}
+
+    /// Called when the agent took too long to decide, before the default action of the clock
+    /// is played for it.
+    fn out_of_time(&mut self, _state: &State) {}
+
+    /// Called for every event of every turn with the state after the turn. Agents must only
+    /// use what `Event::visible_to` and `describe` would show their seat.
+    fn observe(&mut self, _state: &State, _event: &Event) {}
+
+    /// Called when the round is over, with the state after the tokens were awarded and the seats
+    /// that won it.
+    fn end_round(&mut self, _state: &State, _winners: &[usize]) {}
+
+    /// Why the agent gave up the game, if it did. Agents that forfeit must still pick legal
+    /// actions until the round is over.
+    fn forfeited(&self) -> Option<String> {
+        None
+    }
+}
@@ -0,0 +1,208 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::card::{list_cards, Card};
+use crate::game::{red_queen_blocks, Event};
+use crate::options::Options;
+use crate::record::{load_rounds, RoundRecord};
+use crate::sim::{game_seed, random_bots, simulate_game, BatchConfig, Merge, StartPolicy};
+
+/// Per card counters, indexed by card value.
+type Counts = [u64; 10];
+
+/// How every card fared over a number of rounds.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CardStats {
+    pub rounds: u64,
+    /// Times the card was played by choice.
+    pub played: Counts,
+    /// Times playing the card knocked out the player who played it.
+    pub self_out: Counts,
+    /// Times playing the card knocked out another player.
+    pub target_out: Counts,
+    /// Times the card was discarded because of the Knave of Hearts.
+    pub forced: Counts,
+    /// Times being made to discard the card by the Knave of Hearts knocked the player out.
+    pub forced_out: Counts,
+    /// Times the card was in the hand of a player who was still in at the end of the round.
+    pub held: Counts,
+    /// Times a player holding the card at the end of the round won it.
+    pub held_won: Counts,
+    /// Guard guesses by the guessed card.
+    pub guesses: Counts,
+    pub hits: Counts,
+    /// Turns where a player held the Red Queen.
+    pub red_queen_turns: u64,
+    /// Turns where the Red Queen had to be discarded because of Time or the Executioner.
+    pub red_queen_forced: u64,
+}
+
+impl CardStats {
+    pub fn add(&mut self, round: &RoundRecord) {
+        self.rounds += 1;
+        for turn in &round.turns {
+            if turn.hand.contains(&Card::RedQueen) {
+                self.red_queen_turns += 1;
+                if red_queen_blocks(&turn.hand[0], &turn.hand[1]) || red_queen_blocks(&turn.hand[1], &turn.hand[0]) {
+                    self.red_queen_forced += 1;
+                }
+            }
+            for event in &turn.events {
+                let card = event.card().value();
+                self.played[card] += 1;
+                match event.eliminated() {
+                    Some(player) if player == event.player() => self.self_out[card] += 1,
+                    Some(_) => self.target_out[card] += 1,
+                    None => {},
+                }
+                match event {
+                    Event::Knave { card: discarded, .. } => {
+                        self.forced[discarded.value()] += 1;
+                        if event.eliminated().is_some() {
+                            self.forced_out[discarded.value()] += 1;
+                        }
+                    },
+                    Event::Guessed { guess, correct, .. } => {
+                        self.guesses[guess.value()] += 1;
+                        if *correct {
+                            self.hits[guess.value()] += 1;
+                        }
+                    },
+                    _ => {},
+                }
+            }
+        }
+        // Players knocked out by the tie breaker still held their card at the end of the round
+        let end = &round.end;
+        let knocked_out: Vec<usize> = round.turns.iter().flat_map(|turn| turn.events.iter().filter_map(|event| event.eliminated())).collect();
+        for player in 0..end.players.len() {
+            if knocked_out.contains(&player) {
+                continue;
+            }
+            if let Some(card) = end.hands[player] {
+                self.held[card.value()] += 1;
+                if round.winners.contains(&player) {
+                    self.held_won[card.value()] += 1;
+                }
+            }
+        }
+    }
+
+    pub fn print(&self) {
+        println!("Rounds: {}", self.rounds);
+        println!("{:<18}{:>9}{:>11}{:>13}{:>15}{:>15}{:>11}", "Card", "Played", "Self out", "Target out", "Forced (out)", "Held at end", "Won held");
+        for card in list_cards() {
+            let i = card.value();
+            println!(
+                "{:<18}{:>9}{:>11}{:>13}{:>15}{:>15}{:>11}",
+                format!("{} {}", i, card.name()),
+                self.played[i],
+                percent(self.self_out[i], self.played[i]),
+                percent(self.target_out[i], self.played[i]),
+                format!("{} ({})", self.forced[i], percent(self.forced_out[i], self.forced[i])),
+                self.held[i],
+                percent(self.held_won[i], self.held[i]),
+            );
+        }
+
+        println!("\nGuard guesses:");
+        println!("{:<18}{:>9}{:>11}", "Guess", "Guesses", "Hit rate");
+        for card in list_cards() {
+            let i = card.value();
+            if self.guesses[i] > 0 {
+                println!("{:<18}{:>9}{:>11}", format!("{} {}", i, card.name()), self.guesses[i], percent(self.hits[i], self.guesses[i]));
+            }
+        }
+
+        println!("\nThe Red Queen was held on {} turns and had to be discarded because of Time or the Executioner on {} of them ({}).", self.red_queen_turns, self.red_queen_forced, percent(self.red_queen_forced, self.red_queen_turns));
+    }
+}
+
+pub fn percent(part: u64, whole: u64) -> String {
+    if whole == 0 {
+        "-".to_string()
+    } else {
+        format!("{:.1}%", 100.0 * part as f64 / whole as f64)
+    }
+}
+
+impl Merge for CardStats {
+    fn merge(&mut self, other: CardStats) {
+        self.rounds += other.rounds;
+        for i in 0..10 {
+            self.played[i] += other.played[i];
+            self.self_out[i] += other.self_out[i];
+            self.target_out[i] += other.target_out[i];
+            self.forced[i] += other.forced[i];
+            self.forced_out[i] += other.forced_out[i];
+            self.held[i] += other.held[i];
+            self.held_won[i] += other.held_won[i];
+            self.guesses[i] += other.guesses[i];
+            self.hits[i] += other.hits[i];
+        }
+        self.red_queen_turns += other.red_queen_turns;
+        self.red_queen_forced += other.red_queen_forced;
+    }
+}
+
+/// `balance`: card statistics from a record file (`--record`) or from simulated games.
+pub fn command(options: &Options) -> Result<(), String> {
+    let stats = match options.value("record") {
+        Some(path) => {
+            let mut stats = CardStats::default();
+            for round in load_rounds(path)? {
+                stats.add(&round);
+            }
+            stats
+        },
+        None => {
+            let config = BatchConfig::from_options(options)?;
+            config.run(CardStats::default, |game, stats: &mut CardStats| {
+                let mut rng = StdRng::seed_from_u64(game_seed(config.seed, game));
+                let agents = random_bots(config.players, &mut rng);
+                simulate_game(agents, StartPolicy::Rotate, &mut rng, &mut |round, _| stats.add(round));
+            })
+        },
+    };
+    stats.print();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::create_deck;
+    use crate::game::{Action, State};
+    use crate::record::replay_round;
+
+    #[test]
+    fn test_tweedies_backfire() {
+        // Ann holds a Guard and draws the Tweedies, Bob holds Alice
+        let mut deck = create_deck();
+        deck.push(Card::Tweedies);
+        let start = State {
+            deck,
+            discard: vec![vec![], vec![]],
+            players: vec!["Ann".to_string(), "Bob".to_string()],
+            out: Vec::new(),
+            hands: vec![Some(Card::Guard), Some(Card::Alice)],
+            tokens: vec![0, 0],
+            round: 1,
+            turn: 0,
+            dormouse: None,
+        };
+        let turns = vec![crate::record::Turn {
+            player: 0,
+            hand: [Card::Guard, Card::Tweedies],
+            action: Action { card: Card::Tweedies, target: Some(1), guess: None },
+            executioner: None,
+            events: Vec::new(),
+        }];
+        let mut stats = CardStats::default();
+        stats.add(&replay_round(start, &turns).unwrap());
+        assert_eq!(stats.played[Card::Tweedies.value()], 1);
+        assert_eq!(stats.self_out[Card::Tweedies.value()], 1);
+        assert_eq!(stats.held[Card::Alice.value()], 1);
+        assert_eq!(stats.held_won[Card::Alice.value()], 1);
+    }
+}
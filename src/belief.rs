@@ -0,0 +1,221 @@
+use crate::card::{list_cards, Card};
+use crate::game::{Event, State};
+use crate::model::{OpponentModel, Tendencies};
+
+/// Probability of each card, indexed by card value.
+pub type Distribution = [f64; 10];
+
+/// What one seat knows about the other hands during a round, built only from the events that
+/// seat is allowed to see.
+#[derive(Debug, Clone)]
+pub struct Knowledge {
+    pub seat: usize,
+    /// The card we know each player holds.
+    pub known: Vec<Option<Card>>,
+    /// The card each player who is out was seen holding when they went out, by us or everyone.
+    pub revealed: Vec<Option<Card>>,
+    /// The card we hold between turns. Bots update it when they decide what to keep.
+    pub hand: Option<Card>,
+    /// How much more likely than the others each card is to be in each hand, from how the
+    /// player played it. All 1 for players we know nothing about.
+    pub likelihood: Vec<Distribution>,
+    /// What we learned about each player in earlier games.
+    pub tendencies: Vec<Option<Tendencies>>,
+}
+
+impl Knowledge {
+    pub fn new(state: &State, seat: usize) -> Knowledge {
+        let players = state.players.len();
+        Knowledge { seat, known: vec![None; players], revealed: vec![None; players], hand: state.hands[seat], likelihood: vec![[1.0; 10]; players], tendencies: vec![None; players] }
+    }
+
+    /// Uses what `model` learned about the other players at the table, by their names.
+    pub fn use_model(&mut self, state: &State, model: &OpponentModel) {
+        for (player, name) in state.players.iter().enumerate() {
+            if player != self.seat {
+                self.tendencies[player] = model.tendencies(name).cloned();
+            }
+        }
+    }
+
+    /// Updates what we know after `event`, given the state after the turn it happened in.
+    pub fn observe(&mut self, state: &State, event: &Event) {
+        let player = event.player();
+        let me = self.seat;
+
+        // How likely they were to play this card next to each other card
+        if let Some(tendencies) = &self.tendencies[player] {
+            for other in list_cards() {
+                self.likelihood[player][other.value()] = tendencies.play_rate(event.card(), other);
+            }
+        }
+        match event {
+            Event::Swapped { target, .. } => self.likelihood.swap(player, *target),
+            Event::Executioner { .. } => self.likelihood[player] = [1.0; 10],
+            Event::Knave { target, .. } => self.likelihood[*target] = [1.0; 10],
+            _ => {},
+        }
+
+        // A player who discards the card we knew about now holds the card they drew
+        if self.known[player] == Some(event.card()) {
+            self.known[player] = None;
+        }
+
+        match event {
+            Event::Swapped { target, .. } => {
+                if player == me {
+                    self.known[*target] = self.hand;
+                } else if *target == me {
+                    self.known[player] = self.hand;
+                } else {
+                    self.known.swap(player, *target);
+                }
+            },
+            Event::Executioner { .. } => self.known[player] = None,
+            Event::Knave { target, .. } => self.known[*target] = None,
+            Event::Compared { target, player_card, target_card, .. } => {
+                if player == me {
+                    self.known[*target] = Some(*target_card);
+                } else if *target == me {
+                    self.known[player] = Some(*player_card);
+                }
+            },
+            Event::Revealed { target, card, .. } if player == me => self.known[*target] = Some(*card),
+            Event::Guessed { target, guess, correct: true, .. } => self.revealed[*target] = Some(*guess),
+            _ => {},
+        }
+
+        for out in &state.out {
+            if let Some(card) = self.known[*out].take() {
+                self.revealed[*out].get_or_insert(card);
+            }
+            self.likelihood[*out] = [1.0; 10];
+        }
+        self.known[me] = None;
+        self.hand = state.hands[me];
+    }
+
+    /// How many copies of each card we have not seen, given the cards in our own `hand`.
+    /// These are in the deck, the removed card or the unknown hands of other players, out or not.
+    pub fn unseen(&self, state: &State, hand: &[Card]) -> [usize; 10] {
+        let mut counts = [0; 10];
+        for card in list_cards() {
+            counts[card.value()] = card.count();
+        }
+        let seen = state.discard.iter().flatten().chain(hand.iter()).chain(self.known.iter().flatten()).chain(self.revealed.iter().flatten());
+        for card in seen {
+            counts[card.value()] = counts[card.value()].saturating_sub(1);
+        }
+        counts
+    }
+
+    /// The chance that `player` holds each card.
+    pub fn hand_distribution(&self, state: &State, hand: &[Card], player: usize) -> Distribution {
+        let mut distribution = [0.0; 10];
+        if let Some(card) = self.known[player] {
+            distribution[card.value()] = 1.0;
+            return distribution;
+        }
+        let unseen = self.unseen(state, hand);
+        for (value, count) in unseen.iter().enumerate() {
+            distribution[value] = *count as f64 * self.likelihood[player][value];
+        }
+        let total: f64 = distribution.iter().sum();
+        if total == 0.0 {
+            return distribution;
+        }
+        distribution.map(|weight| weight / total)
+    }
+}
+
+/// The chance that a card drawn from `distribution` has a value lower and higher than `card`.
+pub fn compare(distribution: &Distribution, card: &Card) -> (f64, f64) {
+    let lower = distribution.iter().take(card.value()).sum();
+    let higher = distribution.iter().skip(card.value() + 1).sum();
+    (lower, higher)
+}
+
+pub fn expected_value(distribution: &Distribution) -> f64 {
+    distribution.iter().enumerate().map(|(value, chance)| value as f64 * chance).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::create_deck;
+
+    #[test]
+    fn test_wilkins_and_time() {
+        let mut state = State {
+            deck: create_deck(),
+            discard: vec![vec![], vec![], vec![]],
+            players: vec!["Ann".to_string(), "Bob".to_string(), "Cid".to_string()],
+            out: Vec::new(),
+            hands: vec![Some(Card::Guard), Some(Card::Nobody), Some(Card::Alice)],
+            tokens: vec![0, 0, 0],
+            round: 1,
+            turn: 0,
+            dormouse: None,
+        };
+        let mut ann = Knowledge::new(&state, 0);
+        let mut cid = Knowledge::new(&state, 2);
+
+        // Ann looks at Bob's hand, Cid does not see it
+        state.discard[0].push(Card::Wilkins);
+        let event = Event::Revealed { player: 0, target: 1, card: Card::Nobody };
+        ann.observe(&state, &event);
+        cid.observe(&state, &event);
+        assert_eq!(ann.known[1], Some(Card::Nobody));
+        assert_eq!(cid.known[1], None);
+        assert_eq!(ann.hand_distribution(&state, &[Card::Guard], 1)[Card::Nobody.value()], 1.0);
+
+        // Cid swaps their Alice for Ann's Guard, both know what the other has now
+        state.discard[2].push(Card::Time);
+        state.hands.swap(0, 2);
+        let event = Event::Swapped { player: 2, target: 0 };
+        ann.observe(&state, &event);
+        cid.observe(&state, &event);
+        assert_eq!(cid.known[0], Some(Card::Alice));
+        assert_eq!(ann.known[2], Some(Card::Guard));
+        assert_eq!(ann.hand, Some(Card::Alice));
+
+        // Bob discards the Nobody Ann knew about
+        state.discard[1].push(Card::Nobody);
+        ann.observe(&state, &Event::Protected { player: 1 });
+        assert_eq!(ann.known[1], None);
+        let unseen = ann.unseen(&state, &[Card::Alice]);
+        assert_eq!(unseen[Card::Nobody.value()], 1);
+        assert_eq!(unseen[Card::Guard.value()], 5);
+    }
+
+    #[test]
+    fn test_tendencies() {
+        let state = State {
+            deck: create_deck(),
+            discard: vec![vec![], vec![Card::Nobody]],
+            players: vec!["Ann".to_string(), "Bob".to_string()],
+            out: Vec::new(),
+            hands: vec![Some(Card::Time), Some(Card::Guard)],
+            tokens: vec![0, 0],
+            round: 1,
+            turn: 0,
+            dormouse: None,
+        };
+
+        // Bob always plays a Guard before a Nobody, so a Nobody would not have been played over one
+        let mut bob = Tendencies::default();
+        bob.held[Card::Guard.value()][Card::Nobody.value()] = 18;
+        bob.held[Card::Nobody.value()][Card::Guard.value()] = 18;
+        bob.played[Card::Guard.value()][Card::Nobody.value()] = 18;
+        let mut model = OpponentModel::default();
+        model.players.insert("Bob".to_string(), bob);
+
+        let mut ann = Knowledge::new(&state, 0);
+        let before = ann.hand_distribution(&state, &[Card::Time], 1);
+        ann.use_model(&state, &model);
+        ann.observe(&state, &Event::Protected { player: 1 });
+        let after = ann.hand_distribution(&state, &[Card::Time], 1);
+        assert!(after[Card::Guard.value()] < before[Card::Guard.value()] / 5.0);
+        assert!((after.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,266 @@
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use crate::agent::Agent;
+use crate::belief::{compare, expected_value, Knowledge};
+use crate::card::Card;
+use crate::game::{Action, Event, ExecutionerChoice, State};
+use crate::model::OpponentModel;
+use crate::profile::Profile;
+use crate::solver::{win_chances, ENDGAME_DECK, MAX_ASSIGNMENTS};
+
+/// Plays a uniformly random legal action.
+pub struct RandomBot {
+    rng: StdRng,
+}
+
+impl RandomBot {
+    pub fn new(seed: u64) -> RandomBot {
+        RandomBot { rng: StdRng::seed_from_u64(seed) }
+    }
+}
+
+impl Agent for RandomBot {
+    fn choose_action(&mut self, _state: &State, _hand: [Card; 2], legal: &[Action]) -> Action {
+        *legal.choose(&mut self.rng).unwrap()
+    }
+
+    fn choose_executioner(&mut self, _state: &State, _hand: Card, drawn: &[Card]) -> ExecutionerChoice {
+        ExecutionerChoice { keep: self.rng.gen_range(0..drawn.len()), hand_on_top: self.rng.gen() }
+    }
+}
+
+/// How a heuristic bot likes to play. The default is the plain heuristic bot.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Style {
+    /// How much it goes for knocking players out with the Guard and the Tweedies.
+    pub aggression: f64,
+    /// From 0 to 1, how much it keeps Alice rather than passing it on with Time or an Executioner.
+    pub hold_alice: f64,
+    /// How readily it plays Time even when the trade looks bad.
+    pub time_bluff: f64,
+    /// How much it goes for the Dormouse token.
+    pub dormouse: f64,
+}
+
+impl Default for Style {
+    fn default() -> Style {
+        Style { aggression: 1.0, hold_alice: 0.5, time_bluff: 0.0, dormouse: 1.0 }
+    }
+}
+
+/// Scores every legal action with simple rules of thumb and what it knows about the other hands.
+pub struct HeuristicBot {
+    rng: StdRng,
+    knowledge: Option<Knowledge>,
+    endgame: bool,
+    style: Style,
+    model: Option<OpponentModel>,
+}
+
+impl HeuristicBot {
+    pub fn new(seed: u64) -> HeuristicBot {
+        HeuristicBot { rng: StdRng::seed_from_u64(seed), knowledge: None, endgame: false, style: Style::default(), model: None }
+    }
+
+    pub fn with_style(seed: u64, style: Style) -> HeuristicBot {
+        HeuristicBot { style, ..HeuristicBot::new(seed) }
+    }
+
+    /// A bot that plays the action with the best exact chance of winning the round once the deck
+    /// is down to `ENDGAME_DECK` cards, and by its rules of thumb before that.
+    pub fn with_endgame(seed: u64) -> HeuristicBot {
+        HeuristicBot { endgame: true, ..HeuristicBot::new(seed) }
+    }
+
+    /// How good `action` looks while holding `hand`. Higher is better.
+    pub fn score(&self, state: &State, hand: [Card; 2], action: &Action) -> f64 {
+        let me = state.turn;
+        let kept = if action.card == hand[0] { hand[1] } else { hand[0] };
+        let fresh;
+        let knowledge = match &self.knowledge {
+            Some(knowledge) => knowledge,
+            None => {
+                fresh = Knowledge::new(state, me);
+                &fresh
+            },
+        };
+        let distribution = |player: usize| knowledge.hand_distribution(state, &hand, player);
+        let style = &self.style;
+
+        // Keeping a high card wins the tie breaker
+        let mut score = 0.5 * kept.value() as f64;
+        score += match (action.card, action.target) {
+            (Card::Alice, _) => -100.0,
+            (Card::Guard, Some(target)) => 10.0 * style.aggression * distribution(target)[action.guess.unwrap().value()],
+            (Card::Tweedies, Some(target)) => {
+                let (lower, higher) = compare(&distribution(target), &kept);
+                10.0 * (style.aggression * lower - higher)
+            },
+            (Card::KnaveOfHearts, Some(target)) if target == me => {
+                if kept == Card::Alice {
+                    -100.0
+                } else {
+                    let unseen = knowledge.unseen(state, &hand);
+                    let total: usize = unseen.iter().sum();
+                    let average = unseen.iter().enumerate().map(|(value, count)| (value * count) as f64).sum::<f64>() / total.max(1) as f64;
+                    0.5 * (average - kept.value() as f64)
+                }
+            },
+            (Card::KnaveOfHearts, Some(target)) => {
+                let distribution = distribution(target);
+                10.0 * distribution[Card::Alice.value()] + 0.1 * expected_value(&distribution)
+            },
+            (Card::Time, Some(target)) => {
+                let passing_alice = if kept == Card::Alice { 10.0 * (0.5 - style.hold_alice) } else { 0.0 };
+                0.5 * (expected_value(&distribution(target)) - kept.value() as f64) + passing_alice + 2.0 * style.time_bluff
+            },
+            (Card::Wilkins, Some(target)) if knowledge.known[target].is_none() => 1.5,
+            (Card::Nobody, _) => 1.0,
+            (Card::Executioner, _) => 1.0,
+            (Card::Dormouse, _) => match state.dormouse {
+                None => 1.5 * style.dormouse,
+                Some(player) if player == me => -1.0,
+                Some(_) => style.dormouse,
+            },
+            _ => 0.0,
+        };
+
+        // Keeping the card the players we know guess the most is asking to be knocked out
+        let guessed = knowledge.tendencies.iter().enumerate()
+            .filter(|(player, _)| *player != me && !state.out.contains(player))
+            .filter_map(|(_, tendencies)| tendencies.as_ref().map(|tendencies| tendencies.guess_rate(kept)))
+            .fold(0.0, f64::max);
+        score -= 5.0 * guessed;
+        score
+    }
+
+    /// The score of every action in `legal`, counting the exact chance of winning the round
+    /// for the endgame bot.
+    pub fn scores(&self, state: &State, hand: [Card; 2], legal: &[Action]) -> Vec<f64> {
+        let mut scores: Vec<f64> = legal.iter().map(|action| self.score(state, hand, action)).collect();
+        if let Some(knowledge) = self.knowledge.as_ref().filter(|_| self.endgame && state.deck.len() <= ENDGAME_DECK) {
+            // The rules of thumb only break ties between equally good actions
+            if let Some(chances) = win_chances(state, knowledge, hand, MAX_ASSIGNMENTS) {
+                for (action, chance) in chances {
+                    if let Some(i) = legal.iter().position(|legal| *legal == action) {
+                        scores[i] += 1000.0 * chance;
+                    }
+                }
+            }
+        }
+        scores
+    }
+
+    /// Lets the bot use what `model` learned about the players it meets, from the next round on.
+    pub fn set_model(&mut self, model: OpponentModel) {
+        self.model = Some(model);
+    }
+
+    /// Replaces what the bot knows about the round, to evaluate a position of another player.
+    pub fn set_knowledge(&mut self, knowledge: Knowledge) {
+        self.knowledge = Some(knowledge);
+    }
+}
+
+impl Agent for HeuristicBot {
+    fn start_round(&mut self, state: &State, seat: usize) {
+        let mut knowledge = Knowledge::new(state, seat);
+        if let Some(model) = &self.model {
+            knowledge.use_model(state, model);
+        }
+        self.knowledge = Some(knowledge);
+    }
+
+    fn choose_action(&mut self, state: &State, hand: [Card; 2], legal: &[Action]) -> Action {
+        let scores = self.scores(state, hand, legal);
+        let best = scores.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let choices: Vec<&Action> = legal.iter().zip(scores.iter()).filter(|(_, score)| **score >= best - 1e-9).map(|(action, _)| action).collect();
+        let action = **choices.choose(&mut self.rng).unwrap();
+        if let Some(knowledge) = self.knowledge.as_mut() {
+            knowledge.hand = Some(if action.card == hand[0] { hand[1] } else { hand[0] });
+        }
+        action
+    }
+
+    fn choose_executioner(&mut self, _state: &State, _hand: Card, drawn: &[Card]) -> ExecutionerChoice {
+        // Alice is the highest card, but a bot that does not like holding it keeps another one
+        let avoid_alice = self.style.hold_alice < 0.5 && drawn.iter().any(|card| *card != Card::Alice);
+        let keep = (0..drawn.len()).filter(|i| !avoid_alice || drawn[*i] != Card::Alice).max_by_key(|i| drawn[*i].value()).unwrap();
+        if let Some(knowledge) = self.knowledge.as_mut() {
+            knowledge.hand = Some(drawn[keep]);
+        }
+        ExecutionerChoice { keep, hand_on_top: true }
+    }
+
+    fn observe(&mut self, state: &State, event: &Event) {
+        if let Some(knowledge) = self.knowledge.as_mut() {
+            knowledge.observe(state, event);
+        }
+    }
+}
+
+/// Names of the bots `make_bot` knows.
+pub fn bot_names() -> Vec<String> {
+    vec!["random".to_string(), "heuristic".to_string(), "endgame".to_string()]
+}
+
+pub fn make_bot(name: &str, seed: u64) -> Option<Box<dyn Agent>> {
+    match name {
+        "random" => Some(Box::new(RandomBot::new(seed))),
+        "heuristic" => Some(Box::new(HeuristicBot::new(seed))),
+        "endgame" => Some(Box::new(HeuristicBot::with_endgame(seed))),
+        _ => None,
+    }
+}
+
+/// Like `make_bot`, also knowing the heuristic bots of `profiles` by their names. Heuristic bots
+/// use what `model` learned about the players they meet, if given.
+pub fn make_profile_bot(name: &str, seed: u64, profiles: &[Profile], model: Option<&OpponentModel>) -> Option<Box<dyn Agent>> {
+    let mut bot = match (profiles.iter().find(|profile| profile.name == name), name) {
+        (Some(profile), _) => HeuristicBot::with_style(seed, profile.style),
+        (None, "heuristic") => HeuristicBot::new(seed),
+        (None, "endgame") => HeuristicBot::with_endgame(seed),
+        (None, _) => return make_bot(name, seed),
+    };
+    if let Some(model) = model {
+        bot.set_model(model.clone());
+    }
+    Some(Box::new(bot))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::create_deck;
+    use crate::game::legal_actions;
+    use crate::model::Tendencies;
+
+    #[test]
+    fn test_model_changes_the_choice() {
+        let state = State {
+            deck: create_deck(),
+            discard: vec![vec![], vec![]],
+            players: vec!["Ann".to_string(), "Bob".to_string()],
+            out: Vec::new(),
+            hands: vec![Some(Card::Nobody), Some(Card::Guard)],
+            tokens: vec![0, 0],
+            round: 1,
+            turn: 0,
+            dormouse: None,
+        };
+        let hand = [Card::Nobody, Card::Wilkins];
+        let legal = legal_actions(&state, hand);
+        let choose = |model: Option<&OpponentModel>| {
+            let mut bot = make_profile_bot("heuristic", 1, &[], model).unwrap();
+            bot.start_round(&state, 0);
+            bot.choose_action(&state, hand, &legal).card
+        };
+        // Ann looks at Bob's hand, unless Bob is known to guess the Nobody she would keep
+        assert_eq!(choose(None), Card::Wilkins);
+        let mut bob = Tendencies::default();
+        bob.guesses[Card::Nobody.value()] = 50;
+        let model = OpponentModel { players: [("Bob".to_string(), bob)].into_iter().collect() };
+        assert_eq!(choose(Some(&model)), Card::Nobody);
+    }
+}
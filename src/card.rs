@@ -0,0 +1,140 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Card {
+    Alice, // 9 - (1) If discarded you are out.
+    RedQueen, // 8 - (1) Must be discarded if you have (7) Time or (5) Knave of Hearts in your hand
+    Time, // 7 - (1) Trade hands with another player.
+    Executioner, // 6 - (2) Draw 2 cards, pick one and place 2 cards on the bottom of the deck.
+    KnaveOfHearts, // 5 - (2) Discard a player's hand (including your own) and make them draw a new card.
+    Nobody, // 4 - (2) Protection until your next turn.
+    Tweedies, // 3 - (2) Compare hands with another player. Lowest hand is out.
+    Wilkins, // 2 - (2) Look at another player's hand.
+    Guard, // 1 - (6) Guess a player's hand and if correct, player is out.
+    Dormouse, // 0 - (2) Gain one token if no one else discarded a dormouse by the end of the round.
+}
+
+
+impl Card {
+    pub fn value(&self) -> usize {
+        match self {
+            Card::Alice => 9,
+            Card::RedQueen => 8,
+            Card::Time => 7,
+            Card::Executioner => 6,
+            Card::KnaveOfHearts => 5,
+            Card::Nobody => 4,
+            Card::Tweedies => 3,
+            Card::Wilkins => 2,
+            Card::Guard => 1,
+            Card::Dormouse => 0,
+        }
+    }
+
+    pub fn from_value(value: usize) -> Option<Card> {
+        list_cards().into_iter().find(|card| card.value() == value)
+    }
+
+    pub fn count(&self) -> usize {
+        match self {
+            Card::Alice => 1,
+            Card::RedQueen => 1,
+            Card::Time => 1,
+            Card::KnaveOfHearts => 2,
+            Card::Executioner => 2,
+            Card::Nobody => 2,
+            Card::Tweedies => 2,
+            Card::Wilkins => 2,
+            Card::Guard => 6,
+            Card::Dormouse => 2,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Card::Alice => "Alice".to_string(),
+            Card::RedQueen => "The Red Queen".to_string(),
+            Card::Time => "Time".to_string(),
+            Card::Executioner => "Executioner".to_string(),
+            Card::KnaveOfHearts => "Knave of Hearts".to_string(),
+            Card::Nobody => "Nobody".to_string(),
+            Card::Tweedies => "The Tweedies".to_string(),
+            Card::Wilkins => "Wilkins".to_string(),
+            Card::Guard => "Guard".to_string(),
+            Card::Dormouse => "The Dormouse".to_string(),
+        }
+    }
+
+    pub fn description(&self) -> String {
+        match self {
+            Card::Alice => "If discarded you are out.".to_string(),
+            Card::RedQueen => "Must be discarded if you have (6) or (7) in your hand.".to_string(),
+            Card::Time => "Trade hands with another player.".to_string(),
+            Card::Executioner => "Draw 2 cards, pick one and place 2 cards on the bottom of the deck.".to_string(),
+            Card::KnaveOfHearts => "Discard a player's hand (including your own) and make them draw a new card.".to_string(),
+            Card::Nobody => "Protection until your next turn.".to_string(),
+            Card::Tweedies => "Compare hands with another player. Lowest hand is out.".to_string(),
+            Card::Wilkins => "Look at another player's hand.".to_string(),
+            Card::Guard => "Guess a player's hand and if correct, player is out.".to_string(),
+            Card::Dormouse => "Gain one token if no one else discarded a dormouse by the end of the round.".to_string(),
+        }
+    }
+
+    pub fn targetting(&self) -> bool {
+        matches!(self, Card::Time | Card::KnaveOfHearts | Card::Tweedies | Card::Wilkins | Card::Guard)
+    }
+
+    pub fn can_target_self(&self) -> bool {
+        matches!(self, Card::KnaveOfHearts)
+    }
+
+    pub fn protects(&self) -> bool {
+        matches!(self, Card::Nobody)
+    }
+}
+
+impl fmt::Display for Card {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} - {} (x{}): {}", self.value(), self.name(), self.count(), self.description())
+    }
+}
+
+/// All cards from highest to lowest value.
+pub fn list_cards() -> Vec<Card> {
+    vec![
+        Card::Alice,
+        Card::RedQueen,
+        Card::Time,
+        Card::Executioner,
+        Card::KnaveOfHearts,
+        Card::Nobody,
+        Card::Tweedies,
+        Card::Wilkins,
+        Card::Guard,
+        Card::Dormouse,
+    ]
+}
+
+pub fn create_deck() -> Vec<Card> {
+    let mut deck: Vec<Card> = Vec::new();
+    for card in list_cards() {
+        for _ in 0..card.count() {
+            deck.push(card);
+        }
+    }
+    deck
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_value() {
+        for card in list_cards() {
+            assert_eq!(Card::from_value(card.value()), Some(card));
+        }
+        assert_eq!(Card::from_value(10), None);
+        assert_eq!(create_deck().len(), 21);
+    }
+}
@@ -0,0 +1,372 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, UdpSocket};
+use std::time::{Duration, Instant};
+
+use crate::agent::Agent;
+use crate::card::Card;
+use crate::discovery::{self, Found, DEFAULT_WAIT, DISCOVERY_PORT};
+use crate::engine::{action_token, parse_action_token};
+use crate::game::{Action, State};
+use crate::human::{print_table, read_line, Human};
+use crate::options::Options;
+use crate::server::DEFAULT_PORT;
+
+/// What the server last told us about the table, read from the lines of the protocol in
+/// `server.rs`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Table {
+    pub names: Vec<String>,
+    pub seat: usize,
+    pub round: i32,
+    pub deck: usize,
+    pub tokens: Vec<i32>,
+    pub out: Vec<bool>,
+    pub known: Vec<Option<Card>>,
+    pub dormouse: Option<usize>,
+    pub discard: Vec<Vec<Card>>,
+    pub hand: Vec<Card>,
+    pub drawn: Vec<Card>,
+    pub legal: Vec<Action>,
+    /// Every hand and the deck, top card first, in the omniscient view of spectators.
+    pub hands: Vec<Option<Card>>,
+    pub order: Vec<Card>,
+}
+
+impl Table {
+    /// Takes in one line of a view or decision. False if it is not one.
+    pub fn read(&mut self, line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((keyword, rest)) = words.split_first() else {
+            return false;
+        };
+        let numbers = || rest.iter().filter_map(|word| word.parse().ok()).collect::<Vec<usize>>();
+        let cards = || rest.iter().filter_map(|word| word.parse().ok().and_then(Card::from_value)).collect::<Vec<Card>>();
+        match *keyword {
+            "names" => self.names = rest.iter().map(|name| name.to_string()).collect(),
+            "view" => {
+                let numbers = numbers();
+                if numbers.len() == 4 {
+                    (self.seat, self.round, self.deck) = (numbers[0], numbers[2] as i32, numbers[3]);
+                    self.discard = vec![Vec::new(); numbers[1]];
+                }
+            },
+            "table" => {
+                let numbers = numbers();
+                if numbers.len() == 4 {
+                    (self.seat, self.round, self.deck) = (numbers[1], numbers[0] as i32, numbers[2]);
+                    self.discard = vec![Vec::new(); numbers[3]];
+                    self.hands.clear();
+                    self.order.clear();
+                }
+            },
+            "tokens" => self.tokens = rest.iter().filter_map(|word| word.parse().ok()).collect(),
+            "out" => self.out = rest.iter().map(|flag| *flag == "1").collect(),
+            "protected" => {},
+            "dormouse" => self.dormouse = rest.first().and_then(|seat| seat.parse().ok()),
+            "known" => self.known = rest.iter().map(|card| card.parse().ok().and_then(Card::from_value)).collect(),
+            "discard" => {
+                if let Some((seat, pile)) = rest.split_first() {
+                    let seat: usize = seat.parse().unwrap_or(usize::MAX);
+                    if seat < self.discard.len() {
+                        self.discard[seat] = pile.iter().filter_map(|card| card.parse().ok().and_then(Card::from_value)).collect();
+                    }
+                }
+            },
+            "hand" => self.hand = cards(),
+            "drawn" => self.drawn = cards(),
+            "hands" => self.hands = rest.iter().map(|card| card.parse().ok().and_then(Card::from_value)).collect(),
+            "order" => self.order = cards(),
+            "legal" => self.legal = rest.iter().filter_map(|token| parse_action_token(token)).collect(),
+            _ => return false,
+        }
+        true
+    }
+
+    /// The table as a state with us to act. The deck and the hands we cannot see hold stand-in
+    /// cards, which nothing shown to the player uses.
+    pub fn state(&self) -> State {
+        let players = self.discard.len();
+        let names = if self.names.len() == players { self.names.clone() } else { (0..players).map(|seat| format!("Seat {}", seat + 1)).collect() };
+        let hands = (0..players).map(|seat| {
+            if seat == self.seat {
+                self.hand.first().copied()
+            } else {
+                Some(self.known.get(seat).copied().flatten().unwrap_or(Card::Guard))
+            }
+        }).collect();
+        State {
+            deck: vec![Card::Guard; self.deck],
+            discard: self.discard.clone(),
+            players: names,
+            out: (0..players).filter(|seat| self.out.get(*seat) == Some(&true)).collect(),
+            hands,
+            tokens: self.tokens.clone(),
+            round: self.round,
+            turn: self.seat,
+            dormouse: self.dormouse,
+        }
+    }
+}
+
+/// What the client prints for a line it shows the same way while playing and watching: the
+/// events, the errors and the winner of the game.
+fn shown(line: &str) -> Option<String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let text = line.split_once(' ').map_or("", |(_, text)| text);
+    match words[..] {
+        ["info", ..] | ["error", ..] => Some(text.to_string()),
+        ["winner", "-"] => Some("The game is over without a winner.".to_string()),
+        ["winner", winner] => Some(format!("{} won the game!", winner)),
+        _ => None,
+    }
+}
+
+/// Plays on a server as the human at this terminal until the game is over, coming back to the
+/// seat of the session token `resume` if given.
+pub fn play(stream: TcpStream, name: &str, resume: Option<&str>) -> Result<(), String> {
+    let mut writer = stream.try_clone().map_err(|error| error.to_string())?;
+    let mut send = |line: String| writeln!(writer, "{}", line).map_err(|error| format!("Lost the server: {}", error));
+    let mut human = Human::alone();
+    let mut table = Table::default();
+    let mut deadline: Option<Instant> = None;
+    let late = |deadline: Option<Instant>| deadline.is_some_and(|deadline| Instant::now() >= deadline);
+    for line in BufReader::new(stream).lines() {
+        let line = line.map_err(|error| format!("Lost the server: {}", error))?;
+        if table.read(&line) {
+            continue;
+        }
+        if let Some(text) = shown(&line) {
+            println!("{}", text);
+            continue;
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let text = line.split_once(' ').map_or("", |(_, text)| text);
+        match words[..] {
+            ["loveletter", _] => match resume {
+                Some(token) => send(format!("resume {}", token))?,
+                None => send(format!("join {}", name))?,
+            },
+            ["welcome", name] => println!("Joined as {}.", name),
+            ["session", token] => println!("If you lose the connection, come back with --resume {}", token),
+            ["wait", joined, players] => println!("{} of {} players are here.", joined, players),
+            ["room", id, players, start, seated, ready, status] => {
+                println!("Room {}: {} of {} seated, {} ready, {} starts rounds, {}.", id, seated, players, ready, start, status);
+            },
+            ["lobby", room] => {
+                match room {
+                    "-" => println!("You are in the lobby."),
+                    room => println!("You are in room {}.", room),
+                }
+                println!("Type rooms, create <players> [rotate|winner|loser|random], enter <room>, leave or ready.");
+                print!(": ");
+                let Some(command) = read_line() else {
+                    return Ok(());
+                };
+                send(command.trim().to_string())?;
+            },
+            ["round", round, "seat", _, "players", _] => println!("\nRound {} starts.", round),
+            ["clock", left, bank] => {
+                let left = Duration::from_millis(left.parse().unwrap_or(0));
+                deadline = Instant::now().checked_add(left);
+                human.start_clock(left, bank.parse().ok().map(Duration::from_millis));
+            },
+            ["timeout"] => println!("You ran out of time, so the default was played for you."),
+            ["go", "play"] => {
+                let state = table.state();
+                let hand = [table.hand[0], table.hand[1]];
+                let action = human.choose_action(&state, hand, &table.legal);
+                if !late(deadline.take()) {
+                    send(format!("play {}", action_token(&action)))?;
+                }
+            },
+            ["go", "keep"] => {
+                let state = table.state();
+                let choice = human.choose_executioner(&state, table.hand[0], &table.drawn);
+                if !late(deadline.take()) {
+                    send(format!("keep {} {}", choice.keep, if choice.hand_on_top { "top" } else { "bottom" }))?;
+                }
+            },
+            ["result", ..] => {
+                let (tokens, winners) = text.split_once("winners").unwrap_or((text, ""));
+                let tokens: Vec<&str> = tokens.split_whitespace().collect();
+                let name = |seat: &str| seat.parse().ok().and_then(|seat: usize| table.names.get(seat).cloned()).unwrap_or(seat.to_string());
+                let winners: Vec<String> = winners.split_whitespace().map(name).collect();
+                println!("{} won the round.", winners.join(" and "));
+                let standings: Vec<String> = table.names.iter().zip(tokens.iter()).map(|(name, tokens)| format!("{} {}", name, tokens)).collect();
+                println!("Tokens: {}.", standings.join(", "));
+            },
+            ["quit"] => return Ok(()),
+            _ => {},
+        }
+    }
+    Err("The server closed the connection.".to_string())
+}
+
+/// Watches a game on a server, with every hand and the deck if `omniscient`, until it is over.
+pub fn watch(stream: TcpStream, omniscient: bool) -> Result<(), String> {
+    let mut writer = stream.try_clone().map_err(|error| error.to_string())?;
+    let mut table = Table::default();
+    for line in BufReader::new(stream).lines() {
+        let line = line.map_err(|error| format!("Lost the server: {}", error))?;
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let text = line.split_once(' ').map_or("", |(_, text)| text);
+        if table.read(&line) {
+            // The discard pile of the last seat ends the table
+            if words[0] == "discard" && words.get(1) == Some(&table.discard.len().saturating_sub(1).to_string().as_str()) {
+                print_table(&table.state());
+                let names = table.names.iter();
+                let hands: Vec<String> = names.zip(&table.hands).map(|(name, card)| format!("{} {}", name, card.map_or("-".to_string(), |card| card.name()))).collect();
+                if !hands.is_empty() {
+                    println!("Hands: {}.", hands.join(", "));
+                    let order: Vec<String> = table.order.iter().map(|card| card.name()).collect();
+                    println!("Deck from the top: {}.\n", order.join(", "));
+                }
+            }
+            continue;
+        }
+        if let Some(text) = shown(&line) {
+            println!("{}", text);
+            continue;
+        }
+        match words[..] {
+            ["loveletter", _] => writeln!(writer, "watch{}", if omniscient { " all" } else { "" }).map_err(|error| format!("Lost the server: {}", error))?,
+            ["watching", "public"] => println!("Watching the game."),
+            ["watching", "all"] => println!("Watching the game with every hand, each round once it is over."),
+            ["result", ..] => {
+                let winners = text.split_once("winners").map_or("", |(_, winners)| winners);
+                let name = |seat: &str| seat.parse().ok().and_then(|seat: usize| table.names.get(seat).cloned()).unwrap_or(seat.to_string());
+                let winners: Vec<String> = winners.split_whitespace().map(name).collect();
+                println!("{} won the round.", winners.join(" and "));
+            },
+            ["quit"] => return Ok(()),
+            _ => {},
+        }
+    }
+    Err("The server closed the connection.".to_string())
+}
+
+/// Prints the servers found on the network and their games, numbered from 1.
+pub fn print_found(found: &[Found]) {
+    for (number, server) in found.iter().enumerate() {
+        let announcement = &server.announcement;
+        let protocol = match announcement.protocol.as_str() {
+            "lines" => String::new(),
+            protocol => format!(" ({} clients only)", protocol),
+        };
+        println!("{}. {} at {}:{}{}", number + 1, announcement.name, server.address, announcement.port, protocol);
+        if announcement.games.is_empty() {
+            println!("\tNo rooms yet: open one in the lobby.");
+        }
+        for game in &announcement.games {
+            let table = game.room.map_or("Table".to_string(), |room| format!("Room {}", room));
+            let status = if game.playing { "playing".to_string() } else { format!("{} free", game.free()) };
+            println!("\t{}: {} of {} seated, {}, {}.", table, game.seated, game.players, status, game.rules);
+        }
+    }
+}
+
+/// Listens for `--wait` seconds for the servers on the local network and lists their games.
+/// Given `--name`, joins the one picked.
+fn find(options: &Options) -> Result<(), String> {
+    let wait = Duration::from_secs_f64(options.get("wait", DEFAULT_WAIT.as_secs_f64())?.max(0.0));
+    let socket = UdpSocket::bind(("0.0.0.0", DISCOVERY_PORT)).map_err(|error| format!("Could not listen for games on port {}: {}", DISCOVERY_PORT, error))?;
+    println!("Looking for games on the network...");
+    let found: Vec<Found> = discovery::find(&socket, wait)?;
+    if found.is_empty() {
+        println!("No games found. The server has to be started with --announce.");
+        return Ok(());
+    }
+    print_found(&found);
+    let Some(name) = options.value("name") else {
+        println!("Join one with --host and --port, or find again with --name to pick one.");
+        return Ok(());
+    };
+    loop {
+        print!("Join which server? ");
+        let answer = read_line().unwrap_or_default();
+        if answer.trim().is_empty() {
+            return Ok(());
+        }
+        match answer.trim().parse::<usize>().ok().and_then(|number| found.get(number.wrapping_sub(1))) {
+            Some(server) if server.announcement.protocol == "lines" => {
+                let address = (server.address, server.announcement.port);
+                let stream = TcpStream::connect(address).map_err(|error| format!("Could not connect to {}:{}: {}", address.0, address.1, error))?;
+                return play(stream, name, None);
+            },
+            Some(_) => println!("This client cannot join that server."),
+            None => println!("Type the number of a server, or nothing to quit."),
+        }
+    }
+}
+
+/// `connect`: joins the game on `--host` and `--port` as `--name`, or comes back to a seat with
+/// `--resume token`. With `--watch` it watches the game instead, seeing every hand with `--all`
+/// if the server allows it. With `--find` it lists the games announced on the local network.
+pub fn command(options: &Options) -> Result<(), String> {
+    if options.flag("find") {
+        return find(options);
+    }
+    let host = options.value("host").unwrap_or("127.0.0.1");
+    let port: u16 = options.get("port", DEFAULT_PORT)?;
+    if options.flag("watch") {
+        let stream = TcpStream::connect((host, port)).map_err(|error| format!("Could not connect to {}:{}: {}", host, port, error))?;
+        return watch(stream, options.flag("all"));
+    }
+    let resume = options.value("resume");
+    let name = match resume {
+        Some(_) => "",
+        None => options.value("name").ok_or("Give your name with --name.")?,
+    };
+    let stream = TcpStream::connect((host, port)).map_err(|error| format!("Could not connect to {}:{}: {}", host, port, error))?;
+    play(stream, name, resume)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::belief::Knowledge;
+    use crate::engine::decision_lines;
+    use crate::env::Decision;
+    use crate::game::{deal, legal_actions, Event};
+    use crate::protocol::Message;
+    use rand::prelude::*;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn test_table() {
+        let mut state = deal(vec!["Ann".to_string(), "Bob".to_string(), "Cid".to_string()], vec![1, 0, 2], 3, &mut StdRng::seed_from_u64(1));
+        state.turn = 1;
+        state.out = vec![2];
+        state.discard[0] = vec![Card::Guard, Card::Nobody];
+        let held = state.hands[1].unwrap();
+        let drawn = state.deck.pop().unwrap();
+        let legal = legal_actions(&state, [held, drawn]);
+
+        let mut table = Table::default();
+        table.read("names Ann Bob Cid");
+        for line in decision_lines(&state, &Knowledge::new(&state, 1), &Decision::Play { hand: [held, drawn] }, &legal) {
+            if !line.starts_with("go") {
+                assert!(table.read(&line), "{}", line);
+            }
+        }
+        let view = table.state();
+        assert_eq!(view.players, state.players);
+        assert_eq!(view.discard, state.discard);
+        assert_eq!(view.out, state.out);
+        assert_eq!(view.tokens, state.tokens);
+        assert_eq!(view.deck.len(), state.deck.len());
+        assert_eq!((view.turn, view.round), (1, 3));
+        assert_eq!(table.hand, vec![held, drawn]);
+        assert_eq!(table.legal, legal);
+    }
+
+    #[test]
+    fn test_events_of_several_lines() {
+        let names = vec!["Ann".to_string(), "Bob".to_string()];
+        let text = Event::Revealed { player: 0, target: 1, card: Card::Nobody }.describe(&names, 0).unwrap();
+        let lines = Message::Event { text: text.clone() }.lines();
+        assert!(lines.len() > 1);
+        let printed: Vec<String> = lines.iter().filter_map(|line| shown(line)).collect();
+        assert_eq!(printed.join("\n"), text);
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use rand::prelude::*;
+
+use crate::agent::Agent;
+use crate::card::Card;
+use crate::game::{Action, Event, ExecutionerChoice, PlayError, State};
+use crate::options::Options;
+
+/// What is played for a player who runs out of time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeoutAction {
+    /// Discard the lower card, without a target if it can be played without one.
+    Lower,
+    /// Any legal action.
+    Random,
+}
+
+impl TimeoutAction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TimeoutAction::Lower => "lower",
+            TimeoutAction::Random => "random",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<TimeoutAction> {
+        [TimeoutAction::Lower, TimeoutAction::Random].into_iter().find(|action| action.name() == name)
+    }
+}
+
+/// The time limits of a match: a limit for every decision, a bank of time every player spends
+/// from over the whole match like a chess clock, or both. A player whose bank is empty gets the
+/// default action for every decision left.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Clock {
+    pub decision: Option<Duration>,
+    pub bank: Option<Duration>,
+    pub timeout: TimeoutAction,
+}
+
+impl Clock {
+    /// The clock of `--turn-time` and `--bank`, both in seconds, with `--on-timeout` lower or
+    /// random. `None` if neither limit is given.
+    pub fn from_options(options: &Options) -> Result<Option<Clock>, String> {
+        let seconds = |name: &str| -> Result<Option<Duration>, String> {
+            match options.value(name) {
+                Some(_) => Ok(Some(Duration::from_secs_f64(options.get(name, 0.0f64)?.max(0.0)))),
+                None => Ok(None),
+            }
+        };
+        let (decision, bank) = (seconds("turn-time")?, seconds("bank")?);
+        let timeout = options.value("on-timeout").unwrap_or("lower");
+        let timeout = TimeoutAction::from_name(timeout).ok_or(format!("\"{}\" is not a timeout action. Use lower or random.", timeout))?;
+        Ok((decision.is_some() || bank.is_some()).then_some(Clock { decision, bank, timeout }))
+    }
+
+    /// What is played for a player who runs out of time on `legal`.
+    pub fn default_action(&self, legal: &[Action]) -> Action {
+        match self.timeout {
+            TimeoutAction::Lower => *legal.iter().min_by_key(|action| (action.card.value(), action.target.is_some())).unwrap(),
+            TimeoutAction::Random => *legal.choose(&mut thread_rng()).unwrap(),
+        }
+    }
+
+    /// What is kept for a player who runs out of time after playing the Executioner.
+    pub fn default_executioner(&self, drawn: &[Card]) -> ExecutionerChoice {
+        match self.timeout {
+            TimeoutAction::Lower => ExecutionerChoice { keep: 0, hand_on_top: true },
+            TimeoutAction::Random => ExecutionerChoice { keep: thread_rng().gen_range(0..drawn.len()), hand_on_top: random() },
+        }
+    }
+}
+
+/// An agent playing against the clock. The agent is told when its time is up through
+/// `Agent::start_clock`, decisions made too late are replaced by the default action of the clock,
+/// and the time spent is taken from the bank of the player, by name so it follows them from seat
+/// to seat.
+pub struct Timed {
+    pub agent: Box<dyn Agent>,
+    clock: Clock,
+    banks: HashMap<String, Duration>,
+}
+
+impl Timed {
+    pub fn new(agent: Box<dyn Agent>, clock: Clock) -> Timed {
+        Timed { agent, clock, banks: HashMap::new() }
+    }
+
+    /// Starts the clock of the player to act, returning how long they have.
+    fn start(&mut self, state: &State) -> Duration {
+        let bank = self.clock.bank.map(|bank| *self.banks.entry(state.players[state.turn].clone()).or_insert(bank));
+        let left = match (self.clock.decision, bank) {
+            (Some(decision), Some(bank)) => decision.min(bank),
+            (decision, bank) => decision.or(bank).unwrap_or(Duration::MAX),
+        };
+        if !left.is_zero() {
+            self.agent.start_clock(left, bank);
+        }
+        left
+    }
+
+    /// Stops the clock of the player to act, who took `spent`. False if they ran out of time.
+    fn stop(&mut self, state: &State, left: Duration, spent: Duration) -> bool {
+        if let Some(bank) = self.banks.get_mut(&state.players[state.turn]) {
+            *bank = bank.saturating_sub(spent);
+        }
+        if spent < left {
+            return true;
+        }
+        println!("{} ran out of time.", state.players[state.turn]);
+        self.agent.out_of_time(state);
+        false
+    }
+}
+
+impl Agent for Timed {
+    fn start_round(&mut self, state: &State, seat: usize) {
+        self.agent.start_round(state, seat);
+    }
+
+    fn choose_action(&mut self, state: &State, hand: [Card; 2], legal: &[Action]) -> Action {
+        let started = Instant::now();
+        let left = self.start(state);
+        let action = if left.is_zero() { None } else { Some(self.agent.choose_action(state, hand, legal)) };
+        match action {
+            Some(action) if self.stop(state, left, started.elapsed()) => action,
+            _ => self.clock.default_action(legal),
+        }
+    }
+
+    fn choose_executioner(&mut self, state: &State, hand: Card, drawn: &[Card]) -> ExecutionerChoice {
+        let started = Instant::now();
+        let left = self.start(state);
+        let choice = if left.is_zero() { None } else { Some(self.agent.choose_executioner(state, hand, drawn)) };
+        match choice {
+            Some(choice) if self.stop(state, left, started.elapsed()) => choice,
+            _ => self.clock.default_executioner(drawn),
+        }
+    }
+
+    fn invalid_action(&mut self, state: &State, action: &Action, error: &PlayError) {
+        self.agent.invalid_action(state, action, error);
+    }
+
+    fn observe(&mut self, state: &State, event: &Event) {
+        self.agent.observe(state, event);
+    }
+
+    fn end_round(&mut self, state: &State, winners: &[usize]) {
+        self.agent.end_round(state, winners);
+    }
+
+    fn forfeited(&self) -> Option<String> {
+        self.agent.forfeited()
+    }
+}
+
+/// `duration` as minutes and seconds, like a chess clock.
+pub fn format_time(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{deal, legal_actions};
+    use rand::rngs::StdRng;
+    use std::thread;
+
+    /// Takes `delay` over every decision and plays the highest card.
+    struct Slow {
+        delay: Duration,
+    }
+
+    impl Agent for Slow {
+        fn choose_action(&mut self, _state: &State, _hand: [Card; 2], legal: &[Action]) -> Action {
+            thread::sleep(self.delay);
+            *legal.iter().max_by_key(|action| action.card.value()).unwrap()
+        }
+
+        fn choose_executioner(&mut self, _state: &State, _hand: Card, _drawn: &[Card]) -> ExecutionerChoice {
+            ExecutionerChoice { keep: 0, hand_on_top: true }
+        }
+    }
+
+    #[test]
+    fn test_timed() {
+        let mut state = deal(vec!["Ann".to_string(), "Bob".to_string()], vec![0, 0], 1, &mut StdRng::seed_from_u64(1));
+        state.hands[0] = Some(Card::Guard);
+        let hand = [Card::Guard, Card::Nobody];
+        let legal = legal_actions(&state, hand);
+        assert_eq!(Clock { decision: None, bank: None, timeout: TimeoutAction::Lower }.default_action(&legal).card, Card::Guard);
+
+        // Within the time of a decision, the choice of the player stands
+        let clock = Clock { decision: Some(Duration::from_secs(1)), bank: Some(Duration::from_millis(500)), timeout: TimeoutAction::Lower };
+        let mut timed = Timed::new(Box::new(Slow { delay: Duration::from_millis(200) }), clock);
+        assert_eq!(timed.choose_action(&state, hand, &legal).card, Card::Nobody);
+        // The bank is the shorter limit: 300 ms are left for the second decision and 100 ms for
+        // the third, which runs out
+        assert_eq!(timed.choose_action(&state, hand, &legal).card, Card::Nobody);
+        assert_eq!(timed.choose_action(&state, hand, &legal).card, Card::Guard);
+        assert_eq!(timed.banks["Ann"], Duration::ZERO);
+        // An empty bank plays the default at once
+        let started = Instant::now();
+        assert_eq!(timed.choose_action(&state, hand, &legal).card, Card::Guard);
+        assert!(started.elapsed() < Duration::from_millis(100));
+        // Bob spends from another bank
+        state.turn = 1;
+        assert_eq!(timed.choose_action(&state, hand, &legal).card, Card::Nobody);
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::clock::{format_time, Clock};
+use crate::sim::StartPolicy;
+
+// A server started with `--announce` tells the local network about its games, so players can
+// find them with `connect --find` instead of typing addresses. Every second it broadcasts a UDP
+// datagram to port 4445:
+//
+// loveletter-games 1
+// server <port> <lines|websocket|http> <name of the server>
+// game <room, or - at a server without rooms> <players> <seated> <waiting|playing> <rules>
+//
+// with a `game` line for every table or room, and none at a lobby without rooms. The rules are
+// how rounds start and the time limits, if any.
+
+pub const DISCOVERY_PORT: u16 = 4445;
+const FORMAT: &str = "loveletter-games 1";
+/// How often a server announces its games.
+pub const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(1);
+/// How long `connect --find` listens for servers, unless set with `--wait`.
+pub const DEFAULT_WAIT: Duration = Duration::from_secs(2);
+
+/// A game on a server: a table or a room of its lobby.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Listing {
+    pub room: Option<usize>,
+    pub players: usize,
+    pub seated: usize,
+    pub playing: bool,
+    pub rules: String,
+}
+
+impl Listing {
+    /// The seats still open.
+    pub fn free(&self) -> usize {
+        if self.playing { 0 } else { self.players.saturating_sub(self.seated) }
+    }
+}
+
+/// What a server announces about itself.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Announcement {
+    pub name: String,
+    pub port: u16,
+    /// How clients talk to it: lines, websocket or http.
+    pub protocol: String,
+    pub games: Vec<Listing>,
+}
+
+impl Announcement {
+    pub fn write(&self) -> String {
+        let mut text = format!("{}\nserver {} {} {}\n", FORMAT, self.port, self.protocol, self.name);
+        for game in &self.games {
+            let room = game.room.map_or("-".to_string(), |room| room.to_string());
+            let status = if game.playing { "playing" } else { "waiting" };
+            text += &format!("game {} {} {} {} {}\n", room, game.players, game.seated, status, game.rules);
+        }
+        text
+    }
+
+    pub fn read(text: &str) -> Option<Announcement> {
+        let mut lines = text.lines();
+        if lines.next()? != FORMAT {
+            return None;
+        }
+        let words: Vec<&str> = lines.next()?.splitn(4, ' ').collect();
+        let ["server", port, protocol, name] = words[..] else { return None };
+        let mut announcement = Announcement { name: name.to_string(), port: port.parse().ok()?, protocol: protocol.to_string(), games: Vec::new() };
+        for line in lines {
+            let words: Vec<&str> = line.splitn(6, ' ').collect();
+            let ["game", room, players, seated, status, rules] = words[..] else { return None };
+            let room = if room == "-" { None } else { Some(room.parse().ok()?) };
+            let playing = status == "playing";
+            announcement.games.push(Listing { room, players: players.parse().ok()?, seated: seated.parse().ok()?, playing, rules: rules.to_string() });
+        }
+        Some(announcement)
+    }
+}
+
+/// The rules of a game, as announced: how rounds start and the time limits.
+pub fn rules(start: StartPolicy, clock: Option<Clock>) -> String {
+    let mut rules = vec![format!("{} start", start.name())];
+    if let Some(clock) = clock {
+        rules.extend(clock.decision.map(|decision| format!("{}s a turn", decision.as_secs_f64())));
+        rules.extend(clock.bank.map(|bank| format!("{} bank", format_time(bank))));
+    }
+    rules.join(", ")
+}
+
+/// Where and under what name a server announces its games.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Beacon {
+    pub name: String,
+    pub target: SocketAddr,
+}
+
+impl Beacon {
+    /// Announces to the whole local network, under `name`.
+    pub fn broadcast(name: &str) -> Beacon {
+        Beacon { name: name.to_string(), target: SocketAddr::new(IpAddr::V4(Ipv4Addr::BROADCAST), DISCOVERY_PORT) }
+    }
+
+    /// Announces the games `games` lists every `ANNOUNCE_INTERVAL` on a thread of its own, for
+    /// the server on `port` that speaks `protocol`, until `games` returns `None`.
+    pub fn start(self, port: u16, protocol: &str, mut games: impl FnMut() -> Option<Vec<Listing>> + Send + 'static) -> Result<(), String> {
+        let socket = UdpSocket::bind(("0.0.0.0", 0)).map_err(|error| format!("Could not announce the server: {}", error))?;
+        socket.set_broadcast(true).map_err(|error| format!("Could not announce the server: {}", error))?;
+        let protocol = protocol.to_string();
+        thread::spawn(move || {
+            while let Some(games) = games() {
+                let announcement = Announcement { name: self.name.clone(), port, protocol: protocol.clone(), games };
+                if let Err(error) = socket.send_to(announcement.write().as_bytes(), self.target) {
+                    println!("Could not announce the server: {}", error);
+                    return;
+                }
+                thread::sleep(ANNOUNCE_INTERVAL);
+            }
+        });
+        Ok(())
+    }
+}
+
+/// A server found on the network.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Found {
+    pub address: IpAddr,
+    pub announcement: Announcement,
+}
+
+/// Listens on `socket` for `wait` and returns the servers that announced themselves, with the
+/// last games they announced, in the order they were first heard.
+pub fn find(socket: &UdpSocket, wait: Duration) -> Result<Vec<Found>, String> {
+    let until = Instant::now() + wait;
+    let mut found: Vec<Found> = Vec::new();
+    let mut heard: HashMap<(IpAddr, u16), usize> = HashMap::new();
+    let mut buffer = [0u8; 8192];
+    loop {
+        let left = until.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Ok(found);
+        }
+        socket.set_read_timeout(Some(left)).map_err(|error| error.to_string())?;
+        let Ok((length, from)) = socket.recv_from(&mut buffer) else { continue };
+        let Some(announcement) = Announcement::read(&String::from_utf8_lossy(&buffer[..length])) else { continue };
+        let server = Found { address: from.ip(), announcement };
+        match heard.get(&(from.ip(), server.announcement.port)) {
+            Some(index) => found[*index] = server,
+            None => {
+                heard.insert((from.ip(), server.announcement.port), found.len());
+                found.push(server);
+            },
+        }
+    }
+}
+
+/// The name a server announces unless given one: the name of the machine, if it is known.
+pub fn default_name() -> String {
+    std::env::var("HOSTNAME").ok().filter(|name| !name.trim().is_empty()).unwrap_or("loveletter".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_find() {
+        let announcement = Announcement {
+            name: "Game night".to_string(),
+            port: 4444,
+            protocol: "lines".to_string(),
+            games: vec![
+                Listing { room: Some(1), players: 3, seated: 1, playing: false, rules: rules(StartPolicy::WinnerStarts, None) },
+                Listing { room: Some(2), players: 2, seated: 2, playing: true, rules: "rotate start, 30s a turn".to_string() },
+            ],
+        };
+        assert_eq!(Announcement::read(&announcement.write()), Some(announcement.clone()));
+        assert_eq!(announcement.games.iter().map(Listing::free).collect::<Vec<usize>>(), vec![2, 0]);
+        assert_eq!(Announcement::read("hello"), None);
+
+        let socket = UdpSocket::bind(("127.0.0.1", 0)).unwrap();
+        let beacon = Beacon { name: "Game night".to_string(), target: socket.local_addr().unwrap() };
+        let sent = Arc::new(AtomicUsize::new(0));
+        let counter = sent.clone();
+        let games = announcement.games.clone();
+        beacon.start(4444, "lines", move || (counter.fetch_add(1, Ordering::SeqCst) < 1).then(|| games.clone())).unwrap();
+        let found = find(&socket, Duration::from_millis(300)).unwrap();
+        assert_eq!(found, vec![Found { address: IpAddr::V4(Ipv4Addr::LOCALHOST), announcement }]);
+    }
+}
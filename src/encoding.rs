@@ -0,0 +1,151 @@
+use crate::card::Card;
+use crate::env::{Decision, Observation, MAX_PLAYERS};
+
+// The features of an observation, all of them counts or 0/1 flags:
+//
+// `HAND + v`          copies of the card of value `v` in our hand, both cards when choosing what to play
+// `DRAWN + v`         copies of the card of value `v` drawn by our Executioner, when choosing what to keep
+// `DECISION`          1 when choosing what to play
+// `DECISION + 1`      1 when choosing what to keep after an Executioner
+// `DECK`              cards left in the deck
+// `PLAYERS`           players at the table
+// `SEATS + t * SEAT`  the seat `t` places after ours, 0 being ourselves, all 0 past the last seat:
+//     `+ PRESENT`           1 if the seat is at the table
+//     `+ OUT`               1 if they are out of the round
+//     `+ PROTECTED`         1 if they are protected by a Nobody
+//     `+ TOKENS`            their tokens
+//     `+ DORMOUSE`          1 if they are the only one to have discarded a Dormouse
+//     `+ KNOWN + v`         1 if we know they hold the card of value `v`, always 0 for ourselves
+//     `+ DISCARD + 10 * i + v`  1 if the `i`th card they discarded this round has value `v`
+//
+// At most 20 cards are discarded in a round, the deck without the removed card, so every discard
+// pile fits.
+pub const HAND: usize = 0;
+pub const DRAWN: usize = HAND + 10;
+pub const DECISION: usize = DRAWN + 10;
+pub const DECK: usize = DECISION + 2;
+pub const PLAYERS: usize = DECK + 1;
+pub const SEATS: usize = PLAYERS + 1;
+
+pub const PRESENT: usize = 0;
+pub const OUT: usize = 1;
+pub const PROTECTED: usize = 2;
+pub const TOKENS: usize = 3;
+pub const DORMOUSE: usize = 4;
+pub const KNOWN: usize = 5;
+pub const DISCARD: usize = KNOWN + 10;
+pub const DISCARD_SLOTS: usize = 20;
+pub const SEAT: usize = DISCARD + 10 * DISCARD_SLOTS;
+
+pub const FEATURE_COUNT: usize = SEATS + MAX_PLAYERS * SEAT;
+
+/// Encodes what the player to act sees as `FEATURE_COUNT` numbers, laid out as described above.
+pub fn encode(observation: &Observation) -> Vec<f32> {
+    let mut features = vec![0.0; FEATURE_COUNT];
+    let mut count = |offset: usize, cards: &[Card]| {
+        for card in cards {
+            features[offset + card.value()] += 1.0;
+        }
+    };
+    match &observation.decision {
+        Decision::Play { hand } => {
+            count(HAND, hand);
+            features[DECISION] = 1.0;
+        },
+        Decision::Executioner { hand, drawn } => {
+            count(HAND, &[*hand]);
+            count(DRAWN, drawn);
+            features[DECISION + 1] = 1.0;
+        },
+        Decision::Done => {},
+    }
+    features[DECK] = observation.deck_size as f32;
+    let players = observation.players.len();
+    features[PLAYERS] = players as f32;
+
+    for offset in 0..players {
+        let seat = (observation.seat + offset) % players;
+        let base = SEATS + offset * SEAT;
+        features[base + PRESENT] = 1.0;
+        features[base + OUT] = observation.out[seat] as u8 as f32;
+        features[base + PROTECTED] = observation.protected[seat] as u8 as f32;
+        features[base + TOKENS] = observation.tokens[seat] as f32;
+        features[base + DORMOUSE] = (observation.dormouse == Some(seat)) as u8 as f32;
+        if let Some(card) = observation.known[seat] {
+            if seat != observation.seat {
+                features[base + KNOWN + card.value()] = 1.0;
+            }
+        }
+        for (i, card) in observation.discard[seat].iter().take(DISCARD_SLOTS).enumerate() {
+            features[base + DISCARD + 10 * i + card.value()] = 1.0;
+        }
+    }
+    features
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand::rngs::StdRng;
+
+    use super::*;
+    use crate::env::{observe, Env};
+
+    /// Deals the cards nobody at the table may know about again, keeping what the player to act
+    /// holds and knows.
+    fn reshuffle_hidden(env: &Env, observation: &Observation, rng: &mut StdRng) -> Observation {
+        let mut state = env.state().clone();
+        let knowledge = env.knowledge(observation.seat);
+        let hidden: Vec<usize> = (0..state.players.len()).filter(|seat| *seat != observation.seat && !state.out.contains(seat) && knowledge.known[*seat].is_none()).collect();
+        let mut cards: Vec<Card> = hidden.iter().map(|seat| state.hands[*seat].unwrap()).chain(state.deck.iter().cloned()).collect();
+        cards.shuffle(rng);
+        for seat in &hidden {
+            state.hands[*seat] = cards.pop();
+        }
+        state.deck = cards;
+        observe(&state, knowledge, &observation.players, &observation.decision)
+    }
+
+    #[test]
+    fn test_encoding_never_leaks_hidden_cards() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for players in 2..=MAX_PLAYERS {
+            let mut env = Env::new(players);
+            let mut observation = env.reset(players as u64);
+            let mut known = 0;
+            while observation.decision != Decision::Done {
+                let features = encode(&observation);
+                assert_eq!(features.len(), FEATURE_COUNT);
+                assert_eq!(encode(&reshuffle_hidden(&env, &observation, &mut rng)), features);
+
+                // Whatever we know about another hand is encoded as it is
+                for offset in 1..players {
+                    let seat = (observation.seat + offset) % players;
+                    if let Some(card) = observation.known[seat] {
+                        assert_eq!(features[SEATS + offset * SEAT + KNOWN + card.value()], 1.0);
+                        known += 1;
+                    }
+                }
+
+                let action = *observation.legal().choose(&mut rng).unwrap();
+                observation = env.step(action).unwrap().0;
+            }
+            assert!(known > 0);
+        }
+    }
+
+    #[test]
+    fn test_layout() {
+        let mut env = Env::new(3);
+        let observation = env.reset(1);
+        let features = encode(&observation);
+        let Decision::Play { hand } = observation.decision else { panic!() };
+        assert_eq!(features[HAND..DRAWN].iter().sum::<f32>(), 2.0);
+        assert_eq!(features[HAND + hand[1].value()], if hand[0] == hand[1] { 2.0 } else { 1.0 });
+        assert_eq!(features[DECISION], 1.0);
+        assert_eq!(features[DECK], observation.deck_size as f32);
+        assert_eq!(features[PLAYERS], 3.0);
+        assert_eq!(features[SEATS + 2 * SEAT + PRESENT], 1.0);
+        assert_eq!(features[SEATS + 3 * SEAT + PRESENT], 0.0);
+    }
+}
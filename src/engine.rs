@@ -0,0 +1,389 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use crate::agent::Agent;
+use crate::belief::Knowledge;
+use crate::bots::make_profile_bot;
+use crate::card::Card;
+use crate::env::{observe, Decision, Observation};
+use crate::game::{Action, Event, ExecutionerChoice, State};
+use crate::model::OpponentModel;
+use crate::options::Options;
+use crate::profile::load_profiles;
+use crate::sim::{simulate_game, StartPolicy};
+#[cfg(feature = "wasm")]
+use crate::wasm::{WasmBot, DEFAULT_FUEL, DEFAULT_MEMORY};
+
+// The protocol between the game and an external engine, one message per line. Cards are given
+// by value, seats are numbered from 0 in the seating of the current round and `-` means none.
+//
+// game:   loveletter 1
+// engine: ready [name]
+//
+// At the start of every round:
+// game:   round <round> seat <our seat> players <players>
+//
+// For everything that happens that our seat may see, as it would be shown to a human:
+// game:   info <text>           a line for every line of the text
+//
+// When we have to decide, the game sends our view and waits for the answer:
+// game:   view <our seat> <players> <round> <cards in deck>
+//         tokens <tokens of every seat>
+//         out <1 if out of the round, for every seat>
+//         protected <1 if protected, for every seat>
+//         dormouse <seat that alone discarded a Dormouse>
+//         known <card we know every seat holds>
+//         discard <seat> <discarded cards, oldest first>     (one line per seat)
+// then either, to play a card:
+//         hand <held card> <drawn card>
+//         legal <action> ...
+//         go play
+// engine: play <action>
+// where an action is `card`, `card:target` or `card:target:guess` and must be listed in `legal`,
+// or, to keep a card after an Executioner:
+//         hand <held card>
+//         drawn <drawn cards>
+//         go keep
+// engine: keep <index of the drawn card> <top|bottom>
+// where the held card goes on top of or below the other drawn card at the bottom of the deck.
+//
+// When the game is over:
+// game:   quit
+//
+// An engine that does not answer within the time limit, answers something else or exits forfeits.
+
+const PROTOCOL_VERSION: u32 = 1;
+
+/// How an action is written in the protocol.
+pub fn action_token(action: &Action) -> String {
+    let seat = |seat: Option<usize>| seat.map(|seat| seat.to_string());
+    let card = |card: Option<Card>| card.map(|card| card.value().to_string());
+    [Some(action.card.value().to_string()), seat(action.target), card(action.guess)].iter().flatten().cloned().collect::<Vec<String>>().join(":")
+}
+
+/// Reads an action written by `action_token`.
+pub fn parse_action_token(token: &str) -> Option<Action> {
+    let parts: Vec<&str> = token.split(':').collect();
+    let card = |part: &str| part.parse().ok().and_then(Card::from_value);
+    let action = match parts[..] {
+        [played] => Action { card: card(played)?, target: None, guess: None },
+        [played, target] => Action { card: card(played)?, target: Some(target.parse().ok()?), guess: None },
+        [played, target, guess] => Action { card: card(played)?, target: Some(target.parse().ok()?), guess: Some(card(guess)?) },
+        _ => return None,
+    };
+    Some(action)
+}
+
+/// The lines describing `observation` up to, not including, the decision.
+pub fn write_view(observation: &Observation) -> Vec<String> {
+    let flags = |flags: &[bool]| flags.iter().map(|flag| (*flag as u8).to_string()).collect::<Vec<String>>().join(" ");
+    let optional = |value: Option<String>| value.unwrap_or("-".to_string());
+    let mut lines = vec![
+        format!("view {} {} {} {}", observation.seat, observation.players.len(), observation.round, observation.deck_size),
+        format!("tokens {}", observation.tokens.iter().map(|tokens| tokens.to_string()).collect::<Vec<String>>().join(" ")),
+        format!("out {}", flags(&observation.out)),
+        format!("protected {}", flags(&observation.protected)),
+        format!("dormouse {}", optional(observation.dormouse.map(|seat| seat.to_string()))),
+        format!("known {}", observation.known.iter().map(|card| optional(card.map(|card| card.value().to_string()))).collect::<Vec<String>>().join(" ")),
+    ];
+    for (seat, pile) in observation.discard.iter().enumerate() {
+        let cards: Vec<String> = pile.iter().map(|card| card.value().to_string()).collect();
+        lines.push(format!("discard {} {}", seat, cards.join(" ")).trim_end().to_string());
+    }
+    lines
+}
+
+/// Everything sent for a decision of the seat `knowledge` belongs to: the view, the cards and
+/// what may be answered, ending with the `go` line.
+pub fn decision_lines(state: &State, knowledge: &Knowledge, decision: &Decision, legal: &[Action]) -> Vec<String> {
+    let seats: Vec<usize> = (0..state.players.len()).collect();
+    let mut lines = write_view(&observe(state, knowledge, &seats, decision));
+    lines.extend(write_decision(decision, legal));
+    lines
+}
+
+/// The lines after the view: the cards, what may be answered and the `go` line.
+pub fn write_decision(decision: &Decision, legal: &[Action]) -> Vec<String> {
+    match decision {
+        Decision::Play { hand } => {
+            let tokens: Vec<String> = legal.iter().map(action_token).collect();
+            vec![format!("hand {} {}", hand[0].value(), hand[1].value()), format!("legal {}", tokens.join(" ")), "go play".to_string()]
+        },
+        Decision::Executioner { hand, drawn } => {
+            let cards: Vec<String> = drawn.iter().map(|card| card.value().to_string()).collect();
+            vec![format!("hand {}", hand.value()), format!("drawn {}", cards.join(" ")), "go keep".to_string()]
+        },
+        Decision::Done => Vec::new(),
+    }
+}
+
+/// Reads an answer to a play decision, `play <action>`, which must be one of `legal`.
+pub fn parse_play(answer: &str, legal: &[Action]) -> Option<Action> {
+    let token = answer.strip_prefix("play ")?.trim();
+    legal.iter().find(|action| action_token(action) == token).copied()
+}
+
+/// Reads an Executioner answer, `keep <index> <top|bottom>`.
+pub fn parse_keep(answer: &str, drawn: usize) -> Option<ExecutionerChoice> {
+    let words: Vec<&str> = answer.split_whitespace().collect();
+    let [command, keep, place] = words[..] else {
+        return None;
+    };
+    let keep: usize = keep.parse().ok()?;
+    let hand_on_top = match place {
+        "top" => true,
+        "bottom" => false,
+        _ => return None,
+    };
+    (command == "keep" && keep < drawn).then_some(ExecutionerChoice { keep, hand_on_top })
+}
+
+/// A seat played by another process speaking the protocol above. After forfeiting it plays the
+/// first legal action until the round is over.
+pub struct ExternalBot {
+    pub name: String,
+    child: Child,
+    input: ChildStdin,
+    output: Receiver<String>,
+    time_limit: Duration,
+    knowledge: Option<Knowledge>,
+    forfeit: Option<String>,
+}
+
+impl ExternalBot {
+    /// Starts `command` through the shell and waits for it to be ready.
+    pub fn start(command: &str, time_limit: Duration) -> Result<ExternalBot, String> {
+        let mut shell = if cfg!(windows) {
+            let mut shell = Command::new("cmd");
+            shell.arg("/C");
+            shell
+        } else {
+            let mut shell = Command::new("sh");
+            shell.arg("-c");
+            shell
+        };
+        let mut child = shell.arg(command).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().map_err(|error| format!("Could not start \"{}\": {}", command, error))?;
+        let input = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let (sender, output) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut bot = ExternalBot { name: command.to_string(), child, input, output, time_limit, knowledge: None, forfeit: None };
+        bot.send(&[format!("loveletter {}", PROTOCOL_VERSION)]);
+        match bot.receive() {
+            Some(answer) if answer.split_whitespace().next() == Some("ready") => {
+                let name = answer.split_whitespace().skip(1).collect::<Vec<&str>>().join(" ");
+                if !name.is_empty() {
+                    bot.name = name;
+                }
+                Ok(bot)
+            },
+            _ => Err(format!("\"{}\" did not answer ready: {}", command, bot.forfeit.clone().unwrap_or("unexpected answer".to_string()))),
+        }
+    }
+
+    fn send(&mut self, lines: &[String]) {
+        if self.forfeit.is_some() {
+            return;
+        }
+        let text: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+        if self.input.write_all(text.as_bytes()).and_then(|_| self.input.flush()).is_err() {
+            self.forfeit = Some("the engine exited".to_string());
+        }
+    }
+
+    /// The next line of the engine, or `None` after forfeiting.
+    fn receive(&mut self) -> Option<String> {
+        if self.forfeit.is_some() {
+            return None;
+        }
+        match self.output.recv_timeout(self.time_limit) {
+            Ok(line) => Some(line.trim().to_string()),
+            Err(RecvTimeoutError::Timeout) => {
+                self.forfeit = Some(format!("no answer within {} ms", self.time_limit.as_millis()));
+                None
+            },
+            Err(RecvTimeoutError::Disconnected) => {
+                self.forfeit = Some("the engine exited".to_string());
+                None
+            },
+        }
+    }
+
+    fn request(&self, state: &State, decision: Decision, legal: &[Action]) -> Vec<String> {
+        let knowledge = self.knowledge.clone().unwrap_or(Knowledge::new(state, state.turn));
+        decision_lines(state, &knowledge, &decision, legal)
+    }
+}
+
+impl Agent for ExternalBot {
+    fn start_round(&mut self, state: &State, seat: usize) {
+        self.knowledge = Some(Knowledge::new(state, seat));
+        self.send(&[format!("round {} seat {} players {}", state.round, seat, state.players.len())]);
+    }
+
+    fn choose_action(&mut self, state: &State, hand: [Card; 2], legal: &[Action]) -> Action {
+        let lines = self.request(state, Decision::Play { hand }, legal);
+        self.send(&lines);
+        let action = match self.receive() {
+            Some(answer) => parse_play(&answer, legal).unwrap_or_else(|| {
+                self.forfeit = Some(format!("illegal answer \"{}\"", answer));
+                legal[0]
+            }),
+            None => legal[0],
+        };
+        if let Some(knowledge) = self.knowledge.as_mut() {
+            knowledge.hand = Some(if action.card == hand[0] { hand[1] } else { hand[0] });
+        }
+        action
+    }
+
+    fn choose_executioner(&mut self, state: &State, hand: Card, drawn: &[Card]) -> ExecutionerChoice {
+        let lines = self.request(state, Decision::Executioner { hand, drawn: drawn.to_vec() }, &[]);
+        self.send(&lines);
+        let fallback = ExecutionerChoice { keep: 0, hand_on_top: true };
+        let choice = match self.receive() {
+            Some(answer) => parse_keep(&answer, drawn.len()).unwrap_or_else(|| {
+                self.forfeit = Some(format!("illegal answer \"{}\"", answer));
+                fallback
+            }),
+            None => fallback,
+        };
+        if let Some(knowledge) = self.knowledge.as_mut() {
+            knowledge.hand = Some(drawn[choice.keep]);
+        }
+        choice
+    }
+
+    fn observe(&mut self, state: &State, event: &Event) {
+        let Some(knowledge) = self.knowledge.as_mut() else { return };
+        knowledge.observe(state, event);
+        if let Some(text) = event.describe(&state.players, knowledge.seat) {
+            self.send(&text.lines().map(|line| format!("info {}", line)).collect::<Vec<String>>());
+        }
+    }
+
+    fn forfeited(&self) -> Option<String> {
+        self.forfeit.clone()
+    }
+}
+
+impl Drop for ExternalBot {
+    fn drop(&mut self) {
+        self.send(&["quit".to_string()]);
+        thread::sleep(Duration::from_millis(10));
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// `match`: plays `--games` games between the seats of `--seats a,b,c`, each either the name of
+/// a built-in bot, a `.wasm` bot or a command that starts an external engine for every game.
+/// Engines get `--move-time` milliseconds per decision and WebAssembly bots `--fuel` instructions
+/// and `--memory` bytes. Seats may also name the play styles of `--profiles file`. With `--model
+/// file` the heuristic bots use what the opponent model learned about the players they meet.
+pub fn command(options: &Options) -> Result<(), String> {
+    let seats: Vec<String> = options.value("seats").ok_or("Give the players with --seats a,b,c.")?.split(',').map(|seat| seat.trim().to_string()).collect();
+    if !(2..=6).contains(&seats.len()) {
+        return Err(format!("{} is not a valid number of players. Must be between 2 and 6.", seats.len()));
+    }
+    let time_limit = Duration::from_millis(options.get("move-time", 1000)?);
+    let games: u64 = options.get("games", 10)?;
+    #[cfg(feature = "wasm")]
+    let (fuel, memory) = (options.get("fuel", DEFAULT_FUEL)?, options.get("memory", DEFAULT_MEMORY)?);
+    let profiles = match options.value("profiles") {
+        Some(path) => load_profiles(path)?,
+        None => Vec::new(),
+    };
+    let model = options.value("model").map(OpponentModel::load).transpose()?;
+    let mut rng = StdRng::seed_from_u64(options.get("seed", 0)?);
+
+    let mut wins = vec![0; seats.len()];
+    let mut forfeits = vec![0; seats.len()];
+    let mut names = seats.clone();
+    for game in 0..games {
+        // Every game starts a seat later so nobody always begins
+        let mut order: Vec<usize> = (0..seats.len()).collect();
+        order.rotate_left(game as usize % seats.len());
+        let mut agents: Vec<Box<dyn Agent>> = Vec::new();
+        for seat in &order {
+            agents.push(match make_profile_bot(&seats[*seat], rng.gen(), &profiles, model.as_ref()) {
+                Some(bot) => bot,
+                #[cfg(feature = "wasm")]
+                None if seats[*seat].ends_with(".wasm") => Box::new(WasmBot::load(&seats[*seat], fuel, memory)?),
+                None => {
+                    let bot = ExternalBot::start(&seats[*seat], time_limit)?;
+                    names[*seat] = bot.name.clone();
+                    Box::new(bot)
+                },
+            });
+        }
+        let result = simulate_game(agents, StartPolicy::Rotate, &mut rng, &mut |_, _| {});
+        if let Some(winner) = result.winner {
+            wins[order[winner]] += 1;
+        }
+        if let Some(forfeit) = result.forfeit {
+            forfeits[order[forfeit]] += 1;
+        }
+        let winner = result.winner.map(|winner| names[order[winner]].clone()).unwrap_or("nobody".to_string());
+        println!("Game {}: {} won after {} rounds.", game + 1, winner, result.rounds);
+    }
+
+    println!("\n{:<30}{:>8}{:>10}", "Player", "Wins", "Forfeits");
+    for seat in 0..seats.len() {
+        println!("{:<30}{:>8}{:>10}", names[seat], wins[seat], forfeits[seat]);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bots::RandomBot;
+
+    /// Plays the first legal action and keeps the first drawn card.
+    const FIRST_LEGAL: &str = "while read command rest; do case $command in loveletter) echo ready first;; legal) set -- $rest; first=$1;; go) if [ \"$rest\" = play ]; then echo play $first; else echo keep 0 top; fi;; quit) exit;; esac; done";
+
+    #[test]
+    fn test_tokens() {
+        let action = Action { card: Card::Guard, target: Some(2), guess: Some(Card::Alice) };
+        assert_eq!(action_token(&action), "1:2:9");
+        assert_eq!(action_token(&Action { card: Card::Nobody, target: None, guess: None }), "4");
+        assert_eq!(parse_action_token("1:2:9"), Some(action));
+        assert_eq!(parse_action_token("1:x"), None);
+        assert_eq!(parse_keep("keep 1 bottom", 2), Some(ExecutionerChoice { keep: 1, hand_on_top: false }));
+        assert_eq!(parse_keep("keep 2 top", 2), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_engine_plays_and_forfeits() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let engine = ExternalBot::start(FIRST_LEGAL, Duration::from_secs(5)).unwrap();
+        assert_eq!(engine.name, "first");
+        let agents: Vec<Box<dyn Agent>> = vec![Box::new(engine), Box::new(RandomBot::new(1))];
+        let result = simulate_game(agents, StartPolicy::Rotate, &mut rng, &mut |_, _| {});
+        assert_eq!(result.forfeit, None);
+        assert!(result.winner.is_some());
+
+        let silent = "read hello; echo ready; cat > /dev/null";
+        let engine = ExternalBot::start(silent, Duration::from_millis(50)).unwrap();
+        let agents: Vec<Box<dyn Agent>> = vec![Box::new(engine), Box::new(RandomBot::new(1))];
+        let result = simulate_game(agents, StartPolicy::Rotate, &mut rng, &mut |_, _| {});
+        assert_eq!(result.forfeit, Some(0));
+        assert_eq!(result.winner, Some(1));
+    }
+}
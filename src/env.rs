@@ -0,0 +1,345 @@
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use crate::agent::Agent;
+use crate::belief::Knowledge;
+use crate::card::Card;
+use crate::game::{award_tokens, deal, deal_next_round, executioner_draw, executioner_place, is_protected, legal_actions, next_turn, play_card, play_tie_breaker, Action, Event, ExecutionerChoice, PlayError, State};
+use crate::sim::{tokens_to_win, MAX_ROUNDS};
+
+/// Seats at the biggest table. Targets are numbered relative to the acting seat up to this.
+pub const MAX_PLAYERS: usize = 6;
+
+// The action space, in this order:
+// - `NO_TARGET + v`: discard the card of value `v` without a target.
+// - `TARGETED + 6 * v + t`: discard the card of value `v` at the seat `t` places after ours, 0 being ourselves.
+// - `GUESSED + 10 * t + g`: discard a Guard at the seat `t` places after ours, naming the card of value `g`.
+// - `EXECUTIONER + 2 * k + h`: keep drawn card `k` after an Executioner, with the old hand on top if `h` is 1.
+// Guards always use the guessed part, so their targeted indices are never legal.
+pub const NO_TARGET: usize = 0;
+pub const TARGETED: usize = NO_TARGET + 10;
+pub const GUESSED: usize = TARGETED + 10 * MAX_PLAYERS;
+pub const EXECUTIONER: usize = GUESSED + 10 * MAX_PLAYERS;
+pub const ACTION_COUNT: usize = EXECUTIONER + 4;
+
+/// The index of `action` taken by the player in seat `seat` of a table of `players`.
+pub fn action_index(action: &Action, seat: usize, players: usize) -> usize {
+    let card = action.card.value();
+    match (action.target, action.guess) {
+        (None, _) => NO_TARGET + card,
+        (Some(target), None) => TARGETED + MAX_PLAYERS * card + (target + players - seat) % players,
+        (Some(target), Some(guess)) => GUESSED + 10 * ((target + players - seat) % players) + guess.value(),
+    }
+}
+
+/// The action behind `index` for the player in seat `seat`, or `None` if it is not a card action.
+pub fn decode_action(index: usize, seat: usize, players: usize) -> Option<Action> {
+    let target = |offset: usize| if offset < players { Some((seat + offset) % players) } else { None };
+    if index < TARGETED {
+        Some(Action { card: Card::from_value(index - NO_TARGET)?, target: None, guess: None })
+    } else if index < GUESSED {
+        let index = index - TARGETED;
+        Some(Action { card: Card::from_value(index / MAX_PLAYERS)?, target: Some(target(index % MAX_PLAYERS)?), guess: None })
+    } else if index < EXECUTIONER {
+        let index = index - GUESSED;
+        Some(Action { card: Card::Guard, target: Some(target(index / 10)?), guess: Some(Card::from_value(index % 10)?) })
+    } else {
+        None
+    }
+}
+
+/// The index of an Executioner choice.
+pub fn executioner_index(choice: &ExecutionerChoice) -> usize {
+    EXECUTIONER + 2 * choice.keep + choice.hand_on_top as usize
+}
+
+/// What the player to act has to decide.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decision {
+    /// Which card to play, holding `hand` (held card first, drawn card second).
+    Play { hand: [Card; 2] },
+    /// Which card to keep after playing an Executioner while holding `hand`.
+    Executioner { hand: Card, drawn: Vec<Card> },
+    /// The game is over.
+    Done,
+}
+
+/// What the player to act can see. Everything indexed by seat is in the seating of the current
+/// round, which rotates between rounds; `players` maps seats back to players.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observation {
+    /// The player to act, numbered by their seat in the first round.
+    pub player: usize,
+    pub seat: usize,
+    pub players: Vec<usize>,
+    pub decision: Decision,
+    /// Which of the `ACTION_COUNT` actions are legal.
+    pub mask: Vec<bool>,
+    pub round: i32,
+    pub deck_size: usize,
+    pub discard: Vec<Vec<Card>>,
+    pub out: Vec<bool>,
+    pub protected: Vec<bool>,
+    pub tokens: Vec<i32>,
+    pub dormouse: Option<usize>,
+    /// The cards we know other players hold from Wilkins, Time and the Tweedies.
+    pub known: Vec<Option<Card>>,
+}
+
+impl Observation {
+    /// The legal action indices.
+    pub fn legal(&self) -> Vec<usize> {
+        (0..ACTION_COUNT).filter(|index| self.mask[*index]).collect()
+    }
+}
+
+/// `play_card` only asks for an Executioner choice, which the environment resolves itself.
+struct NoChoice;
+
+impl Agent for NoChoice {
+    fn choose_action(&mut self, _state: &State, _hand: [Card; 2], _legal: &[Action]) -> Action {
+        unreachable!()
+    }
+
+    fn choose_executioner(&mut self, _state: &State, _hand: Card, _drawn: &[Card]) -> ExecutionerChoice {
+        unreachable!()
+    }
+}
+
+/// A whole game as a multi-agent environment: every step is one decision of the player to act,
+/// and the rewards of a step are the tokens each player won during it.
+pub struct Env {
+    players: usize,
+    rng: StdRng,
+    state: State,
+    /// The player in every seat of the current round.
+    seats: Vec<usize>,
+    knowledge: Vec<Knowledge>,
+    decision: Decision,
+    rounds: u32,
+}
+
+impl Env {
+    pub fn new(players: usize) -> Env {
+        assert!((2..=MAX_PLAYERS).contains(&players));
+        let mut rng = StdRng::seed_from_u64(0);
+        let state = deal(vec![String::new(); players], vec![0; players], 1, &mut rng);
+        Env { players, rng, state, seats: Vec::new(), knowledge: Vec::new(), decision: Decision::Done, rounds: 0 }
+    }
+
+    /// Starts a new game and returns what the first player sees.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.rng = StdRng::seed_from_u64(seed);
+        let names = (0..self.players).map(|i| format!("Player {}", i + 1)).collect();
+        self.seats = (0..self.players).collect();
+        self.rounds = 0;
+        let state = deal(names, vec![0; self.players], 1, &mut self.rng);
+        self.start_round(state);
+        let mut rewards = vec![0.0; self.players];
+        self.advance(&mut rewards);
+        self.observation()
+    }
+
+    /// Takes the action with index `action` for the player to act. Returns what the next player
+    /// to act sees, the rewards of every player and whether the game is over.
+    pub fn step(&mut self, action: usize) -> Result<(Observation, Vec<f64>, bool), PlayError> {
+        if action >= ACTION_COUNT || !self.mask()[action] {
+            return Err(PlayError::InvalidCard);
+        }
+        let mut rewards = vec![0.0; self.players];
+        let seat = self.state.turn;
+        let mut events = Vec::new();
+        match self.decision.clone() {
+            Decision::Play { hand } => {
+                let action = decode_action(action, seat, self.players).unwrap();
+                let kept = if action.card == hand[0] { hand[1] } else { hand[0] };
+                self.state.hands[seat] = Some(kept);
+                self.knowledge[seat].hand = Some(kept);
+                if action.card == Card::Executioner {
+                    self.state.discard[seat].push(action.card);
+                    let drawn = executioner_draw(&mut self.state);
+                    if !drawn.is_empty() {
+                        self.decision = Decision::Executioner { hand: kept, drawn };
+                        return Ok((self.observation(), rewards, false));
+                    }
+                    events.push(Event::Executioner { player: seat, drawn, kept: None });
+                } else {
+                    let state = self.state.clone();
+                    let (result, state) = play_card(state, &action, &mut NoChoice, &mut events);
+                    result?;
+                    self.state = state;
+                }
+            },
+            Decision::Executioner { drawn, .. } => {
+                let index = action - EXECUTIONER;
+                let choice = ExecutionerChoice { keep: index / 2, hand_on_top: index % 2 == 1 };
+                let kept = executioner_place(&mut self.state, &drawn, choice)?;
+                self.knowledge[seat].hand = kept;
+                events.push(Event::Executioner { player: seat, drawn, kept });
+            },
+            Decision::Done => return Err(PlayError::InvalidPlayer),
+        }
+
+        for event in &events {
+            for knowledge in self.knowledge.iter_mut() {
+                knowledge.observe(&self.state, event);
+            }
+        }
+        next_turn(&mut self.state);
+        let done = self.advance(&mut rewards);
+        Ok((self.observation(), rewards, done))
+    }
+
+    /// The state of the current round, including the hidden cards.
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    /// What the player in `seat` knows about the other hands.
+    pub fn knowledge(&self, seat: usize) -> &Knowledge {
+        &self.knowledge[seat]
+    }
+
+    pub fn players(&self) -> usize {
+        self.players
+    }
+
+    fn start_round(&mut self, state: State) {
+        self.knowledge = (0..self.players).map(|seat| Knowledge::new(&state, seat)).collect();
+        self.state = state;
+    }
+
+    /// Skips players who are out and finishes rounds until someone has to decide. Returns
+    /// whether the game is over.
+    fn advance(&mut self, rewards: &mut [f64]) -> bool {
+        loop {
+            let state = &mut self.state;
+            if state.out.len() < state.players.len() - 1 && !state.deck.is_empty() {
+                if state.out.contains(&state.turn) {
+                    next_turn(state);
+                    continue;
+                }
+                let hand = [state.hands[state.turn].unwrap(), state.deck.pop().unwrap()];
+                self.decision = Decision::Play { hand };
+                return false;
+            }
+
+            // The round is over, with a tie breaker unless only one player is left
+            if state.out.len() < state.players.len() - 1 {
+                *state = play_tie_breaker(state.clone());
+            }
+            let before = state.tokens.clone();
+            award_tokens(state);
+            for seat in 0..self.players {
+                rewards[self.seats[seat]] += (state.tokens[seat] - before[seat]) as f64;
+            }
+            self.rounds += 1;
+
+            let best = *state.tokens.iter().max().unwrap();
+            let leaders = state.tokens.iter().filter(|tokens| **tokens == best).count();
+            if (best >= tokens_to_win(self.players) && leaders == 1) || self.rounds >= MAX_ROUNDS {
+                self.decision = Decision::Done;
+                return true;
+            }
+
+            // Rotate the players
+            self.seats.rotate_left(1);
+            let next = deal_next_round(state, 1, &mut self.rng);
+            self.start_round(next);
+        }
+    }
+
+    fn mask(&self) -> Vec<bool> {
+        legal_mask(&self.state, &self.decision)
+    }
+
+    fn observation(&self) -> Observation {
+        observe(&self.state, &self.knowledge[self.state.turn], &self.seats, &self.decision)
+    }
+}
+
+/// Which action indices are legal for the player to act in `state`.
+pub fn legal_mask(state: &State, decision: &Decision) -> Vec<bool> {
+    let mut mask = vec![false; ACTION_COUNT];
+    match decision {
+        Decision::Play { hand } => {
+            for action in legal_actions(state, *hand) {
+                mask[action_index(&action, state.turn, state.players.len())] = true;
+            }
+        },
+        Decision::Executioner { drawn, .. } => {
+            for keep in 0..drawn.len() {
+                for hand_on_top in [false, true] {
+                    mask[executioner_index(&ExecutionerChoice { keep, hand_on_top })] = true;
+                }
+            }
+        },
+        Decision::Done => {},
+    }
+    mask
+}
+
+/// What the player to act in `state` sees, given what they know and the player in every seat.
+pub fn observe(state: &State, knowledge: &Knowledge, seats: &[usize], decision: &Decision) -> Observation {
+    let seat = state.turn;
+    let all = 0..state.players.len();
+    Observation {
+        player: seats[seat],
+        seat,
+        players: seats.to_vec(),
+        decision: decision.clone(),
+        mask: legal_mask(state, decision),
+        round: state.round,
+        deck_size: state.deck.len(),
+        discard: state.discard.clone(),
+        out: all.clone().map(|other| state.out.contains(&other)).collect(),
+        protected: all.map(|other| is_protected(state, other)).collect(),
+        tokens: state.tokens.clone(),
+        dormouse: state.dormouse,
+        known: knowledge.known.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_action_index_round_trip() {
+        for players in 2..=MAX_PLAYERS {
+            for seat in 0..players {
+                for index in 0..EXECUTIONER {
+                    if let Some(action) = decode_action(index, seat, players) {
+                        assert_eq!(action_index(&action, seat, players), index);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_games() {
+        for players in 2..=MAX_PLAYERS {
+            let mut env = Env::new(players);
+            let mut rng = StdRng::seed_from_u64(players as u64);
+            let mut observation = env.reset(players as u64);
+            let mut tokens = vec![0.0; players];
+            loop {
+                assert!(env.step(observation.mask.iter().position(|legal| !legal).unwrap()).is_err());
+                let action = *observation.legal().choose(&mut rng).unwrap();
+                let (next, rewards, done) = env.step(action).unwrap();
+                for (player, reward) in rewards.iter().enumerate() {
+                    tokens[player] += reward;
+                }
+                observation = next;
+                if done {
+                    break;
+                }
+            }
+            assert_eq!(observation.decision, Decision::Done);
+            for seat in 0..players {
+                assert_eq!(tokens[observation.players[seat]], env.state().tokens[seat] as f64);
+            }
+        }
+    }
+}
@@ -0,0 +1,696 @@
+use rand::prelude::*;
+
+use crate::agent::Agent;
+use crate::card::{create_deck, Card};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct State {
+    pub deck: Vec<Card>,
+    pub discard: Vec<Vec<Card>>,
+    pub players: Vec<String>,
+    pub out: Vec<usize>,
+    pub hands: Vec<Option<Card>>,
+    pub tokens: Vec<i32>,
+    pub round: i32,
+    pub turn: usize,
+    pub dormouse: Option<usize>,
+}
+
+/// Shuffles a fresh deck, removes one card and deals one card to each player.
+pub fn deal<R: Rng + ?Sized>(players: Vec<String>, tokens: Vec<i32>, round: i32, rng: &mut R) -> State {
+    let mut deck = create_deck();
+    deck.shuffle(rng);
+
+    // Removing one card
+    deck.pop();
+
+    let mut hands = Vec::new();
+    let mut discard = Vec::new();
+    for _ in 0..players.len() {
+        hands.push(deck.pop());
+        discard.push(Vec::new());
+    }
+
+    State {
+        deck,
+        discard,
+        players,
+        out: Vec::new(),
+        hands,
+        tokens,
+        round,
+        turn: 0,
+        dormouse: None,
+    }
+}
+
+/// Rotates the table so the player in seat `start` begins the next round, keeping the order of play.
+/// `setup` uses a `start` of 1, moving the first player to the back.
+pub fn rotate_seats(state: &State, start: usize) -> (Vec<String>, Vec<i32>) {
+    let mut players = state.players.clone();
+    let mut tokens = state.tokens.clone();
+    players.rotate_left(start);
+    tokens.rotate_left(start);
+    (players, tokens)
+}
+
+/// Deals the round after `state`, numbered one higher, with the player in seat `start` first.
+pub fn deal_next_round<R: Rng + ?Sized>(state: &State, start: usize, rng: &mut R) -> State {
+    let (players, tokens) = rotate_seats(state, start);
+    deal(players, tokens, state.round + 1, rng)
+}
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug)]
+pub enum PlayError {
+    InvalidCard,
+    InvalidPlayer,
+    InvalidTargetPlayer,
+    InvalidHand,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RoundStatus {
+    Continue,
+    Skip,
+    TieBreaker,
+    End,
+}
+pub type TurnResult = Result<RoundStatus, PlayError>;
+
+/// A complete decision for one turn: the card to discard, who it targets and what a Guard guesses.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Action {
+    pub card: Card,
+    pub target: Option<usize>,
+    pub guess: Option<Card>,
+}
+
+impl Action {
+    /// A short description such as "Guard on Bob, guessing Alice".
+    pub fn describe(&self, players: &[String]) -> String {
+        match (self.target, self.guess) {
+            (None, _) => self.card.name(),
+            (Some(target), None) => format!("{} on {}", self.card.name(), players[target]),
+            (Some(target), Some(guess)) => format!("{} on {}, guessing {}", self.card.name(), players[target], guess.name()),
+        }
+    }
+}
+
+/// How to resolve an Executioner: which drawn card to keep and whether the old hand
+/// is placed on top of the other card at the bottom of the deck.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExecutionerChoice {
+    pub keep: usize,
+    pub hand_on_top: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DormouseOutcome {
+    First,
+    Second,
+    Cancelled(usize),
+}
+
+/// Everything that can happen when a card is played. `player` is always the one who played the card.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Alice { player: usize },
+    RedQueen { player: usize },
+    NoTarget { player: usize, card: Card },
+    Swapped { player: usize, target: usize },
+    Executioner { player: usize, drawn: Vec<Card>, kept: Option<Card> },
+    Knave { player: usize, target: usize, card: Card, drew: bool },
+    Protected { player: usize },
+    Compared { player: usize, target: usize, player_card: Card, target_card: Card },
+    Revealed { player: usize, target: usize, card: Card },
+    Guessed { player: usize, target: usize, guess: Card, correct: bool },
+    Dormouse { player: usize, outcome: DormouseOutcome },
+}
+
+impl Event {
+    pub fn player(&self) -> usize {
+        match self {
+            Event::Alice { player }
+            | Event::RedQueen { player }
+            | Event::NoTarget { player, .. }
+            | Event::Swapped { player, .. }
+            | Event::Executioner { player, .. }
+            | Event::Knave { player, .. }
+            | Event::Protected { player }
+            | Event::Compared { player, .. }
+            | Event::Revealed { player, .. }
+            | Event::Guessed { player, .. }
+            | Event::Dormouse { player, .. } => *player,
+        }
+    }
+
+    /// The card that was discarded to cause this event.
+    pub fn card(&self) -> Card {
+        match self {
+            Event::Alice { .. } => Card::Alice,
+            Event::RedQueen { .. } => Card::RedQueen,
+            Event::NoTarget { card, .. } => *card,
+            Event::Swapped { .. } => Card::Time,
+            Event::Executioner { .. } => Card::Executioner,
+            Event::Knave { .. } => Card::KnaveOfHearts,
+            Event::Protected { .. } => Card::Nobody,
+            Event::Compared { .. } => Card::Tweedies,
+            Event::Revealed { .. } => Card::Wilkins,
+            Event::Guessed { .. } => Card::Guard,
+            Event::Dormouse { .. } => Card::Dormouse,
+        }
+    }
+
+    /// The player knocked out of the round by this event, if any.
+    pub fn eliminated(&self) -> Option<usize> {
+        match self {
+            Event::Alice { player } => Some(*player),
+            Event::Knave { target, card, drew, .. } if *card == Card::Alice || !drew => Some(*target),
+            Event::Compared { player, target, player_card, target_card } => {
+                if player_card.value() > target_card.value() {
+                    Some(*target)
+                } else if player_card.value() < target_card.value() {
+                    Some(*player)
+                } else {
+                    None
+                }
+            },
+            Event::Guessed { target, correct: true, .. } => Some(*target),
+            _ => None,
+        }
+    }
+
+    /// Whether `viewer` is allowed to see this event at all.
+    pub fn visible_to(&self, viewer: usize) -> bool {
+        match self {
+            Event::Revealed { player, .. } => *player == viewer,
+            _ => true,
+        }
+    }
+
+    /// Describes the event from the point of view of `viewer`, or `None` if it is hidden from them.
+    pub fn describe(&self, players: &[String], viewer: usize) -> Option<String> {
+        if !self.visible_to(viewer) {
+            return None;
+        }
+        let you = self.player() == viewer;
+        let name = |player: usize| players[player].clone();
+        let actor = name(self.player());
+        let text = match self {
+            Event::Alice { .. } => {
+                if you { "You are out.".to_string() } else { format!("{} discarded Alice and is out.", actor) }
+            },
+            Event::RedQueen { .. } => {
+                if you { "You played the Red Queen.".to_string() } else { format!("{} played the Red Queen.", actor) }
+            },
+            Event::NoTarget { card, .. } => {
+                if you { "You discard without a target.".to_string() } else { format!("{} discards {} without a target.", actor, card.name()) }
+            },
+            Event::Swapped { target, .. } => {
+                if you {
+                    format!("You swap hands with {}.", name(*target))
+                } else if *target == viewer {
+                    format!("{} swaps hands with you.", actor)
+                } else {
+                    format!("{} swaps hands with {}.", actor, name(*target))
+                }
+            },
+            Event::Executioner { drawn, .. } => {
+                if you {
+                    "You placed the cards at the bottom of the deck.".to_string()
+                } else {
+                    format!("{} draws {} cards with the Executioner and places {} at the bottom of the deck.", actor, drawn.len(), drawn.len())
+                }
+            },
+            Event::Knave { target, card, drew, .. } => {
+                let mut text = format!("{} discards {}.", name(*target), card.name());
+                if *card == Card::Alice {
+                    text += &format!("\n{} is out.", name(*target));
+                } else if *drew {
+                    text += &format!("\n{} draws a card.", name(*target));
+                } else {
+                    text += &format!("\n{} has no cards left to draw and is out.", name(*target));
+                }
+                text
+            },
+            Event::Protected { .. } => {
+                if you { "You are protected.".to_string() } else { format!("{} is protected.", actor) }
+            },
+            Event::Compared { target, player_card, target_card, .. } => {
+                if you {
+                    let outcome = if player_card.value() > target_card.value() {
+                        format!("You win, {} is out.", name(*target))
+                    } else if player_card.value() < target_card.value() {
+                        "You lose, you are out.".to_string()
+                    } else {
+                        "You tie.".to_string()
+                    };
+                    format!("You compare hands with {} who has a {}.\n{}", name(*target), target_card.name(), outcome)
+                } else if *target == viewer {
+                    let outcome = if target_card.value() > player_card.value() {
+                        format!("You win, {} is out.", actor)
+                    } else if target_card.value() < player_card.value() {
+                        "You lose, you are out.".to_string()
+                    } else {
+                        "You tie.".to_string()
+                    };
+                    format!("{} compares hands with you and has a {}.\n{}", actor, player_card.name(), outcome)
+                } else {
+                    match self.eliminated() {
+                        Some(loser) => format!("{} compares hands with {}, {} is out.", actor, name(*target), name(loser)),
+                        None => format!("{} compares hands with {}, it is a tie.", actor, name(*target)),
+                    }
+                }
+            },
+            Event::Revealed { target, card, .. } => format!("{}'s hand is:\n{}", name(*target), card),
+            Event::Guessed { target, guess, correct, .. } => {
+                match (you, correct) {
+                    (true, true) => format!("You guessed \"{}\" correctly, {} is out.", guess.name(), name(*target)),
+                    (true, false) => format!("You guessed \"{}\", which is incorrect.", guess.name()),
+                    (false, true) => format!("{} guessed that {} has \"{}\", which is correct. {} is out.", actor, name(*target), guess.name(), name(*target)),
+                    (false, false) => format!("{} guessed that {} has \"{}\", which is incorrect.", actor, name(*target), guess.name()),
+                }
+            },
+            Event::Dormouse { outcome, .. } => {
+                match (you, outcome) {
+                    (true, DormouseOutcome::First) => "You discarded the first Dormouse.".to_string(),
+                    (true, DormouseOutcome::Second) => "You discarded the second Dormouse.".to_string(),
+                    (true, DormouseOutcome::Cancelled(other)) => format!("You discarded the second Dormouse, nor you or {} will be awarded a token.", name(*other)),
+                    (false, DormouseOutcome::First) => format!("{} discarded the first Dormouse.", actor),
+                    (false, DormouseOutcome::Second) => format!("{} discarded the second Dormouse.", actor),
+                    (false, DormouseOutcome::Cancelled(other)) => format!("{} discarded the second Dormouse, nor they or {} will be awarded a token.", actor, name(*other)),
+                }
+            },
+        };
+        Some(text)
+    }
+}
+
+pub fn is_protected(state: &State, player: usize) -> bool {
+    state.discard[player].last().is_some_and(|card| card.protects())
+}
+
+pub fn no_possible_play(state: &State, card: &Card) -> bool {
+    let hand = state.hands[state.turn].unwrap();
+    let card_targetting = card.targetting() && !card.can_target_self();
+    let hand_targetting = hand.targetting() && !hand.can_target_self();
+    if card_targetting && hand_targetting {
+        // Check if all players are protected
+        let mut all_protected = true;
+        for i in 0..state.players.len() {
+            if !state.out.contains(&i) && !is_protected(state, i) && i != state.turn {
+                all_protected = false;
+                break;
+            }
+        }
+        all_protected
+    } else {
+        false
+    }
+}
+
+/// Players that `card` may target this turn.
+pub fn valid_targets(state: &State, card: &Card) -> Vec<usize> {
+    let mut targets = Vec::new();
+    if !card.targetting() {
+        return targets;
+    }
+    for i in 0..state.players.len() {
+        if state.out.contains(&i) {
+            continue;
+        }
+        if i == state.turn {
+            if card.can_target_self() {
+                targets.push(i);
+            }
+            continue;
+        }
+        if !is_protected(state, i) {
+            targets.push(i);
+        }
+    }
+    targets
+}
+
+/// Cards a Guard may name.
+pub fn guesses() -> Vec<Card> {
+    crate::card::list_cards().into_iter().filter(|card| *card != Card::Guard).collect()
+}
+
+/// The Red Queen may not be kept while discarding Time or the Executioner.
+pub fn red_queen_blocks(card: &Card, kept: &Card) -> bool {
+    *kept == Card::RedQueen && matches!(card, Card::Time | Card::Executioner)
+}
+
+/// Every legal action for the player whose turn it is, holding the two cards in `hand`.
+pub fn legal_actions(state: &State, hand: [Card; 2]) -> Vec<Action> {
+    let mut actions = Vec::new();
+    for (i, card) in hand.iter().enumerate() {
+        let kept = hand[1 - i];
+        if i == 1 && hand[0] == hand[1] {
+            break;
+        }
+        if red_queen_blocks(card, &kept) {
+            continue;
+        }
+        if !card.targetting() {
+            actions.push(Action { card: *card, target: None, guess: None });
+            continue;
+        }
+
+        let targets = valid_targets(state, card);
+        if targets.is_empty() {
+            let mut turn_state = state.clone();
+            turn_state.hands[state.turn] = Some(kept);
+            if no_possible_play(&turn_state, card) {
+                actions.push(Action { card: *card, target: None, guess: None });
+            }
+            continue;
+        }
+        for target in targets {
+            if *card == Card::Guard {
+                for guess in guesses() {
+                    actions.push(Action { card: *card, target: Some(target), guess: Some(guess) });
+                }
+            } else {
+                actions.push(Action { card: *card, target: Some(target), guess: None });
+            }
+        }
+    }
+    actions
+}
+
+/// Draws up to two cards for the Executioner.
+pub fn executioner_draw(state: &mut State) -> Vec<Card> {
+    let mut drawn = Vec::new();
+    for _ in 0..2 {
+        if let Some(card) = state.deck.pop() {
+            drawn.push(card);
+        }
+    }
+    drawn
+}
+
+/// Keeps one of the drawn cards and places the old hand and the rest at the bottom of the deck.
+pub fn executioner_place(state: &mut State, drawn: &[Card], choice: ExecutionerChoice) -> Result<Option<Card>, PlayError> {
+    if drawn.is_empty() {
+        return Ok(None);
+    }
+    if choice.keep >= drawn.len() {
+        return Err(PlayError::InvalidCard);
+    }
+    let hand = state.hands[state.turn].ok_or(PlayError::InvalidHand)?;
+    let kept = drawn[choice.keep];
+    state.hands[state.turn] = Some(kept);
+    let rest: Vec<Card> = drawn.iter().enumerate().filter(|(i, _)| *i != choice.keep).map(|(_, card)| *card).collect();
+
+    // Place cards at bottom of deck
+    if choice.hand_on_top {
+        state.deck.insert(0, hand);
+        for card in rest {
+            state.deck.insert(0, card);
+        }
+    } else {
+        for card in rest {
+            state.deck.insert(0, card);
+        }
+        state.deck.insert(0, hand);
+    }
+    Ok(Some(kept))
+}
+
+/// Plays `action` for the player whose turn it is. Their hand must already hold the card they keep.
+pub fn play_card(state_: State, action: &Action, agent: &mut dyn Agent, events: &mut Vec<Event>) -> (TurnResult, State) {
+    let Some(hand) = state_.hands[state_.turn] else {
+        return (Err(PlayError::InvalidHand), state_);
+    };
+    if state_.out.contains(&state_.turn) {
+        return (Err(PlayError::InvalidPlayer), state_);
+    }
+    if !legal_actions(&state_, [action.card, hand]).contains(action) {
+        return (Err(PlayError::InvalidCard), state_);
+    }
+    let mut state = state_.clone();
+    let card = action.card;
+    let player = state.turn;
+
+    // Discard card
+    state.discard[player].push(card);
+
+    let event = match (card, action.target) {
+        (Card::Alice, _) => {
+            state.out.push(player);
+            Event::Alice { player }
+        },
+        (Card::RedQueen, _) => Event::RedQueen { player },
+        (Card::Executioner, _) => {
+            let drawn = executioner_draw(&mut state);
+            let kept = if drawn.is_empty() {
+                None
+            } else {
+                let choice = agent.choose_executioner(&state, hand, &drawn);
+                match executioner_place(&mut state, &drawn, choice) {
+                    Ok(kept) => kept,
+                    Err(error) => return (Err(error), state_),
+                }
+            };
+            Event::Executioner { player, drawn, kept }
+        },
+        (Card::Nobody, _) => Event::Protected { player },
+        (Card::Dormouse, _) => {
+            let outcome = match state.dormouse {
+                Some(other) if other == player => DormouseOutcome::Second,
+                Some(other) => {
+                    state.dormouse = None;
+                    DormouseOutcome::Cancelled(other)
+                },
+                None => {
+                    state.dormouse = Some(player);
+                    DormouseOutcome::First
+                },
+            };
+            Event::Dormouse { player, outcome }
+        },
+        (_, None) => Event::NoTarget { player, card },
+        (Card::Time, Some(target)) => {
+            state.hands.swap(player, target);
+            Event::Swapped { player, target }
+        },
+        (Card::KnaveOfHearts, Some(target)) => {
+            // Makes target discard a card and draw a new one
+            let discarded = state.hands[target].unwrap();
+            state.discard[target].push(discarded);
+            state.hands[target] = None;
+            let mut drew = false;
+            if discarded == Card::Alice {
+                state.out.push(target);
+            } else if let Some(card) = state.deck.pop() {
+                state.hands[target] = Some(card);
+                drew = true;
+            } else {
+                state.out.push(target);
+            }
+            Event::Knave { player, target, card: discarded, drew }
+        },
+        (Card::Tweedies, Some(target)) => {
+            let event = Event::Compared { player, target, player_card: state.hands[player].unwrap(), target_card: state.hands[target].unwrap() };
+            if let Some(loser) = event.eliminated() {
+                state.out.push(loser);
+            }
+            event
+        },
+        (Card::Wilkins, Some(target)) => Event::Revealed { player, target, card: state.hands[target].unwrap() },
+        (Card::Guard, Some(target)) => {
+            let guess = action.guess.unwrap();
+            let correct = state.hands[target] == Some(guess);
+            if correct {
+                state.out.push(target);
+            }
+            Event::Guessed { player, target, guess, correct }
+        },
+    };
+    events.push(event);
+
+    (Ok(RoundStatus::Continue), state)
+}
+
+/// Lets the player whose turn it is draw a card and have `agent` decide what to play.
+pub fn play_turn(state_: State, agent: &mut dyn Agent, events: &mut Vec<Event>) -> (RoundStatus, State) {
+    // Check if game is over
+    if state_.out.len() == state_.players.len() - 1 {
+        return (RoundStatus::End, state_);
+    }
+
+    // Check if player is out
+    if state_.out.contains(&state_.turn) {
+        return (RoundStatus::Skip, state_);
+    }
+
+    // Check if there is cards in the deck
+    if state_.deck.is_empty() {
+        return (RoundStatus::TieBreaker, state_);
+    }
+
+    let mut state = state_.clone();
+    let card1 = state.hands[state.turn].unwrap();
+    let card2 = state.deck.pop().unwrap();
+    let legal = legal_actions(&state, [card1, card2]);
+    loop {
+        let action = agent.choose_action(&state, [card1, card2], &legal);
+        let mut turn_state = state.clone();
+        turn_state.hands[turn_state.turn] = Some(if action.card == card1 { card2 } else { card1 });
+        let (result, turn_state) = play_card(turn_state, &action, agent, events);
+        match result {
+            Ok(round_status) => return (round_status, turn_state),
+            Err(play_error) => agent.invalid_action(&state, &action, &play_error),
+        }
+    }
+}
+
+pub fn next_turn(state: &mut State) {
+    state.turn += 1;
+    if state.turn >= state.players.len() {
+        state.turn = 0;
+    }
+}
+
+pub fn play_tie_breaker(state_: State) -> State {
+    let mut state = state_.clone();
+
+    // Check the hands of all players that are not out
+    let mut hands = Vec::new();
+    for i in 0..state.players.len() {
+        if !state.out.contains(&i) {
+            hands.push(state.hands[i].unwrap());
+        }
+    }
+
+    // Check what card has the highest value
+    let mut highest_card = hands[0];
+    for card in hands {
+        if card.value() > highest_card.value() {
+            highest_card = card;
+        }
+    }
+
+    // All players with hands lower than the highest card are out
+    for i in 0..state.players.len() {
+        if !state.out.contains(&i) && state.hands[i].unwrap().value() < highest_card.value() {
+            state.out.push(i);
+        }
+    }
+
+    state
+}
+
+/// Gives a token to every player still in the round and to the only player that discarded a Dormouse.
+/// Returns the winners of the round.
+pub fn award_tokens(state: &mut State) -> Vec<usize> {
+    let mut winners = Vec::new();
+    for i in 0..state.players.len() {
+        if !state.out.contains(&i) {
+            state.tokens[i] += 1;
+            winners.push(i);
+        }
+    }
+    if let Some(player) = state.dormouse {
+        state.tokens[player] += 1;
+    }
+    winners
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub fn test_state() -> State {
+        State {
+            deck: create_deck(),
+            discard: vec![vec![], vec![], vec![]],
+            players: vec!["Alice".to_string(), "Bob".to_string(), "Carol".to_string()],
+            out: Vec::new(),
+            hands: vec![Some(Card::Guard), Some(Card::Wilkins), Some(Card::Alice)],
+            tokens: vec![0, 0, 0],
+            round: 1,
+            turn: 0,
+            dormouse: None,
+        }
+    }
+
+    #[test]
+    fn test_deal_next_round() {
+        let mut state = test_state();
+        state.tokens = vec![2, 0, 1];
+        state.round = 3;
+        let next = deal_next_round(&state, 1, &mut thread_rng());
+        assert_eq!(next.round, 4);
+        assert_eq!(next.players, vec!["Bob", "Carol", "Alice"]);
+        assert_eq!(next.tokens, vec![0, 1, 2]);
+        assert_eq!(next.hands.len(), 3);
+    }
+
+    #[test]
+    fn test_no_possible_play() {
+        let mut state = State {
+            deck: create_deck(),
+            discard: vec![vec![Card::Nobody], vec![Card::Nobody]],
+            players: vec!["Alice".to_string(), "Bob".to_string()],
+            out: Vec::new(),
+            hands: vec![Some(Card::Guard), Some(Card::Guard)],
+            tokens: vec![0, 0],
+            round: 0,
+            turn: 0,
+            dormouse: None,
+        };
+
+        let card = Card::Guard;
+        assert!(no_possible_play(&state, &card));
+
+        state.hands[0] = Some(Card::Time);
+        assert!(no_possible_play(&state, &card));
+
+        state.hands[0] = Some(Card::Guard);
+        let card = Card::Wilkins;
+        assert!(no_possible_play(&state, &card));
+
+        let card = Card::RedQueen;
+        assert!(!no_possible_play(&state, &card));
+    }
+
+    #[test]
+    fn test_legal_actions() {
+        let mut state = test_state();
+        let actions = legal_actions(&state, [Card::Guard, Card::RedQueen]);
+        // 2 targets with 9 guesses each and the Red Queen
+        assert_eq!(actions.len(), 19);
+        assert!(actions.iter().all(|action| action.target != Some(0)));
+
+        // The Red Queen must be discarded with Time
+        let actions = legal_actions(&state, [Card::Time, Card::RedQueen]);
+        assert_eq!(actions, vec![Action { card: Card::RedQueen, target: None, guess: None }]);
+
+        // The Knave may target yourself
+        let actions = legal_actions(&state, [Card::KnaveOfHearts, Card::KnaveOfHearts]);
+        assert_eq!(actions.len(), 3);
+
+        // Nobody protects
+        state.discard[1].push(Card::Nobody);
+        state.discard[2].push(Card::Nobody);
+        let actions = legal_actions(&state, [Card::Guard, Card::Wilkins]);
+        assert_eq!(actions.len(), 2);
+        assert!(actions.iter().all(|action| action.target.is_none()));
+    }
+
+    #[test]
+    fn test_executioner_place() {
+        let mut state = test_state();
+        state.deck = vec![Card::Dormouse, Card::Nobody, Card::Time];
+        let drawn = executioner_draw(&mut state);
+        assert_eq!(drawn, vec![Card::Time, Card::Nobody]);
+        let kept = executioner_place(&mut state, &drawn, ExecutionerChoice { keep: 1, hand_on_top: true }).unwrap();
+        assert_eq!(kept, Some(Card::Nobody));
+        assert_eq!(state.hands[0], Some(Card::Nobody));
+        assert_eq!(state.deck, vec![Card::Time, Card::Guard, Card::Dormouse]);
+    }
+}
@@ -0,0 +1,133 @@
+use crate::belief::{compare, Knowledge};
+use crate::bots::HeuristicBot;
+use crate::card::Card;
+use crate::game::{Action, State};
+use crate::solver::{win_chances, ENDGAME_DECK, MAX_ASSIGNMENTS};
+
+/// The play the endgame bot recommends, with why.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hint {
+    pub action: Action,
+    pub reason: String,
+    /// What playing the other card would do instead, if it can be played at all.
+    pub instead: Option<(Action, String)>,
+}
+
+fn chance(chance: f64) -> String {
+    format!("{:.0}%", 100.0 * chance)
+}
+
+/// What `action` does for the player to act holding `hand`, from what `knowledge` tells them,
+/// e.g. "discarding Time would hand Alice to Bob, who you peeked with Wilkins".
+pub fn explain(state: &State, knowledge: &Knowledge, hand: [Card; 2], action: &Action) -> String {
+    let me = state.turn;
+    let kept = if action.card == hand[0] { hand[1] } else { hand[0] };
+    let name = |player: usize| state.players[player].clone();
+    let distribution = |player: usize| knowledge.hand_distribution(state, &hand, player);
+    let mut reason = match (action.card, action.target) {
+        (Card::Alice, _) => "discarding Alice puts you out".to_string(),
+        (Card::Guard, Some(target)) => {
+            let guess = action.guess.unwrap();
+            match knowledge.known[target] {
+                Some(card) if card == guess => format!("you know {} holds {}", name(target), guess.name()),
+                Some(card) => format!("you know {} holds {}, so the guess is wasted", name(target), card.name()),
+                None => format!("{} holds {} with a chance of {}", name(target), guess.name(), chance(distribution(target)[guess.value()])),
+            }
+        },
+        (Card::Tweedies, Some(target)) => match knowledge.known[target] {
+            Some(card) if card.value() < kept.value() => format!("your {} beats the {} you know {} holds", kept.name(), card.name(), name(target)),
+            Some(card) if card.value() > kept.value() => format!("your {} loses to the {} you know {} holds", kept.name(), card.name(), name(target)),
+            Some(card) => format!("your {} ties with the {} you know {} holds", kept.name(), card.name(), name(target)),
+            None => {
+                let (lower, higher) = compare(&distribution(target), &kept);
+                format!("your {} beats {} with a chance of {} and loses with a chance of {}", kept.name(), name(target), chance(lower), chance(higher))
+            },
+        },
+        (Card::KnaveOfHearts, Some(target)) if target == me => format!("you trade your {} for a new card", kept.name()),
+        (Card::KnaveOfHearts, Some(target)) => match knowledge.known[target] {
+            Some(Card::Alice) => format!("{} holds Alice, so discarding it puts them out", name(target)),
+            Some(card) => format!("{} discards their {} and draws a new card", name(target), card.name()),
+            None => format!("{} holds Alice with a chance of {}", name(target), chance(distribution(target)[Card::Alice.value()])),
+        },
+        (Card::Time, Some(target)) => {
+            let giving = format!("discarding Time would hand {} to {}", kept.name(), name(target));
+            match knowledge.known[target] {
+                Some(card) => format!("{}, who you know holds {}", giving, card.name()),
+                None => giving,
+            }
+        },
+        (Card::Wilkins, Some(target)) if knowledge.known[target].is_some() => format!("you already know {} holds {}", name(target), knowledge.known[target].unwrap().name()),
+        (Card::Wilkins, Some(target)) => format!("you see what {} holds", name(target)),
+        (Card::Nobody, _) => "you are protected until your next turn".to_string(),
+        (Card::Executioner, _) => format!("you choose between your {} and the next cards in the deck", kept.name()),
+        (Card::Dormouse, _) => match state.dormouse {
+            None => "it wins you a token if nobody else discards a Dormouse".to_string(),
+            Some(player) if player == me => "you already discarded a Dormouse".to_string(),
+            Some(player) => format!("it takes the Dormouse token away from {}", name(player)),
+        },
+        (Card::RedQueen, _) => "the Red Queen gives nothing away".to_string(),
+        (card, None) => format!("{} has nobody to target", card.name()),
+    };
+    if action.card != Card::Alice && action.card != Card::Time && !(action.card == Card::KnaveOfHearts && action.target == Some(me)) {
+        reason += &format!(", and you keep {}", kept.name());
+    }
+    reason
+}
+
+/// What the endgame bot would play in the place of the player to act, holding `hand` and
+/// knowing what `knowledge` knows.
+pub fn hint(state: &State, knowledge: &Knowledge, hand: [Card; 2], legal: &[Action]) -> Hint {
+    let mut bot = HeuristicBot::with_endgame(0);
+    bot.set_knowledge(knowledge.clone());
+    let scores = bot.scores(state, hand, legal);
+    let best = (0..legal.len()).max_by(|a, b| scores[*a].total_cmp(&scores[*b])).unwrap();
+    let action = legal[best];
+
+    // The best play of the other card
+    let other = (0..legal.len()).filter(|i| legal[*i].card != action.card).max_by(|a, b| scores[*a].total_cmp(&scores[*b]));
+
+    let chances = if state.deck.len() <= ENDGAME_DECK { win_chances(state, knowledge, hand, MAX_ASSIGNMENTS) } else { None };
+    let describe = |action: &Action| {
+        let reason = explain(state, knowledge, hand, action);
+        match chances.as_ref().and_then(|chances| chances.iter().find(|(other, _)| other == action)) {
+            Some((_, win)) => format!("{} ({} to win the round)", reason, chance(*win)),
+            None => reason,
+        }
+    };
+    Hint { action, reason: describe(&action), instead: other.map(|i| (legal[i], describe(&legal[i]))) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::create_deck;
+    use crate::game::{legal_actions, Event};
+
+    #[test]
+    fn test_hint() {
+        let mut state = State {
+            deck: create_deck(),
+            discard: vec![vec![], vec![]],
+            players: vec!["Ann".to_string(), "Bob".to_string()],
+            out: Vec::new(),
+            hands: vec![Some(Card::Time), Some(Card::Nobody)],
+            tokens: vec![0, 0],
+            round: 1,
+            turn: 0,
+            dormouse: None,
+        };
+        let mut knowledge = Knowledge::new(&state, 0);
+        knowledge.observe(&state, &Event::Revealed { player: 0, target: 1, card: Card::Nobody });
+
+        // Ann knows Bob holds a Nobody and guesses it with her Guard
+        let hand = [Card::Time, Card::Guard];
+        let hint = hint(&state, &knowledge, hand, &legal_actions(&state, hand));
+        assert_eq!(hint.action, Action { card: Card::Guard, target: Some(1), guess: Some(Card::Nobody) });
+        assert_eq!(hint.reason, "you know Bob holds Nobody, and you keep Time");
+        assert_eq!(hint.instead.unwrap().1, "discarding Time would hand Guard to Bob, who you know holds Nobody");
+
+        state.hands[0] = Some(Card::Alice);
+        let time = Action { card: Card::Time, target: Some(1), guess: None };
+        assert_eq!(explain(&state, &knowledge, [Card::Time, Card::Alice], &time), "discarding Time would hand Alice to Bob, who you know holds Nobody");
+    }
+}
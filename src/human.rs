@@ -0,0 +1,467 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::agent::Agent;
+use crate::belief::Knowledge;
+use crate::card::{list_cards, Card};
+use crate::clock::format_time;
+use crate::game::{guesses, is_protected, Action, Event, ExecutionerChoice, PlayError, State};
+use crate::hint::hint;
+use crate::model::OpponentModel;
+
+/// How often the time left is redrawn while a timed player types.
+const TICK: Duration = Duration::from_secs(1);
+
+/// What is typed at the terminal, read line by line on a thread of its own so a timed decision
+/// can stop waiting for it.
+pub struct Input {
+    lines: Receiver<String>,
+    /// The words of a line that were not read yet.
+    words: VecDeque<String>,
+}
+
+impl Input {
+    pub fn new(lines: Receiver<String>) -> Input {
+        Input { lines, words: VecDeque::new() }
+    }
+
+    /// The terminal, shared by everything that reads from it.
+    pub fn terminal() -> Arc<Mutex<Input>> {
+        static TERMINAL: OnceLock<Arc<Mutex<Input>>> = OnceLock::new();
+        TERMINAL.get_or_init(|| {
+            let (sender, lines) = mpsc::channel();
+            thread::spawn(move || {
+                for line in io::stdin().lock().lines() {
+                    let Ok(line) = line else { break };
+                    if sender.send(line).is_err() {
+                        break;
+                    }
+                }
+            });
+            Arc::new(Mutex::new(Input::new(lines)))
+        }).clone()
+    }
+
+    /// The next word typed, or `None` if `deadline` passes first or the input is closed. `tick`
+    /// is called every `TICK` while waiting for a deadline.
+    fn word(&mut self, deadline: Option<Instant>, tick: &mut dyn FnMut()) -> Option<String> {
+        io::stdout().flush().ok();
+        loop {
+            if let Some(word) = self.words.pop_front() {
+                return Some(word);
+            }
+            let line = match deadline {
+                None => self.lines.recv().ok()?,
+                Some(deadline) => {
+                    let left = deadline.saturating_duration_since(Instant::now());
+                    if left.is_zero() {
+                        return None;
+                    }
+                    match self.lines.recv_timeout(left.min(TICK)) {
+                        Ok(line) => line,
+                        Err(RecvTimeoutError::Timeout) => {
+                            tick();
+                            continue;
+                        },
+                        Err(RecvTimeoutError::Disconnected) => return None,
+                    }
+                },
+            };
+            self.words.extend(line.split_whitespace().map(|word| word.to_string()));
+        }
+    }
+
+    /// The rest of the line being typed, or the next one. `None` once the input is closed.
+    fn line(&mut self) -> Option<String> {
+        io::stdout().flush().ok();
+        if !self.words.is_empty() {
+            return Some(Vec::from(std::mem::take(&mut self.words)).join(" "));
+        }
+        self.lines.recv().ok()
+    }
+}
+
+/// Reads the next word typed at the terminal as a `T`, asking again until it is one.
+pub fn read<T: FromStr>() -> T {
+    let terminal = Input::terminal();
+    let mut input = terminal.lock().unwrap();
+    loop {
+        let word = input.word(None, &mut || {}).expect("The terminal was closed.");
+        match word.parse() {
+            Ok(value) => return value,
+            Err(_) => print!("\"{}\" is not a valid answer. Try again: ", word),
+        }
+    }
+}
+
+/// Reads a line typed at the terminal, or `None` once it is closed.
+pub fn read_line() -> Option<String> {
+    Input::terminal().lock().unwrap().line()
+}
+
+pub fn clear_screen() {
+    print!("\x1B[2J\x1B[1;1H");
+}
+
+/// Waits until the player types 1.
+pub fn wait_for_one() {
+    while read::<i32>() != 1 {}
+}
+
+pub fn print_table(state: &State) {
+    println!("\n\n================= {} =================", state.players[state.turn]);
+    println!("{}'s turn", state.players[state.turn]);
+    println!("Round {}", state.round);
+    println!("Discard piles:");
+    for (i, player) in state.players.iter().enumerate() {
+        if state.out.contains(&i) {
+            print!("\t{}. {} (Out): ", i + 1, player);
+        } else {
+            print!("\t{}. {}: ", i + 1, player);
+        }
+        let names: Vec<String> = state.discard[i].iter().map(|card| card.name()).collect();
+        println!("{}", names.join(", "));
+    }
+    let tokens: Vec<String> = state.players.iter().zip(state.tokens.iter()).map(|(player, tokens)| format!("{} {}", player, tokens)).collect();
+    println!("Tokens: {}.\n", tokens.join(", "));
+}
+
+/// The hot-seat players sitting at the terminal. With hints on, every player may ask what the
+/// endgame bot would play, which only uses what that player has seen.
+pub struct Human {
+    hints: bool,
+    /// Whether the players share the terminal, so it is cleared and waits between turns.
+    shared: bool,
+    knowledge: Vec<Option<Knowledge>>,
+    model: Option<OpponentModel>,
+    /// When the decision being made is due and when the bank runs out, at a timed table.
+    clock: Option<(Instant, Option<Instant>)>,
+    input: Arc<Mutex<Input>>,
+}
+
+impl Human {
+    pub fn new(hints: bool) -> Human {
+        Human { hints, shared: true, knowledge: Vec::new(), model: None, clock: None, input: Input::terminal() }
+    }
+
+    /// One player at their own terminal, as when playing over the network.
+    pub fn alone() -> Human {
+        Human { shared: false, ..Human::new(false) }
+    }
+
+    /// Lets the hints use what `model` learned about the players at the table.
+    pub fn set_model(&mut self, model: OpponentModel) {
+        self.model = Some(model);
+    }
+
+    /// Shows the time left to decide and in the bank, if the table is timed.
+    fn print_clock(&self) {
+        let Some((deadline, bank)) = self.clock else { return };
+        let left = format_time(deadline.saturating_duration_since(Instant::now()));
+        match bank {
+            Some(bank) => println!("Time left: {} (bank {}).", left, format_time(bank.saturating_duration_since(Instant::now()))),
+            None => println!("Time left: {}.", left),
+        }
+    }
+
+    /// Whether the time to decide ran out.
+    fn time_is_up(&self) -> bool {
+        self.clock.is_some_and(|(deadline, _)| Instant::now() >= deadline)
+    }
+
+    /// Prints `prompt` and reads the answer as a `T`, asking again until it is one. At a timed
+    /// table the time left stands in front of the prompt and counts down in place while the
+    /// player types, and `None` is returned once it runs out.
+    fn ask<T: FromStr>(&self, prompt: &str) -> Option<T> {
+        let deadline = self.clock.map(|(deadline, _)| deadline);
+        let stamp = || deadline.map_or(String::new(), |deadline| format!("[{:>5}] ", format_time(deadline.saturating_duration_since(Instant::now()))));
+        print!("{}{}", stamp(), prompt);
+        let mut input = self.input.lock().unwrap();
+        loop {
+            // Rewrites the time at the start of the line, leaving the cursor where the player types
+            let mut tick = || {
+                print!("\x1B7\r{}\x1B8", stamp());
+                io::stdout().flush().ok();
+            };
+            let Some(word) = input.word(deadline, &mut tick) else {
+                assert!(deadline.is_some(), "The terminal was closed.");
+                println!();
+                return None;
+            };
+            match word.parse() {
+                Ok(value) => return Some(value),
+                Err(_) => print!("{}\"{}\" is not a valid answer. Try again: ", stamp(), word),
+            }
+        }
+    }
+
+    /// Asks who to target among the players `options` allows. Returns `None` if the player
+    /// cancelled or ran out of time.
+    fn play_target(&self, state: &State, card: &Card, options: &[Action]) -> Option<Action> {
+        // Check if both cards player could play needs a target that is not self
+        if options[0].target.is_none() {
+            println!("All players are protected.");
+            println!("0: Cancel.");
+            println!("1: Discard without any target.");
+            loop {
+                let target: i32 = self.ask(": ")?;
+                match target {
+                    0 => return None,
+                    1 => return Some(options[0]),
+                    _ => {
+                        println!("That is not a valid option.");
+                        continue;
+                    }
+                }
+            }
+        }
+
+        println!("Who would you like to target?");
+        println!("0: Cancel.");
+        for (i, player) in state.players.iter().enumerate() {
+            if state.hands[i].is_some() {
+                println!("{}: {}", i + 1, player);
+            }
+        }
+
+        let mut target: usize;
+        loop {
+            target = self.ask("Target player: ")?;
+
+            // Check if user cancelled
+            if target == 0 {
+                return None;
+            }
+
+            // Check if target is valid
+            if target > state.players.len() {
+                println!("That is not a valid player.");
+                continue;
+            }
+
+            // Check if target is out
+            if state.out.contains(&(target - 1)) {
+                println!("That player is out.");
+                continue;
+            }
+
+            // Check if target is self
+            if target - 1 == state.turn && !card.can_target_self() {
+                println!("You cannot target yourself.");
+                continue;
+            }
+
+            // Check if target is protected
+            if target - 1 != state.turn && is_protected(state, target - 1) {
+                println!("That player is protected.");
+                continue;
+            }
+
+            break;
+        }
+
+        let target = target - 1;
+        if *card != Card::Guard {
+            return options.iter().find(|action| action.target == Some(target)).copied();
+        }
+
+        println!("What card would you like to guess?");
+
+        // List all cards except the Guard
+        for card in guesses() {
+            println!("{}. {}", card.value(), card);
+        }
+
+        // Get guess
+        let mut guess: usize;
+        loop {
+            guess = self.ask(": ")?;
+            if guess != Card::Guard.value() && guess < list_cards().len() {
+                break;
+            } else {
+                println!("That is not a valid card.");
+            }
+        }
+        let guess = Card::from_value(guess);
+        options.iter().find(|action| action.target == Some(target) && action.guess == guess).copied()
+    }
+
+    fn print_hint(&self, state: &State, hand: [Card; 2], legal: &[Action]) {
+        let knowledge = self.knowledge.get(state.turn).cloned().flatten().unwrap_or(Knowledge::new(state, state.turn));
+        let hint = hint(state, &knowledge, hand, legal);
+        println!("Hint: play {}: {}.", hint.action.describe(&state.players), hint.reason);
+        if let Some((action, reason)) = hint.instead {
+            println!("Playing {} instead: {}.", action.describe(&state.players), reason);
+        }
+    }
+
+    /// The action the player picks, or `None` if they ran out of time.
+    fn pick_action(&self, state: &State, hand: [Card; 2], legal: &[Action]) -> Option<Action> {
+        if self.shared {
+            clear_screen();
+            print_table(state);
+            println!("Type 1 to draw a card.");
+            while self.ask::<i32>(": ")? != 1 {}
+        } else {
+            print_table(state);
+        }
+        loop {
+            if self.time_is_up() {
+                return None;
+            }
+            self.print_clock();
+            println!("What would you like to do?");
+            println!("1. Discard/Play: {}", hand[0]);
+            println!("2. Discard/Play: {}", hand[1]);
+            if self.hints {
+                println!("3. Hint");
+            }
+            let Some(choice) = self.ask::<i32>(": ") else { continue };
+            let card = match choice {
+                1 => hand[0],
+                2 => hand[1],
+                3 if self.hints => {
+                    self.print_hint(state, hand, legal);
+                    continue;
+                },
+                _ => {
+                    println!("Invalid choice. Try again.");
+                    continue;
+                }
+            };
+            let options: Vec<Action> = legal.iter().filter(|action| action.card == card).copied().collect();
+            if options.is_empty() {
+                if card.targetting() {
+                    println!("There is no one you can target with {}. Try again.", card.name());
+                } else {
+                    println!("You must discard the Red Queen. Try again.");
+                }
+                continue;
+            }
+            if !card.targetting() {
+                return Some(options[0]);
+            }
+            if let Some(action) = self.play_target(state, &card, &options) {
+                return Some(action);
+            }
+        }
+    }
+
+    /// What the player keeps after playing the Executioner, or `None` if they ran out of time.
+    fn pick_executioner(&self, hand: Card, drawn: &[Card]) -> Option<ExecutionerChoice> {
+        println!("You draw {} cards.", drawn.len());
+        self.print_clock();
+        let mut keep: usize = 1;
+        if drawn.len() > 1 {
+            println!("You drew:\n1. {}\n2. {}", drawn[0], drawn[1]);
+            println!("Which card would you like to keep?");
+            loop {
+                keep = self.ask(": ")?;
+                if keep == 1 || keep == 2 {
+                    break;
+                } else {
+                    println!("That is not a valid card.");
+                }
+            }
+        } else {
+            println!("You drew:\n1. {}", drawn[0]);
+        }
+        let card = drawn.iter().enumerate().find(|(i, _)| *i != keep - 1).map(|(_, card)| *card);
+        let Some(card) = card else {
+            return Some(ExecutionerChoice { keep: keep - 1, hand_on_top: true });
+        };
+
+        println!("You will now place the following cards at the bottom of the deck:\n1. {}\n2. {}", hand, card);
+        println!("Which order would you like to place them in? (1 or 2):\n1. \"{}\" on top of \"{}\"\n2. \"{}\" on top of \"{}\"", hand.name(), card.name(), card.name(), hand.name());
+        let mut order: i32;
+        loop {
+            order = self.ask(": ")?;
+            if order == 1 || order == 2 {
+                break;
+            } else {
+                println!("That is not a valid order.");
+            }
+        }
+        Some(ExecutionerChoice { keep: keep - 1, hand_on_top: order == 1 })
+    }
+}
+
+impl Agent for Human {
+    fn start_round(&mut self, state: &State, seat: usize) {
+        self.knowledge.resize(state.players.len(), None);
+        let mut knowledge = Knowledge::new(state, seat);
+        if let Some(model) = &self.model {
+            knowledge.use_model(state, model);
+        }
+        self.knowledge[seat] = Some(knowledge);
+    }
+
+    fn choose_action(&mut self, state: &State, hand: [Card; 2], legal: &[Action]) -> Action {
+        // A player out of time gets the default action of the clock in place of this one
+        let action = self.pick_action(state, hand, legal).unwrap_or(legal[0]);
+        self.clock = None;
+        // The card kept is in hand before the play is seen, so a swap passes on the right one
+        if let Some(knowledge) = self.knowledge.get_mut(state.turn).and_then(Option::as_mut) {
+            knowledge.hand = Some(if action.card == hand[0] { hand[1] } else { hand[0] });
+        }
+        action
+    }
+
+    fn choose_executioner(&mut self, _state: &State, hand: Card, drawn: &[Card]) -> ExecutionerChoice {
+        let choice = self.pick_executioner(hand, drawn).unwrap_or(ExecutionerChoice { keep: 0, hand_on_top: true });
+        self.clock = None;
+        choice
+    }
+
+    fn start_clock(&mut self, left: Duration, bank: Option<Duration>) {
+        let now = Instant::now();
+        self.clock = now.checked_add(left).map(|deadline| (deadline, bank.and_then(|bank| now.checked_add(bank))));
+    }
+
+    fn invalid_action(&mut self, _state: &State, _action: &Action, _error: &PlayError) {
+        println!("Invalid choice. Try again.");
+    }
+
+    fn observe(&mut self, state: &State, event: &Event) {
+        for knowledge in self.knowledge.iter_mut().flatten() {
+            knowledge.observe(state, event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{Clock, TimeoutAction, Timed};
+    use crate::game::{deal, legal_actions};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_silent_player_times_out() {
+        let (sender, lines) = mpsc::channel();
+        let input = Arc::new(Mutex::new(Input::new(lines)));
+        let human = Human { hints: false, shared: false, knowledge: Vec::new(), model: None, clock: None, input };
+        let clock = Clock { decision: Some(Duration::from_millis(300)), bank: None, timeout: TimeoutAction::Lower };
+        let mut timed = Timed::new(Box::new(human), clock);
+        let mut state = deal(vec!["Ann".to_string(), "Bob".to_string()], vec![0, 0], 1, &mut StdRng::seed_from_u64(1));
+        state.hands[0] = Some(Card::Guard);
+        let hand = [Card::Guard, Card::Nobody];
+        let legal = legal_actions(&state, hand);
+
+        // Nothing is typed, so the default is played once the time is up
+        let started = Instant::now();
+        assert_eq!(timed.choose_action(&state, hand, &legal).card, Card::Guard);
+        assert!(started.elapsed() >= Duration::from_millis(300));
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        // An answer in time stands
+        sender.send("2".to_string()).unwrap();
+        assert_eq!(timed.choose_action(&state, hand, &legal).card, Card::Nobody);
+    }
+}
@@ -0,0 +1,338 @@
+use std::fmt;
+
+/// A JSON value, with the fields of objects kept in the order they were written.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn object(fields: Vec<(&str, Json)>) -> Json {
+        Json::Object(fields.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+    }
+
+    pub fn array<T: Into<Json>>(values: impl IntoIterator<Item = T>) -> Json {
+        Json::Array(values.into_iter().map(Into::into).collect())
+    }
+
+    /// The field `key` of an object.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The value if it is a whole number that is not negative.
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Json::Number(number) if *number >= 0.0 && number.fract() == 0.0 => Some(*number as usize),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = Parser { chars: text.chars().collect(), at: 0, depth: 0 };
+        let value = parser.value()?;
+        parser.skip_space();
+        match parser.chars.get(parser.at) {
+            None => Ok(value),
+            Some(c) => Err(format!("Unexpected \"{}\" after the value at {}.", c, parser.at)),
+        }
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Json {
+        Json::Bool(value)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(value: usize) -> Json {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<i32> for Json {
+    fn from(value: i32) -> Json {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<u64> for Json {
+    fn from(value: u64) -> Json {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Json {
+        Json::String(value.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Json {
+        Json::String(value)
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Json {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+impl fmt::Display for Json {
+    /// Writes the value on one line.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(number) => write!(f, "{}", number),
+            Json::String(text) => write_string(f, text),
+            Json::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            },
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            },
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in text.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+/// How deeply arrays and objects may be nested in the text parsed.
+const MAX_DEPTH: usize = 64;
+
+struct Parser {
+    chars: Vec<char>,
+    at: usize,
+    /// How many values the parser is inside of.
+    depth: usize,
+}
+
+impl Parser {
+    fn skip_space(&mut self) {
+        while self.chars.get(self.at).is_some_and(|c| c.is_whitespace()) {
+            self.at += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_space();
+        match self.chars.get(self.at) {
+            Some(c) if *c == expected => {
+                self.at += 1;
+                Ok(())
+            },
+            Some(c) => Err(format!("Expected \"{}\" but found \"{}\" at {}.", expected, c, self.at)),
+            None => Err(format!("Expected \"{}\" but the text ended.", expected)),
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        if self.depth >= MAX_DEPTH {
+            return Err(format!("Values are nested more than {} deep.", MAX_DEPTH));
+        }
+        self.depth += 1;
+        let value = self.nested_value();
+        self.depth -= 1;
+        value
+    }
+
+    fn nested_value(&mut self) -> Result<Json, String> {
+        self.skip_space();
+        let rest: String = self.chars[self.at..].iter().take(5).collect();
+        for (word, value) in [("null", Json::Null), ("true", Json::Bool(true)), ("false", Json::Bool(false))] {
+            if rest.starts_with(word) {
+                self.at += word.len();
+                return Ok(value);
+            }
+        }
+        match self.chars.get(self.at) {
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some('[') => {
+                self.at += 1;
+                let mut values = Vec::new();
+                self.skip_space();
+                if self.chars.get(self.at) == Some(&']') {
+                    self.at += 1;
+                    return Ok(Json::Array(values));
+                }
+                loop {
+                    values.push(self.value()?);
+                    self.skip_space();
+                    if self.chars.get(self.at) == Some(&']') {
+                        self.at += 1;
+                        return Ok(Json::Array(values));
+                    }
+                    self.expect(',')?;
+                }
+            },
+            Some('{') => {
+                self.at += 1;
+                let mut fields = Vec::new();
+                self.skip_space();
+                if self.chars.get(self.at) == Some(&'}') {
+                    self.at += 1;
+                    return Ok(Json::Object(fields));
+                }
+                loop {
+                    self.skip_space();
+                    let key = self.string()?;
+                    self.expect(':')?;
+                    fields.push((key, self.value()?));
+                    self.skip_space();
+                    if self.chars.get(self.at) == Some(&'}') {
+                        self.at += 1;
+                        return Ok(Json::Object(fields));
+                    }
+                    self.expect(',')?;
+                }
+            },
+            Some(c) if *c == '-' || c.is_ascii_digit() => {
+                let start = self.at;
+                while self.chars.get(self.at).is_some_and(|c| c.is_ascii_digit() || "+-.eE".contains(*c)) {
+                    self.at += 1;
+                }
+                let number: String = self.chars[start..self.at].iter().collect();
+                number.parse().map(Json::Number).map_err(|_| format!("\"{}\" is not a number.", number))
+            },
+            Some(c) => Err(format!("Unexpected \"{}\" at {}.", c, self.at)),
+            None => Err("Expected a value but the text ended.".to_string()),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut text = String::new();
+        loop {
+            let c = *self.chars.get(self.at).ok_or("A string is not closed.")?;
+            self.at += 1;
+            match c {
+                '"' => return Ok(text),
+                '\\' => {
+                    let escaped = *self.chars.get(self.at).ok_or("A string is not closed.")?;
+                    self.at += 1;
+                    match escaped {
+                        'n' => text.push('\n'),
+                        'r' => text.push('\r'),
+                        't' => text.push('\t'),
+                        'b' => text.push('\u{8}'),
+                        'f' => text.push('\u{c}'),
+                        'u' => {
+                            let mut code = self.hex()?;
+                            // The second half of a character outside the basic plane. Anything
+                            // else after the first half is read on its own.
+                            if (0xd800..0xdc00).contains(&code) && self.chars.get(self.at) == Some(&'\\') && self.chars.get(self.at + 1) == Some(&'u') {
+                                self.at += 2;
+                                match self.hex()? {
+                                    low @ 0xdc00..0xe000 => code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00),
+                                    _ => self.at -= 6,
+                                }
+                            }
+                            text.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+                        },
+                        c => text.push(c),
+                    }
+                },
+                c => text.push(c),
+            }
+        }
+    }
+
+    fn hex(&mut self) -> Result<u32, String> {
+        let digits: String = self.chars.get(self.at..self.at + 4).ok_or("A \\u escape is cut short.")?.iter().collect();
+        self.at += 4;
+        u32::from_str_radix(&digits, 16).map_err(|_| format!("\"{}\" is not hexadecimal.", digits))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_and_parse() {
+        let value = Json::object(vec![
+            ("type", "event".into()),
+            ("text", "Ann said \"hi\"\n".into()),
+            ("cards", Json::array([1usize, 9])),
+            ("target", Json::from(None::<usize>)),
+            ("nested", Json::object(vec![("ok", true.into()), ("tokens", (-2).into())])),
+        ]);
+        let text = value.to_string();
+        assert_eq!(text, r#"{"type":"event","text":"Ann said \"hi\"\n","cards":[1,9],"target":null,"nested":{"ok":true,"tokens":-2}}"#);
+        assert_eq!(Json::parse(&text).unwrap(), value);
+
+        let spaced = Json::parse(" { \"a\" : [ 1.5 , \"\\u00e9\\ud83d\\ude00\" ] } ").unwrap();
+        assert_eq!(spaced.get("a").unwrap().as_array().unwrap()[1].as_str(), Some("é😀"));
+        assert!(Json::parse("{\"a\":1,}").is_err());
+        assert!(Json::parse("[1] 2").is_err());
+
+        // A first half without its second is replaced, and what follows it is kept
+        assert_eq!(Json::parse("\"\\ud800\\u0041\"").unwrap().as_str(), Some("\u{fffd}A"));
+        assert_eq!(Json::parse("\"\\ud800x\"").unwrap().as_str(), Some("\u{fffd}x"));
+        // Text nested too deeply is refused rather than overflowing the stack
+        assert!(Json::parse(&"[".repeat(100_000)).is_err());
+        assert!(Json::parse(&format!("{}{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH))).is_ok());
+    }
+}
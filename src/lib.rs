@@ -0,0 +1,34 @@
+pub mod agent;
+pub mod analytics;
+pub mod belief;
+pub mod bots;
+pub mod card;
+pub mod client;
+pub mod clock;
+pub mod discovery;
+pub mod encoding;
+pub mod engine;
+pub mod env;
+pub mod game;
+pub mod hint;
+pub mod human;
+pub mod json;
+pub mod lobby;
+pub mod mail;
+pub mod model;
+pub mod options;
+pub mod profile;
+pub mod protocol;
+pub mod ratings;
+pub mod record;
+pub mod rest;
+pub mod review;
+pub mod server;
+pub mod seats;
+pub mod sim;
+pub mod solver;
+pub mod spectate;
+pub mod tournament;
+pub mod websocket;
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -0,0 +1,307 @@
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::discovery::{rules, Beacon, Listing};
+use crate::env::MAX_PLAYERS;
+use crate::protocol::{Message, RoomInfo};
+use crate::server::{play_table, read_greeting, unique_name, Connection, Greeting, Sessions, Settings, Transport};
+use crate::sim::{game_seed, StartPolicy};
+use crate::spectate::{Spectators, Watchers};
+
+// On a server started with `--lobby`, players are not seated as they join. After `welcome` the
+// server lists its rooms and waits for a lobby command:
+//
+// server: room <id> <players> <start> <seated> <ready> <waiting|playing>   for every room
+//         lobby <the room the player is in, or ->
+// client: rooms                        lists the rooms again
+//         create <players> [<start>]   opens a room and enters it, `start` being who starts the
+//                                      next round: rotate (the default), winner, loser or random
+//         enter <room>
+//         leave
+//         ready
+//         watch <room> [all]           watches the game of the room, as in `spectate.rs`
+//
+// Every command but `ready` is answered with the rooms again, after `error <why>` if it was
+// refused. A player who is ready stays in the room and is sent
+// server: wait <seated> <players>
+// every time someone sits down or gets ready there. Once every seat is taken and ready, the room
+// plays its game as on a server without a lobby, and closes when it is over. Spectators may
+// watch a room before its game starts, and stop being in the lobby.
+
+/// A table of the lobby. Players who are ready hand over their connection to it.
+struct Room {
+    id: usize,
+    players: usize,
+    start: StartPolicy,
+    waiting: Vec<String>,
+    ready: Vec<(String, Connection)>,
+    playing: bool,
+    /// The spectators of the room, until its game takes them.
+    spectators: Option<Spectators>,
+    watchers: Watchers,
+}
+
+impl Room {
+    fn info(&self) -> RoomInfo {
+        let seated = self.waiting.len() + self.ready.len();
+        RoomInfo { id: self.id, players: self.players, start: self.start, seated, ready: self.ready.len(), playing: self.playing }
+    }
+
+    /// Tells the players who are ready how full the room is.
+    fn send_wait(&mut self) {
+        let message = [Message::Wait { joined: self.waiting.len() + self.ready.len(), players: self.players }];
+        for (_, connection) in &mut self.ready {
+            connection.send(&message);
+        }
+    }
+}
+
+/// Every room of a server and the names of the players on it.
+#[derive(Default)]
+struct Lobby {
+    rooms: Vec<Room>,
+    names: Vec<String>,
+    next_room: usize,
+    /// The game of room `id` is dealt with `game_seed(seed, id)`.
+    seed: u64,
+    sessions: Sessions,
+    settings: Settings,
+}
+
+impl Lobby {
+    /// The rooms, as announced on the network.
+    fn listings(&self) -> Vec<Listing> {
+        let listing = |room: &Room| Listing { room: Some(room.id), players: room.players, seated: room.info().seated, playing: room.playing, rules: rules(room.start, self.settings.clock) };
+        self.rooms.iter().map(listing).collect()
+    }
+
+    fn message(&self, room: Option<usize>) -> Message {
+        Message::Lobby { rooms: self.rooms.iter().map(Room::info).collect(), room }
+    }
+
+    fn room(&mut self, id: usize) -> Result<&mut Room, String> {
+        self.rooms.iter_mut().find(|room| room.id == id).ok_or(format!("There is no room {}.", id))
+    }
+
+    fn create(&mut self, players: &str, start: &str) -> Result<usize, String> {
+        let players: usize = players.parse().map_err(|_| format!("\"{}\" is not a number of players.", players))?;
+        if !(2..=MAX_PLAYERS).contains(&players) {
+            return Err(format!("Rooms seat 2 to {} players.", MAX_PLAYERS));
+        }
+        let start = StartPolicy::from_name(start).ok_or(format!("\"{}\" is not a way to start rounds.", start))?;
+        self.next_room += 1;
+        let (spectators, watchers) = Spectators::new(self.settings.omniscient);
+        self.rooms.push(Room { id: self.next_room, players, start, waiting: Vec::new(), ready: Vec::new(), playing: false, spectators: Some(spectators), watchers });
+        Ok(self.next_room)
+    }
+
+    fn enter(&mut self, id: &str, name: &str) -> Result<usize, String> {
+        let id: usize = id.parse().map_err(|_| format!("\"{}\" is not a room.", id))?;
+        let room = self.room(id)?;
+        let info = room.info();
+        if info.playing || info.seated == info.players {
+            return Err(format!("Room {} is full.", id));
+        }
+        room.waiting.push(name.to_string());
+        room.send_wait();
+        Ok(id)
+    }
+
+    /// Where spectators of room `id` are handed over.
+    fn watchers(&mut self, id: &str, omniscient: bool) -> Result<Watchers, String> {
+        if omniscient && self.settings.omniscient.is_none() {
+            return Err("This server does not show the hands to spectators.".to_string());
+        }
+        let id: usize = id.parse().map_err(|_| format!("\"{}\" is not a room.", id))?;
+        Ok(self.room(id)?.watchers.clone())
+    }
+
+    /// Takes `name` out of room `id`, closing the room if nobody is left in it.
+    fn leave(&mut self, id: usize, name: &str) {
+        if let Ok(room) = self.room(id) {
+            room.waiting.retain(|waiting| waiting != name);
+            room.send_wait();
+        }
+        self.rooms.retain(|room| room.playing || !room.waiting.is_empty() || !room.ready.is_empty());
+    }
+}
+
+/// Marks `name` ready in room `id` and starts its game once every seat is ready.
+fn ready(shared: &Arc<Mutex<Lobby>>, lobby: &mut Lobby, id: usize, name: &str, connection: Connection) {
+    let seed = game_seed(lobby.seed, id as u64);
+    let Ok(room) = lobby.room(id) else { return };
+    room.waiting.retain(|waiting| waiting != name);
+    room.ready.push((name.to_string(), connection));
+    room.send_wait();
+    if room.ready.len() < room.players {
+        return;
+    }
+
+    room.playing = true;
+    let (names, connections): (Vec<String>, Vec<Connection>) = room.ready.drain(..).unzip();
+    let spectators = room.spectators.take().unwrap();
+    let settings = Settings { start: room.start, ..lobby.settings };
+    let sessions = lobby.sessions.clone();
+    let shared = shared.clone();
+    println!("Room {} starts a game between {}.", id, names.join(", "));
+    thread::spawn(move || {
+        let result = play_table(names.clone(), connections, settings, &sessions, spectators, &mut StdRng::seed_from_u64(seed), &mut |round, _| {
+            let winners: Vec<String> = round.winners.iter().map(|winner| round.end.players[*winner].clone()).collect();
+            println!("Room {}: round {} won by {}.", id, round.end.round, winners.join(", "));
+        });
+        let winner = result.winner.map_or("nobody".to_string(), |winner| names[winner].clone());
+        println!("Room {}: {} won the game after {} rounds.", id, winner, result.rounds);
+        let mut lobby = shared.lock().unwrap();
+        lobby.rooms.retain(|room| room.id != id);
+        lobby.names.retain(|name| !names.contains(name));
+    });
+}
+
+/// Greets a client and answers its lobby commands until it is ready in a room or leaves. A
+/// client that comes back is handed to its seat.
+fn visit(shared: &Arc<Mutex<Lobby>>, mut connection: Connection, address: SocketAddr) {
+    let name = match read_greeting(&mut connection) {
+        Ok(Greeting::Join(name)) => {
+            let mut lobby = shared.lock().unwrap();
+            let name = unique_name(&name, &lobby.names);
+            lobby.names.push(name.clone());
+            name
+        },
+        Ok(Greeting::Resume(token)) => {
+            let sessions = shared.lock().unwrap().sessions.clone();
+            if let Err(message) = sessions.resume(&token, connection) {
+                println!("{} could not come back: {}", address, message);
+            }
+            return;
+        },
+        Ok(Greeting::Watch(_)) => {
+            let message = "Join the lobby and pick a room to watch with watch <room>.".to_string();
+            connection.send(&[Message::Error { message }, Message::Quit]);
+            return;
+        },
+        Err(error) => {
+            println!("{} could not join: {}", address, error);
+            return;
+        },
+    };
+    connection.send(&[Message::Welcome { name: name.clone() }]);
+    println!("{} joined from {}.", name, address);
+
+    let mut room: Option<usize> = None;
+    loop {
+        let message = shared.lock().unwrap().message(room);
+        connection.send(&[message]);
+        let Some(answer) = connection.receive() else {
+            let mut lobby = shared.lock().unwrap();
+            if let Some(id) = room {
+                lobby.leave(id, &name);
+            }
+            lobby.names.retain(|taken| *taken != name);
+            println!("{} left.", name);
+            return;
+        };
+        let mut lobby = shared.lock().unwrap();
+        let words: Vec<&str> = answer.split_whitespace().collect();
+        let refused = match (&words[..], room) {
+            (["rooms"], _) => None,
+            (["create", ..] | ["enter", _] | ["watch", ..], Some(id)) => Some(format!("Leave room {} first.", id)),
+            (["create", players], None) => lobby.create(players, "rotate").and_then(|id| lobby.enter(&id.to_string(), &name)).map(|id| room = Some(id)).err(),
+            (["create", players, start], None) => lobby.create(players, start).and_then(|id| lobby.enter(&id.to_string(), &name)).map(|id| room = Some(id)).err(),
+            (["enter", id], None) => lobby.enter(id, &name).map(|id| room = Some(id)).err(),
+            (["leave"] | ["ready"], None) => Some("You are not in a room.".to_string()),
+            (["leave"], Some(id)) => {
+                lobby.leave(id, &name);
+                room = None;
+                None
+            },
+            (["ready"], Some(id)) => {
+                ready(shared, &mut lobby, id, &name, connection);
+                return;
+            },
+            (["watch", id], None) | (["watch", id, "all"], None) => match lobby.watchers(id, words.len() == 3) {
+                Ok(watchers) => {
+                    lobby.names.retain(|taken| *taken != name);
+                    if watchers.admit(connection, words.len() == 3).is_ok() {
+                        println!("{} watches room {}.", name, id);
+                    }
+                    return;
+                },
+                Err(message) => Some(message),
+            },
+            _ => Some(format!("\"{}\" is not a lobby command.", answer)),
+        };
+        if let Some(message) = refused {
+            connection.send(&[Message::Error { message }]);
+        }
+    }
+}
+
+/// Runs a lobby on `listener` until the server is stopped, each client being greeted on a
+/// thread of its own and each room playing on its own thread with `settings`. The rooms are
+/// announced on the network with `beacon`, if given.
+pub fn serve(listener: &TcpListener, transport: Transport, settings: Settings, seed: u64, beacon: Option<Beacon>) -> Result<(), String> {
+    let shared = Arc::new(Mutex::new(Lobby { seed, settings, ..Lobby::default() }));
+    if let Some(beacon) = beacon {
+        let port = listener.local_addr().map_err(|error| error.to_string())?.port();
+        let lobby = shared.clone();
+        beacon.start(port, transport.name(), move || Some(lobby.lock().unwrap().listings()))?;
+    }
+    loop {
+        let (stream, address) = listener.accept().map_err(|error| error.to_string())?;
+        let shared = shared.clone();
+        thread::spawn(move || match Connection::new(stream, transport) {
+            Ok(connection) => visit(&shared, connection, address),
+            Err(error) => println!("{} could not connect: {}", address, error),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::testing::{scripted_client, Answer};
+    use std::time::Duration;
+
+    /// Joins as `name`, sends `commands` one at a time whenever the lobby asks, entering a room
+    /// that is not open yet again a little later, and plays the first legal action. Returns every
+    /// line received.
+    fn lobby_client(port: u16, name: &str, commands: &[&str]) -> Vec<String> {
+        let mut next = 0;
+        scripted_client(port, name, |line| match line.split_whitespace().next() {
+            Some("error") if commands[next - 1].starts_with("enter") => {
+                next -= 1;
+                thread::sleep(Duration::from_millis(10));
+                Some(Answer::Nothing)
+            },
+            Some("lobby") => {
+                next += 1;
+                Some(Answer::Send(commands[next - 1].to_string()))
+            },
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_rooms() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || serve(&listener, Transport::Lines, Settings { grace: Duration::from_secs(1), ..Settings::default() }, 1, None));
+
+        let scripts: [(&str, &[&str]); 4] = [
+            ("Ann", &["create 2 winner", "ready"]),
+            ("Bob", &["create 2", "ready"]),
+            ("Cid", &["create 7", "ready", "rooms", "enter 1", "ready"]),
+            ("Dee", &["enter 2", "leave", "enter 2", "ready"]),
+        ];
+        let clients: Vec<_> = scripts.into_iter().map(|(name, commands)| thread::spawn(move || lobby_client(port, name, commands))).collect();
+        for client in clients {
+            let received = client.join().unwrap();
+            assert!(received.iter().any(|line| line.starts_with("winner")), "{:?}", received);
+            assert!(received.iter().any(|line| line.starts_with("room")));
+        }
+    }
+}
@@ -0,0 +1,578 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use crate::agent::Agent;
+use crate::belief::Knowledge;
+use crate::card::Card;
+use crate::engine::{action_token, parse_keep, parse_play};
+use crate::env::{observe, Decision};
+use crate::game::{award_tokens, deal, next_turn, play_tie_breaker, play_turn, rotate_seats, Action, Event, ExecutionerChoice, RoundStatus, State};
+use crate::options::Options;
+use crate::sim::{game_seed, tokens_to_win, MAX_ROUNDS};
+use crate::websocket::sha1;
+
+// A game played over days, one decision at a time, through the files of a directory:
+//
+// game.txt             the deal seed, every player's key and every decision so far, encrypted
+//                      and sealed with the secret of the host
+// secret               the secret of the host, readable only by them
+// players/<name>.txt   what the player sees and has to decide, encrypted and sealed with their key
+// moves/<name>.txt     the decision the player sent, sealed with their key
+//
+// The host creates the game with `mail --new --players Ann,Bob` and sends every player their key.
+// A player reads their file with `mail --name Ann --key <key>` and answers with `--play <move>`,
+// or `--keep <card>` and maybe `--bottom` after an Executioner. Their move is applied at once if
+// they can read the secret, and otherwise when the host runs `mail --advance`, which is also how
+// moves that came by mail are applied once saved in `moves`. The files of the players can be
+// mailed to them as they are.
+//
+// Nobody without the secret can read the hands in game.txt or change it unnoticed, and nobody
+// without a player's key can read their file, change it unnoticed or move for them. Every move
+// names the decision it answers, so it cannot be played again later.
+
+pub const DEFAULT_DIR: &str = "loveletter-game";
+const FORMAT: &str = "loveletter-mail 1";
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn unhex(text: &str) -> Result<Vec<u8>, String> {
+    let digits = text.as_bytes();
+    if !digits.len().is_multiple_of(2) {
+        return Err("Odd number of hexadecimal digits.".to_string());
+    }
+    digits.chunks(2).map(|pair| u8::from_str_radix(std::str::from_utf8(pair).unwrap_or("-"), 16).map_err(|_| "Not hexadecimal.".to_string())).collect()
+}
+
+/// A new random key, as hexadecimal.
+fn new_key() -> String {
+    hex(&thread_rng().gen::<[u8; 16]>())
+}
+
+/// HMAC-SHA1 of `message` under `key` (RFC 2104).
+pub fn hmac(key: &[u8], message: &[u8]) -> [u8; 20] {
+    let mut block = [0u8; 64];
+    if key.len() > 64 {
+        block[..20].copy_from_slice(&sha1(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let pad = |byte: u8| block.iter().map(|key| key ^ byte).collect::<Vec<u8>>();
+    let inner = sha1(&[pad(0x36), message.to_vec()].concat());
+    sha1(&[pad(0x5c), inner.to_vec()].concat())
+}
+
+/// Encrypts or decrypts `data` with a keystream of HMACs of `nonce` and a counter under `key`.
+fn crypt(key: &[u8], nonce: &[u8], data: &[u8]) -> Vec<u8> {
+    let stream = (0u64..).flat_map(|block| hmac(key, &[nonce, &block.to_be_bytes()].concat()));
+    data.iter().zip(stream).map(|(byte, key)| byte ^ key).collect()
+}
+
+/// `text` encrypted and sealed with `key`.
+fn seal(key: &str, text: &str) -> String {
+    let nonce: [u8; 16] = thread_rng().gen();
+    let data = crypt(key.as_bytes(), &nonce, text.as_bytes());
+    let seal = hmac(key.as_bytes(), &[&nonce[..], &data].concat());
+    format!("{}\nnonce {}\ndata {}\nseal {}\n", FORMAT, hex(&nonce), hex(&data), hex(&seal))
+}
+
+/// The text sealed in `sealed`, if it was sealed with `key` and not changed since.
+fn open(key: &str, sealed: &str) -> Result<String, String> {
+    let mut lines = sealed.lines();
+    if lines.next() != Some(FORMAT) {
+        return Err("This is not a file of a game played by mail.".to_string());
+    }
+    let mut field = |name: &str| lines.next().and_then(|line| line.strip_prefix(name)).ok_or(format!("The {} is missing.", name.trim())).and_then(unhex);
+    let (nonce, data, seal) = (field("nonce ")?, field("data ")?, field("seal ")?);
+    if hmac(key.as_bytes(), &[&nonce[..], &data].concat())[..] != seal[..] {
+        return Err("The file was changed or the key is wrong.".to_string());
+    }
+    String::from_utf8(crypt(key.as_bytes(), &nonce, &data)).map_err(|error| error.to_string())
+}
+
+/// What the host keeps: everything needed to replay the game to where it is.
+#[derive(Debug, Clone, PartialEq)]
+struct Game {
+    id: String,
+    names: Vec<String>,
+    seed: u64,
+    keys: Vec<String>,
+    /// Every decision so far, as answers of the engine protocol.
+    moves: Vec<String>,
+}
+
+impl Game {
+    fn write(&self) -> String {
+        let mut text = format!("id {}\nnames {}\nseed {}\n", self.id, self.names.join(" "), self.seed);
+        for (name, key) in self.names.iter().zip(&self.keys) {
+            let _ = writeln!(text, "key {} {}", name, key);
+        }
+        for answer in &self.moves {
+            let _ = writeln!(text, "move {}", answer);
+        }
+        text
+    }
+
+    fn read(text: &str) -> Result<Game, String> {
+        let mut game = Game { id: String::new(), names: Vec::new(), seed: 0, keys: Vec::new(), moves: Vec::new() };
+        for line in text.lines() {
+            let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+            match keyword {
+                "id" => game.id = rest.to_string(),
+                "names" => game.names = rest.split_whitespace().map(|name| name.to_string()).collect(),
+                "seed" => game.seed = rest.parse().map_err(|_| format!("\"{}\" is not a seed.", rest))?,
+                "key" => game.keys.extend(rest.split_whitespace().nth(1).map(|key| key.to_string())),
+                "move" => game.moves.push(rest.to_string()),
+                _ => return Err(format!("Unexpected line \"{}\" in the game.", line)),
+            }
+        }
+        if game.names.len() < 2 || game.keys.len() != game.names.len() {
+            return Err("The game has no players or keys.".to_string());
+        }
+        Ok(game)
+    }
+
+    fn key(&self, name: &str) -> Result<&str, String> {
+        let player = self.names.iter().position(|player| player == name).ok_or(format!("{} does not play this game.", name))?;
+        Ok(&self.keys[player])
+    }
+}
+
+/// Plays the recorded moves, noting the first decision it has no move for.
+struct Mailbox<'a> {
+    moves: &'a [String],
+    next: usize,
+    pending: Option<(Decision, Vec<Action>)>,
+    /// The card kept by the play, which the player holds when it is seen.
+    kept: Option<Card>,
+}
+
+impl Agent for Mailbox<'_> {
+    fn choose_action(&mut self, _state: &State, hand: [Card; 2], legal: &[Action]) -> Action {
+        if let Some(action) = self.moves.get(self.next).and_then(|answer| parse_play(answer, legal)) {
+            self.next += 1;
+            self.kept = Some(if action.card == hand[0] { hand[1] } else { hand[0] });
+            return action;
+        }
+        self.pending = Some((Decision::Play { hand }, legal.to_vec()));
+        legal[0]
+    }
+
+    fn choose_executioner(&mut self, _state: &State, hand: Card, drawn: &[Card]) -> ExecutionerChoice {
+        if let Some(choice) = self.moves.get(self.next).and_then(|answer| parse_keep(answer, drawn.len())) {
+            self.next += 1;
+            return choice;
+        }
+        self.pending.get_or_insert((Decision::Executioner { hand, drawn: drawn.to_vec() }, Vec::new()));
+        ExecutionerChoice { keep: 0, hand_on_top: true }
+    }
+}
+
+/// Where a game is after its recorded moves.
+struct Position {
+    /// The state before the turn being played.
+    state: State,
+    knowledge: Vec<Knowledge>,
+    /// The events of the round so far.
+    events: Vec<Event>,
+    /// How the rounds before this one ended.
+    history: Vec<String>,
+    /// The players and events of the round before, to show how it ended.
+    previous: Option<(Vec<String>, Vec<Event>)>,
+    /// What the player to act has to decide, or `None` once the game is over.
+    pending: Option<(Decision, Vec<Action>)>,
+    winner: Option<String>,
+}
+
+/// Replays `game` from the deal of its first round.
+fn replay(game: &Game) -> Result<Position, String> {
+    let players = game.names.len();
+    let deal_round = |names: Vec<String>, tokens: Vec<i32>, round: i32| deal(names, tokens, round, &mut StdRng::seed_from_u64(game_seed(game.seed, round as u64)));
+    let mut state = deal_round(game.names.clone(), vec![0; players], 1);
+    let mut knowledge: Vec<Knowledge> = (0..players).map(|seat| Knowledge::new(&state, seat)).collect();
+    let mut events = Vec::new();
+    let mut history = Vec::new();
+    let mut previous = None;
+    let mut next = 0;
+    loop {
+        let mut mailbox = Mailbox { moves: &game.moves, next, pending: None, kept: None };
+        let mut turn = Vec::new();
+        let (status, mut after) = play_turn(state.clone(), &mut mailbox, &mut turn);
+        if let Some(pending) = mailbox.pending {
+            return Ok(Position { state, knowledge, events, history, previous, pending: Some(pending), winner: None });
+        }
+        next = mailbox.next;
+        if let Some(kept) = mailbox.kept {
+            knowledge[state.turn].hand = Some(kept);
+        }
+        for event in &turn {
+            for seat in knowledge.iter_mut() {
+                seat.observe(&after, event);
+            }
+        }
+        events.extend(turn);
+        next_turn(&mut after);
+        state = after;
+        match status {
+            RoundStatus::Continue | RoundStatus::Skip => continue,
+            RoundStatus::TieBreaker => state = play_tie_breaker(state),
+            RoundStatus::End => {},
+        }
+
+        let winners = award_tokens(&mut state);
+        let names: Vec<&str> = winners.iter().map(|winner| state.players[*winner].as_str()).collect();
+        history.push(format!("Round {} won by {}.", state.round, names.join(" and ")));
+        let best = *state.tokens.iter().max().unwrap();
+        let leaders: Vec<usize> = (0..players).filter(|seat| state.tokens[*seat] == best).collect();
+        if (best >= tokens_to_win(players) && leaders.len() == 1) || state.round as u32 >= MAX_ROUNDS {
+            let winner = (leaders.len() == 1).then(|| state.players[leaders[0]].clone());
+            let previous = Some((state.players.clone(), events));
+            return Ok(Position { state, knowledge, events: Vec::new(), history, previous, pending: None, winner });
+        }
+        previous = Some((state.players.clone(), std::mem::take(&mut events)));
+        let (names, tokens) = rotate_seats(&state, 1);
+        state = deal_round(names, tokens, state.round + 1);
+        knowledge = (0..players).map(|seat| Knowledge::new(&state, seat)).collect();
+    }
+}
+
+/// The file of `name`: a first line for the command to read, then what they see.
+fn notification(game: &Game, position: &Position, name: &str) -> String {
+    let state = &position.state;
+    let Some(seat) = state.players.iter().position(|player| player == name) else {
+        return String::new();
+    };
+    let to_act = position.pending.is_some() && state.turn == seat;
+    let status = match &position.pending {
+        None => "over",
+        Some((Decision::Play { .. }, _)) if to_act => "play",
+        Some(_) if to_act => "keep",
+        Some(_) => "wait",
+    };
+    let mut text = format!("game {} decision {} {}\n", game.id, game.moves.len(), status);
+    if let Some((players, events)) = position.previous.as_ref().filter(|_| position.events.is_empty()) {
+        let seat = players.iter().position(|player| player == name).unwrap_or(seat);
+        for line in events.iter().filter_map(|event| event.describe(players, seat)) {
+            let _ = writeln!(text, "{}", line);
+        }
+    }
+    for line in &position.history {
+        let _ = writeln!(text, "{}", line);
+    }
+    let tokens: Vec<String> = state.players.iter().zip(&state.tokens).map(|(player, tokens)| format!("{} {}", player, tokens)).collect();
+    let _ = writeln!(text, "Tokens: {}.", tokens.join(", "));
+    if let Some(winner) = &position.winner {
+        let _ = writeln!(text, "{} won the game!", winner);
+        return text;
+    } else if position.pending.is_none() {
+        let _ = writeln!(text, "The game is over without a winner.");
+        return text;
+    }
+
+    let _ = writeln!(text, "\nRound {}, {} cards in the deck.", state.round, state.deck.len());
+    for event in &position.events {
+        if let Some(line) = event.describe(&state.players, seat) {
+            let _ = writeln!(text, "{}", line);
+        }
+    }
+    // Only what the seat may see, as the bots and the network clients get it
+    let mut seen = state.clone();
+    seen.turn = seat;
+    let seats: Vec<usize> = (0..state.players.len()).collect();
+    let view = observe(&seen, &position.knowledge[seat], &seats, &Decision::Done);
+    let _ = writeln!(text, "Discard piles:");
+    for (other, player) in state.players.iter().enumerate() {
+        let pile: Vec<String> = view.discard[other].iter().map(|card| card.name()).collect();
+        let mut notes = Vec::new();
+        if view.out[other] {
+            notes.push("out".to_string());
+        }
+        if view.protected[other] {
+            notes.push("protected".to_string());
+        }
+        if let Some(card) = view.known[other] {
+            notes.push(format!("holds {}", card.name()));
+        }
+        let notes = if notes.is_empty() { String::new() } else { format!(" ({})", notes.join(", ")) };
+        let _ = writeln!(text, "\t{}{}: {}", player, notes, pile.join(", "));
+    }
+
+    match &position.pending {
+        Some((Decision::Play { hand }, legal)) if to_act => {
+            let _ = writeln!(text, "\nYou hold {} and drew {}. Play one of:", hand[0].name(), hand[1].name());
+            for action in legal {
+                let _ = writeln!(text, "\t--play {}\t{}", action_token(action), action.describe(&state.players));
+            }
+        },
+        Some((Decision::Executioner { hand, drawn }, _)) if to_act => {
+            let drawn: Vec<String> = drawn.iter().enumerate().map(|(i, card)| format!("{} {}", i, card.name())).collect();
+            let _ = writeln!(text, "\nYour Executioner drew {}. Keep one with --keep <number>; your {} goes to the bottom of the deck, on top unless you add --bottom.", drawn.join(", "), hand.name());
+        },
+        _ => {
+            if let Some(card) = state.hands[seat] {
+                let _ = writeln!(text, "\nYou hold {}.", card.name());
+            }
+            let _ = writeln!(text, "Waiting for {} to play.", state.players[state.turn]);
+        },
+    }
+    text
+}
+
+fn read(path: &Path) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|error| format!("Could not read {}: {}", path.display(), error))
+}
+
+fn write(path: &Path, text: &str) -> Result<(), String> {
+    fs::write(path, text).map_err(|error| format!("Could not write {}: {}", path.display(), error))
+}
+
+/// The files of a game directory.
+struct Files {
+    dir: PathBuf,
+}
+
+impl Files {
+    fn secret(&self) -> Result<String, String> {
+        read(&self.dir.join("secret")).map(|secret| secret.trim().to_string())
+    }
+
+    fn player(&self, name: &str) -> PathBuf {
+        self.dir.join("players").join(format!("{}.txt", name))
+    }
+
+    fn moves(&self, name: &str) -> PathBuf {
+        self.dir.join("moves").join(format!("{}.txt", name))
+    }
+
+    fn load(&self, secret: &str) -> Result<Game, String> {
+        let text = read(&self.dir.join("game.txt"))?;
+        Game::read(&open(secret, &text).map_err(|error| format!("game.txt: {}", error))?)
+    }
+
+    /// Saves `game` and the file of every player, returning where it is.
+    fn save(&self, secret: &str, game: &Game) -> Result<Position, String> {
+        let position = replay(game)?;
+        write(&self.dir.join("game.txt"), &seal(secret, &game.write()))?;
+        for (name, key) in game.names.iter().zip(&game.keys) {
+            write(&self.player(name), &seal(key, &notification(game, &position, name)))?;
+        }
+        Ok(position)
+    }
+}
+
+/// Starts a game between `names` in `dir`, returning every player's key.
+fn create(files: &Files, names: Vec<String>, seed: u64) -> Result<Vec<String>, String> {
+    if files.dir.join("game.txt").exists() {
+        return Err(format!("There already is a game in {}.", files.dir.display()));
+    }
+    for sub in ["players", "moves"] {
+        fs::create_dir_all(files.dir.join(sub)).map_err(|error| format!("Could not create {}: {}", files.dir.display(), error))?;
+    }
+    let secret = new_key();
+    let path = files.dir.join("secret");
+    write(&path, &secret)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).map_err(|error| error.to_string())?;
+    }
+    let keys: Vec<String> = names.iter().map(|_| new_key()).collect();
+    let game = Game { id: new_key()[..8].to_string(), names, seed, keys: keys.clone(), moves: Vec::new() };
+    files.save(&secret, &game)?;
+    Ok(keys)
+}
+
+/// What `name` sees, read with their `key`.
+fn view(files: &Files, name: &str, key: &str) -> Result<String, String> {
+    open(key, &read(&files.player(name))?).map_err(|error| format!("Your file: {}", error))
+}
+
+/// Seals the answer of `name` to the decision they were last shown.
+fn submit(files: &Files, name: &str, key: &str, answer: &str) -> Result<(), String> {
+    let seen = view(files, name, key)?;
+    let words: Vec<&str> = seen.lines().next().unwrap_or("").split_whitespace().collect();
+    let ["game", id, "decision", decision, status] = words[..] else {
+        return Err("Your file has no decision.".to_string());
+    };
+    let expected = if answer.starts_with("play") { "play" } else { "keep" };
+    if status != expected {
+        return Err(match status {
+            "over" => "The game is over.".to_string(),
+            "wait" => "It is not your turn.".to_string(),
+            _ => format!("You have to {} now.", status),
+        });
+    }
+    if expected == "play" && !seen.lines().any(|line| line.trim_start().starts_with(&format!("--play {}\t", &answer[5..]))) {
+        return Err(format!("{} is not one of your moves.", &answer[5..]));
+    }
+    let text = format!("game {}\ndecision {}\nname {}\nmove {}\n", id, decision, name, answer);
+    let seal = hmac(key.as_bytes(), text.as_bytes());
+    write(&files.moves(name), &format!("{}seal {}\n", text, hex(&seal)))
+}
+
+/// Applies the moves that answer the decision the game waits for, as long as there are any.
+/// Moves that are not sealed by their player or answer another decision are thrown away.
+fn advance(files: &Files) -> Result<Position, String> {
+    let secret = files.secret()?;
+    let mut game = files.load(&secret)?;
+    let mut position = replay(&game)?;
+    let mut applied = false;
+    while let Some((decision, legal)) = position.pending.clone() {
+        let name = position.state.players[position.state.turn].clone();
+        let path = files.moves(&name);
+        let Ok(text) = fs::read_to_string(&path) else { break };
+        let _ = fs::remove_file(&path);
+        let fields: HashMap<&str, &str> = text.lines().filter_map(|line| line.split_once(' ')).collect();
+        let body = text.rsplit_once("seal ").map_or("", |(body, _)| body);
+        let expected = hmac(game.key(&name)?.as_bytes(), body.as_bytes());
+        let sealed = fields.get("seal").and_then(|seal| unhex(seal).ok()).is_some_and(|seal| seal[..] == expected[..]);
+        let current = fields.get("game") == Some(&game.id.as_str()) && fields.get("decision") == Some(&game.moves.len().to_string().as_str());
+        let answer = fields.get("move").copied().unwrap_or("");
+        let legal = match &decision {
+            Decision::Executioner { drawn, .. } => parse_keep(answer, drawn.len()).is_some(),
+            _ => parse_play(answer, &legal).is_some(),
+        };
+        if !(sealed && current && legal) {
+            println!("Threw away the move of {}: it is not sealed with their key, not for this decision or not legal.", name);
+            break;
+        }
+        game.moves.push(answer.to_string());
+        position = replay(&game)?;
+        applied = true;
+    }
+    if applied {
+        position = files.save(&secret, &game)?;
+    }
+    Ok(position)
+}
+
+/// `mail`: plays a game by mail in `--dir`. `--new --players Ann,Bob` starts one, `--name` and
+/// `--key` show a player their file and send their answer with `--play` or `--keep`, and
+/// `--advance` applies the answers that came in.
+pub fn command(options: &Options) -> Result<(), String> {
+    let files = Files { dir: PathBuf::from(options.value("dir").unwrap_or(DEFAULT_DIR)) };
+    if options.flag("new") {
+        let names: Vec<String> = options.value("players").ok_or("Give the players with --players Ann,Bob.")?.split(',').map(|name| name.trim().to_string()).collect();
+        if !(2..=6).contains(&names.len()) || names.iter().any(|name| name.is_empty() || name.contains(char::is_whitespace)) {
+            return Err("Give 2 to 6 names without spaces, separated by commas.".to_string());
+        }
+        let seed = match options.value("seed") {
+            Some(_) => options.get("seed", 0)?,
+            None => random(),
+        };
+        let keys = create(&files, names.clone(), seed)?;
+        println!("The game is ready in {}. Send every player their key:", files.dir.display());
+        for (name, key) in names.iter().zip(&keys) {
+            println!("\t{}: {}", name, key);
+        }
+        return Ok(());
+    }
+    if options.flag("advance") {
+        let position = advance(&files)?;
+        match position.pending {
+            Some(_) => println!("Waiting for {}.", position.state.players[position.state.turn]),
+            None => println!("The game is over."),
+        }
+        return Ok(());
+    }
+
+    let name = options.value("name").ok_or("Give your name with --name, or --new or --advance.")?;
+    let key = options.value("key").ok_or("Give your key with --key.")?;
+    let answer = match (options.value("play"), options.value("keep")) {
+        (Some(token), _) => format!("play {}", token),
+        (None, Some(keep)) => format!("keep {} {}", keep, if options.flag("bottom") { "bottom" } else { "top" }),
+        (None, None) => {
+            let text = view(&files, name, key)?;
+            println!("{}", text.split_once('\n').map_or("", |(_, text)| text));
+            return Ok(());
+        },
+    };
+    submit(&files, name, key, &answer)?;
+    if files.secret().is_err() {
+        println!("Your move is sent. It will be played when the host advances the game.");
+        return Ok(());
+    }
+    advance(&files)?;
+    println!("Your move is played.\n");
+    let text = view(&files, name, key)?;
+    println!("{}", text.split_once('\n').map_or("", |(_, text)| text));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hmac_and_seal() {
+        // RFC 2202, test case 2
+        assert_eq!(hex(&hmac(b"Jefe", b"what do ya want for nothing?")), "effcdf6ae5eb2fa2d27416d5f184df9c259a7c79");
+        let sealed = seal("key", "Ann holds Alice");
+        assert!(!sealed.contains("Alice"));
+        assert_eq!(open("key", &sealed).unwrap(), "Ann holds Alice");
+        assert!(open("other", &sealed).is_err());
+        let changed: Vec<String> = sealed.lines().map(|line| match line.strip_prefix("data ") {
+            Some(data) => {
+                let first = u8::from_str_radix(&data[..1], 16).unwrap() ^ 1;
+                format!("data {:x}{}", first, &data[1..])
+            },
+            None => line.to_string(),
+        }).collect();
+        let changed = changed.join("\n");
+        assert_ne!(changed, sealed.trim_end());
+        assert!(open("key", &changed).is_err());
+    }
+
+    #[test]
+    fn test_play_by_mail() {
+        let dir = std::env::temp_dir().join(format!("loveletter-mail-{}", new_key()));
+        let files = Files { dir: dir.clone() };
+        let names = vec!["Ann".to_string(), "Bob".to_string()];
+        let keys = create(&files, names.clone(), 5).unwrap();
+        let key = |name: &str| keys[names.iter().position(|player| player == name).unwrap()].as_str();
+
+        let mut position = advance(&files).unwrap();
+        let mut decisions = 0;
+        while let Some((decision, legal)) = position.pending.clone() {
+            let name = position.state.players[position.state.turn].clone();
+            let other = names.iter().find(|player| **player != name).unwrap();
+            // Only the player to act may answer, and only with their own key
+            assert_eq!(submit(&files, other, key(other), "play 1").unwrap_err(), "It is not your turn.");
+            assert!(submit(&files, &name, key(other), "play 1").is_err());
+            let answer = match decision {
+                Decision::Play { .. } => format!("play {}", action_token(&legal[0])),
+                _ => "keep 0 top".to_string(),
+            };
+            submit(&files, &name, key(&name), &answer).unwrap();
+            if decisions == 0 {
+                // A move changed by someone else is thrown away
+                let path = files.moves(&name);
+                let text = fs::read_to_string(&path).unwrap();
+                fs::write(&path, text.replace(&format!("decision {}", decisions), "decision 7")).unwrap();
+                assert_eq!(advance(&files).unwrap().state, position.state);
+                assert!(!path.exists());
+                submit(&files, &name, key(&name), &answer).unwrap();
+            }
+            position = advance(&files).unwrap();
+            decisions += 1;
+        }
+        assert!(decisions > 2);
+        let game = files.load(&files.secret().unwrap()).unwrap();
+        assert_eq!(game.moves.len(), decisions);
+        assert!(view(&files, "Ann", key("Ann")).unwrap().starts_with(&format!("game {} decision {} over", game.id, decisions)));
+
+        // So is a game file changed by someone without the secret
+        let secret = files.secret().unwrap();
+        let text = fs::read_to_string(dir.join("game.txt")).unwrap();
+        let nonce = text.lines().nth(1).unwrap();
+        fs::write(dir.join("game.txt"), text.replace(nonce, &format!("nonce {}", "0".repeat(32)))).unwrap();
+        assert!(files.load(&secret).is_err());
+        let _ = fs::remove_dir_all(dir);
+    }
+}
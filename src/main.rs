@@ -43,806 +43,167 @@
     *
  */
 
-use text_io::read;
 use rand::prelude::*;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum Card {
-    Alice, // 9 - (1) If discarded you are out.
-    RedQueen, // 8 - (1) Must be discarded if you have (7) Time or (5) Knave of Hearts in your hand
-    Time, // 7 - (1) Trade hands with another player.
-    Executioner, // 6 - (2) Draw 2 cards, pick one and place 2 cards on the bottom of the deck.
-    KnaveOfHearts, // 5 - (2) Discard a player's hand (including your own) and make them draw a new card.
-    Nobody, // 4 - (2) Protection until your next turn.
-    Tweedies, // 3 - (2) Compare hands with another player. Lowest hand is out.
-    Wilkins, // 2 - (2) Look at another player's hand.
-    Guard, // 1 - (6) Guess a player's hand and if correct, player is out.
-    Dormouse, // 0 - (2) Gain one token if no one else discarded a dormouse by the end of the round.
-}
-
-
-impl Card {
-    fn value(&self) -> usize {
-        match self {
-            Card::Alice => 9,
-            Card::RedQueen => 8,
-            Card::Time => 7,
-            Card::Executioner => 6,
-            Card::KnaveOfHearts => 5,
-            Card::Nobody => 4,
-            Card::Tweedies => 3,
-            Card::Wilkins => 2,
-            Card::Guard => 1,
-            Card::Dormouse => 0,
-        }
-    }
-    
-    fn count(&self) -> usize {
-        match self {
-            Card::Alice => 1,
-            Card::RedQueen => 1,
-            Card::Time => 1,
-            Card::KnaveOfHearts => 2,
-            Card::Executioner => 2,
-            Card::Nobody => 2,
-            Card::Tweedies => 2,
-            Card::Wilkins => 2,
-            Card::Guard => 6,
-            Card::Dormouse => 2,
-        }
-    }
-    
-    fn name(&self) -> String {
-        match self {
-            Card::Alice => "Alice".to_string(),
-            Card::RedQueen => "The Red Queen".to_string(),
-            Card::Time => "Time".to_string(),
-            Card::Executioner => "Executioner".to_string(),
-            Card::KnaveOfHearts => "Knave of Hearts".to_string(),
-            Card::Nobody => "Nobody".to_string(),
-            Card::Tweedies => "The Tweedies".to_string(),
-            Card::Wilkins => "Wilkins".to_string(),
-            Card::Guard => "Guard".to_string(),
-            Card::Dormouse => "The Dormouse".to_string(),
-        }
-    }
-
-    fn description(&self) -> String {
-        match self {
-            Card::Alice => "If discarded you are out.".to_string(),
-            Card::RedQueen => "Must be discarded if you have (6) or (7) in your hand.".to_string(),
-            Card::Time => "Trade hands with another player.".to_string(),
-            Card::Executioner => "Draw 2 cards, pick one and place 2 cards on the bottom of the deck.".to_string(),
-            Card::KnaveOfHearts => "Discard a player's hand (including your own) and make them draw a new card.".to_string(),
-            Card::Nobody => "Protection until your next turn.".to_string(),
-            Card::Tweedies => "Compare hands with another player. Lowest hand is out.".to_string(),
-            Card::Wilkins => "Look at another player's hand.".to_string(),
-            Card::Guard => "Guess a player's hand and if correct, player is out.".to_string(),
-            Card::Dormouse => "Gain one token if no one else discarded a dormouse by the end of the round.".to_string(),
-        }
-    }
-
-    fn to_string(&self) -> String {
-        format!("{} - {} (x{}): {}", self.value(), self.name(), self.count(), self.description())
-    }
-    
-    fn targetting(&self) -> bool {
-        match self {
-            Card::Alice => false,
-            Card::RedQueen => false,
-            Card::Time => true,
-            Card::Executioner => false,
-            Card::KnaveOfHearts => true,
-            Card::Nobody => false,
-            Card::Tweedies => true,
-            Card::Wilkins => true,
-            Card::Guard => true,
-            Card::Dormouse => false,
-        }
-    }
-
-    fn can_target_self(&self) -> bool {
-        match self {
-            Card::KnaveOfHearts => true,
-            _ => false,
-        }
-    }
-
-    fn protects(&self) -> bool {
-        match self {
-            Card::Nobody => true,
-            _ => false,
-        }
-    }
-}
-
-fn list_cards() -> Vec<Card> {
-    let mut cards: Vec<Card> = Vec::new();
-    cards.push(Card::Alice);
-    cards.push(Card::RedQueen);
-    cards.push(Card::Time);
-    cards.push(Card::Executioner);
-    cards.push(Card::KnaveOfHearts);
-    cards.push(Card::Nobody);
-    cards.push(Card::Tweedies);
-    cards.push(Card::Wilkins);
-    cards.push(Card::Guard);
-    cards.push(Card::Dormouse);
-    cards
-}
-
-fn create_deck() -> Vec<Card> {
-    let mut deck: Vec<Card> = Vec::new();
-    for _ in 0..Card::Alice.count() {
-        deck.push(Card::Alice);
-    }
-    for _ in 0..Card::RedQueen.count() {
-        deck.push(Card::RedQueen);
-    }
-    for _ in 0..Card::Time.count() {
-        deck.push(Card::Time);
-    }
-    for _ in 0..Card::Executioner.count() {
-        deck.push(Card::Executioner);
-    }
-    for _ in 0..Card::KnaveOfHearts.count() {
-        deck.push(Card::KnaveOfHearts);
-    }
-    for _ in 0..Card::Nobody.count() {
-        deck.push(Card::Nobody);
-    }
-    for _ in 0..Card::Tweedies.count() {
-        deck.push(Card::Tweedies);
-    }
-    for _ in 0..Card::Wilkins.count() {
-        deck.push(Card::Wilkins);
-    }
-    for _ in 0..Card::Guard.count() {
-        deck.push(Card::Guard);
-    }
-    for _ in 0..Card::Dormouse.count() {
-        deck.push(Card::Dormouse);
-    }
-    deck
-}
-
-fn clear_screen() {
-    print!("\x1B[2J\x1B[1;1H");
-}
-
-#[derive(Debug, Clone)]
-struct State {
-    deck: Vec<Card>,
-    discard: Vec<Vec<Card>>,
-    players: Vec<String>,
-    out: Vec<usize>,
-    hands: Vec<Option<Card>>,
-    tokens: Vec<i32>,
-    round: i32,
-    turn: usize,
-    dormouse: Option<usize>,
-}
+use love_letter::agent::Agent;
+use love_letter::analytics;
+use love_letter::client;
+use love_letter::clock::{Clock, Timed};
+use love_letter::engine;
+use love_letter::game::{award_tokens, deal, deal_next_round, next_turn, play_tie_breaker, play_turn, RoundStatus, State};
+use love_letter::human::{read, wait_for_one, Human};
+use love_letter::mail;
+use love_letter::model::{self, OpponentModel};
+use love_letter::options::Options;
+use love_letter::ratings::{self, Match};
+use love_letter::record::{save_rounds, Recorder, RoundRecord};
+use love_letter::review;
+use love_letter::seats;
+use love_letter::server;
+use love_letter::sim;
+use love_letter::solver;
+use love_letter::spectate::{self, Spectators};
+use love_letter::tournament;
 
 fn setup(last_state: Option<State>) -> State {
-    let (mut deck, mut discard, mut players, out, mut hands, mut tokens, round, turn, dormouse) = match last_state {
-        Some(ref state) => (create_deck(), Vec::new(), state.players.clone(), Vec::new(), Vec::new(), state.tokens.clone(), state.round.clone(), 0, None::<usize>),
-        None => (create_deck(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), 1, 0, None),
-    };
-
-    if last_state.is_none() {
-        println!("Welcome to Love Letter!");
+    let mut rng = thread_rng();
+    let state = match last_state {
+        // Rotate the players
+        Some(ref state) => deal_next_round(state, 1, &mut rng),
+        None => {
+            println!("Welcome to Love Letter!");
 
-        let max_players: i32 = 6;
-        println!("How many players are there?");
-        print!(": ");
-        let mut player_count: i32 = read!();
-        while player_count < 2 || player_count > max_players  {
-            print!("{} is not a valid number of players. Must be between 2 and {}. Try again: ", player_count, max_players);
-            player_count = read!();
-        }
-        println!("There are {} players", player_count);
+            let max_players: i32 = 6;
+            println!("How many players are there?");
+            print!(": ");
+            let mut player_count: i32 = read();
+            while !(2..=max_players).contains(&player_count) {
+                print!("{} is not a valid number of players. Must be between 2 and {}. Try again: ", player_count, max_players);
+                player_count = read();
+            }
+            println!("There are {} players", player_count);
+
+            println!("What are your names?");
+            let mut players = Vec::new();
+            for i in 0..player_count {
+                print!("Player {}: ", i + 1);
+                let mut name: String = read();
+                while players.contains(&name) {
+                    print!("{} is already playing. Pick another name: ", name);
+                    name = read();
+                }
+                players.push(name);
+            }
 
-        println!("What are your names?");
-        for i in 0..player_count {
-            print!("Player {}: ", i + 1);
-            let name: String = read!();
-            players.push(name);
-        }
-        
-        // Assign tokens
-        for _ in 0..player_count {
-            tokens.push(0);
-        }
-    } else {
-        // Rotate the players
-        let last_player = players[0].clone();
-        players.remove(0);
-        players.push(last_player);
+            // Assign tokens
+            let tokens = vec![0; players.len()];
+            deal(players, tokens, 1, &mut rng)
+        },
+    };
 
-        let last_token = tokens[0].clone();
-        tokens.remove(0);
-        tokens.push(last_token);
-    }
-    
-    for _ in 0..players.len() {
-        discard.push(Vec::new());
-    }
     println!("Deck created.");
-    
-    // Shuffle the deck
-    let mut rng = thread_rng();
-    deck.shuffle(&mut rng);
     println!("Deck shuffled.");
-    
-    // Removing one card
     println!("Removing one card.");
-    deck.pop();
-
-    // Deal cards
     println!("Dealing cards.");
-    for _ in 0..players.len() {
-        let card = deck.pop().unwrap();
-        hands.push(Some(card));
-    }
-    
-    print!("\nPlayers: {}", players[0]);
-    for i in 1..players.len() {
-        print!(", {}", players[i]);
-    }
-    println!(".");
-    
-    // Create state
-    let state = State {
-        deck: deck,
-        discard: discard,
-        players: players,
-        out: out,
-        hands: hands,
-        tokens: tokens,
-        round: round,
-        turn: turn,
-        dormouse: dormouse,
-    };
+
+    println!("\nPlayers: {}.", state.players.join(", "));
 
     println!("\nType 1 to start the game!");
-    loop {
-        let i: i32 = read!();
-        if i == 1 {
-            break;
-        }
-    }
+    wait_for_one();
     state
 }
 
-enum PlayError {
-    InvalidCard,
-    InvalidPlayer,
-    InvalidTargetPlayer,
-    InvalidHand,
-}
-enum RoundStatus {
-    Continue,
-    Skip,
-    Retry,
-    TieBreaker,
-    End,
-}
-type TurnResult = Result<RoundStatus, PlayError>;
-
-enum Target {
-    Player(usize),
-    None,
-    Cancel,
+/// Records the finished match in the ratings file given with `--ratings <file>`, if any, and prints
+/// how everyone's rating changed.
+fn save_ratings(options: &Options, state: &State) -> Result<(), String> {
+    let Some(path) = options.value("ratings") else {
+        return Ok(());
+    };
+    let game = Match::new(state.players.iter().cloned().zip(state.tokens.iter().cloned()).collect());
+    let changes = ratings::record(path, &[game])?;
+    let (ratings, _) = ratings::load(path)?;
+    println!("Ratings:");
+    for (name, change) in &changes[0] {
+        println!("{}: {:.0} ({:+.0})", name, ratings.rating(name), change);
+    }
+    Ok(())
 }
 
-fn no_possible_play(state: &State, card: &Card) -> bool {
-    let hand = state.hands[state.turn].unwrap();
-    let card_targetting = card.targetting() && !card.can_target_self();
-    let hand_targetting = hand.targetting() && !hand.can_target_self();
-    if card_targetting && hand_targetting {
-        // Check if all players are protected
-        let mut all_protected = true;
-        for i in 0..state.players.len() {
-            if !state.out.contains(&i) && (state.discard[i].len() == 0 || !state.discard[i].last().unwrap().protects()) && i != state.turn {
-                all_protected = false;
-                break;
-            }
-        }
-        all_protected
-    } else {
-        false
+/// Lets spectators watch the game on `--spectate <port>`, showing them the hands of every round
+/// once it is over, `--omniscient` more rounds late, if it is given.
+fn open_spectators(options: &Options) -> Result<Option<Spectators>, String> {
+    if options.value("spectate").is_none() {
+        return Ok(None);
     }
+    let port: u16 = options.get("spectate", server::DEFAULT_PORT)?;
+    let omniscient = match options.value("omniscient") {
+        Some(_) => Some(options.get("omniscient", 0)?),
+        None => None,
+    };
+    let listener = std::net::TcpListener::bind(("0.0.0.0", port)).map_err(|error| format!("Could not listen on port {}: {}", port, error))?;
+    let (spectators, watchers) = Spectators::new(omniscient);
+    spectate::listen(listener, watchers);
+    println!("Spectators can watch on port {}.", port);
+    Ok(Some(spectators))
 }
 
-
-fn play_target(state: State, card: &Card) -> Target {
-    // Check if both cards player could play needs a target that is not self
-    let all_protected = no_possible_play(&state, card);
-
-    if all_protected {
-        println!("All players are protected.");
-    } else {
-        println!("Who would you like to target?");
-    }
-
-    // List players
-    println!("0: Cancel.");
-    if all_protected == false {
-        for i in 0..state.players.len() {
-            if state.hands[i].is_some() {
-                println!("{}: {}", i + 1, state.players[i]);
-            }
-        }
-    } else {
-        println!("1: Discard without any target.");
-        loop {
-            print!(": ");
-            let target = read!();
-            match target {
-                0 => return Target::Cancel,
-                1 => return Target::None,
-                _ => {
-                    println!("That is not a valid option.");
-                    continue;
-                }
-            }
-        }
+/// `play`: a game between humans taking turns at this terminal. With `--turn-time` or `--bank`
+/// the players are timed: the time left counts down at the prompt, and a player still deciding
+/// when it runs out gets the default action.
+fn play_hot_seat(options: &Options) -> Result<(), String> {
+    let record = options.value("record");
+    let mut spectators = open_spectators(options)?;
+    let mut state = setup(None);
+    let mut human = Human::new(options.flag("hints"));
+    if let Some(path) = options.value("model") {
+        human.set_model(OpponentModel::load(path)?);
     }
-
-    let mut target: usize;
+    let mut human: Box<dyn Agent> = match Clock::from_options(options)? {
+        Some(clock) => Box::new(Timed::new(Box::new(human), clock)),
+        None => Box::new(human),
+    };
     loop {
-        print!("Target player: ");
-        target = read!();
-
-        // Check if user cancelled
-        if target == 0 {
-            return Target::Cancel;
+        let start = state.clone();
+        for seat in 0..state.players.len() {
+            human.start_round(&state, seat);
         }
-
-        // Check if target is valid
-        if target < 1 || target > state.players.len() {
-            println!("That is not a valid player.");
-            continue;
-        }
-
-        // Check if target is out
-        if state.out.contains(&(target - 1)) {
-            println!("That player is out.");
-            continue;
+        if let Some(spectators) = spectators.as_mut() {
+            spectators.start_round(&state);
         }
-
-        // Check if target is self
-        if target - 1 == state.turn && card.can_target_self() == false {
-            println!("You cannot target yourself.");
-            continue;
-        }
-
-        // Check if target is protected
-        if Some(&Card::Nobody) == state.discard[target - 1].last() {
-            println!("That player is protected.");
-            continue;
-        }
-
-        break;
-    }
-
-    
-    Target::Player(target - 1)
-}
-
-fn play_card(state_: State, card: Card) -> (TurnResult, State) {
-    if state_.hands[state_.turn].is_none() {
-        return (Err(PlayError::InvalidHand), state_);
-    }
-    if state_.out.contains(&state_.turn) {
-        return (Err(PlayError::InvalidPlayer), state_);
-    }
-    let mut state = state_.clone();
-    let result = {
-        if let Some(hand) = state.hands[state.turn].clone() {
-            // Discard card
-            state.discard[state.turn].push(card);
-
-            // Clone state for targetting
-            let state_clone = state.clone();
-
-            match hand {
-                Card::RedQueen => {
-                    match card {
-                        Card::Time => {
-                            return (Err(PlayError::InvalidCard), state_);
-                        },
-                        Card::Executioner => {
-                            return (Err(PlayError::InvalidCard), state_);
-                        },
-                        _ => {}
-                    };
-                },
-                _ => {},
-            }
-            
-
-            // Implement card effects
-            match card {
-                Card::Alice => {
-                    println!("You are out.");
-                    state.out.push(state.turn);
-                    Ok(RoundStatus::Continue)
-                },
-                Card::RedQueen => {
-                    println!("You played the Red Queen.");
-                    Ok(RoundStatus::Continue)
-                },
-                Card::Time => {
-                    let target = play_target(state_clone, &card);
-                    match target {
-                        Target::Cancel => return (Ok(RoundStatus::Retry), state_),
-                        Target::None => {
-                            println!("You discard without a target.");
-                            Ok(RoundStatus::Continue)
-                        },
-                        Target::Player(target) => {
-                            println!("You swap hands with {}.", state.players[target]);
-                            let temp = state.hands[state.turn];
-                            state.hands[state.turn] = state.hands[target];
-                            state.hands[target] = temp;
-                            Ok(RoundStatus::Continue)
-                        },
-                    }
-                },
-                Card::Executioner => {
-                    println!("You draw two cards.");
-                    let card1 = state.deck.pop().unwrap();
-                    let card2 = state.deck.pop().unwrap();
-                    println!("You drew:\n1. {}\n2. {}", card1.to_string(), card2.to_string());
-                    println!("Which card would you like to keep?");
-                    let mut keep: i32;
-                    loop {
-                        keep = read!();
-                        if keep == 1 || keep == 2 {
-                            break;
-                        } else {
-                            println!("That is not a valid card.");
-                        }
-                    }
-                    let (hand, card) = {
-                        let hand = state.hands[state.turn].unwrap();
-                        if keep == 1 {
-                            state.hands[state.turn] = Some(card1);
-                            (hand, card2)
-                        } else {
-                            state.hands[state.turn] = Some(card2);
-                            (hand, card1)
-                        }
-                    };
-                    println!("You will now place the following cards at the bottom of the deck:\n1. {}\n2. {}", hand.to_string(), card.to_string());
-                    println!("Which order would you like to place them in? (1 or 2):\n1. \"{}\" on top of \"{}\"\n2. \"{}\" on top of \"{}\"", hand.name(), card.name(), card.name(), hand.name());
-                    print!(": ");
-                    let mut order: i32;
-                    loop {
-                        order = read!();
-                        if order == 1 || order == 2 {
-                            break;
-                        } else {
-                            println!("That is not a valid order.");
-                        }
-                    }
-                    
-                    // Place cards at bottom of deck
-                    if order == 1 {
-                        state.deck.insert(0, hand);
-                        state.deck.insert(0, card);
-                    } else {
-                        state.deck.insert(0, card);
-                        state.deck.insert(0, hand);
-                    }
-                    println!("You placed the cards at the bottom of the deck.");
-                    Ok(RoundStatus::Continue)
-                },
-                Card::KnaveOfHearts => {
-                    let target = play_target(state_clone, &card);
-                    match target {
-                        Target::Cancel => return (Ok(RoundStatus::Retry), state_),
-                        Target::None => return (Err(PlayError::InvalidTargetPlayer), state_), // This should never happen
-                        Target::Player(target) => {
-                            // Makes target discard a card and draw a new one
-                            println!("{} discards {}.", state.players[target], state.hands[target].unwrap().name());
-                            let hand = state.hands[target].unwrap();
-                            state.discard[target].push(hand);
-                            state.hands[target] = None;
-                            if state.discard[target].last().unwrap() == &Card::Alice {
-                                println!("{} is out.", state.players[target]);
-                                state.out.push(target);
-                            } else {
-                                let card = state.deck.pop();
-                                if let Some(card) = card {
-                                    println!("{} draws a card.", state.players[target]);
-                                    state.hands[target] = Some(card);
-                                } else {
-                                    println!("{} has no cards left to draw and is out.", state.players[target]);
-                                    state.out.push(target);
-                                }
-                            }
-                            Ok(RoundStatus::Continue)
-                        },
-                    }
-                },
-                Card::Nobody => {
-                    println!("You are protected.");
-                    Ok(RoundStatus::Continue)
-                },
-                Card::Tweedies => {
-                    let target = play_target(state_clone, &card);
-                    match target {
-                        Target::Cancel => return (Ok(RoundStatus::Retry), state_),
-                        Target::None => {
-                            println!("You discard without a target.");
-                            Ok(RoundStatus::Continue)
-                        },
-                        Target::Player(target) => {
-                            println!("You compare hands with {} who has a {}.", state.players[target], state.hands[target].unwrap().name());
-                            let your_score = state.hands[state.turn].unwrap().value();
-                            let their_score = state.hands[target].unwrap().value();
-                            if your_score > their_score {
-                                println!("You win, {} is out.", state.players[target]);
-                                state.out.push(target);
-                            } else if your_score < their_score {
-                                println!("You lose, you are out.");
-                                state.out.push(state.turn);
-                            } else {
-                                println!("You tie.");
-                            }
-                            Ok(RoundStatus::Continue)
-                        },
-                    }
-                },
-                Card::Wilkins => {
-                    let target = play_target(state_clone, &card);
-                    match target {
-                        Target::Cancel => return (Ok(RoundStatus::Retry), state_),
-                        Target::None => {
-                            println!("You discard without a target.");
-                            Ok(RoundStatus::Continue)
-                        },
-                        Target::Player(target) => {
-                            println!("{}'s hand is:\n{}", state.players[target], state.hands[target].unwrap().to_string()); 
-                            Ok(RoundStatus::Continue)
-                        },
-                    }
-                },
-                Card::Guard => {
-                    let target = play_target(state_clone, &card);
-                    match target {
-                        Target::Cancel => return (Ok(RoundStatus::Retry), state_),
-                        Target::None => {
-                            println!("You discard without a target.");
-                            Ok(RoundStatus::Continue)
-                        },
-                        Target::Player(target) => {
-                            println!("What card would you like to guess?");
-                            
-                            // List all cards except the Guard
-                            let card_list = list_cards();
-                            let cards = card_list.len();
-                            for card in card_list {
-                                if card != Card::Guard {
-                                    println!("{}. {}", card.value(), card.to_string());
-                                }
-                            }
-        
-                            // Get guess
-                            print!(": ");
-                            let mut guess: usize;
-                            loop {
-                                guess = read!();
-                                if guess != Card::Guard.value() && guess < cards.try_into().unwrap() {
-                                    break;
-                                } else {
-                                    println!("That is not a valid card.");
-                                    print!(": ");
-                                }
-                            }
-                            let guess = list_cards()[(cards - guess - 1) as usize];
-                            if state.hands[target].unwrap() == guess {
-                                println!("You guessed \"{}\" correctly, {} is out.", guess.name(), state.players[target]);
-                                state.out.push(target);
-                            } else {
-                                println!("You guessed \"{}\", which is incorrect.", guess.name());
-                            }
-                            Ok(RoundStatus::Continue)
-                        },
-                    }
-                },
-                Card::Dormouse => {
-                    match state.dormouse {
-                        Some(player) if player == state.turn => {
-                            println!("You discarded the second Dormouse.");
-                        },
-                        Some(player) => {
-                            println!("You discarded the second Dormouse, nor you or {} will be awarded a token.", state.players[player]);
-                            state.dormouse = None;
-                        },
-                        None => {
-                            println!("You discarded the first Dormouse.");
-                            state.dormouse = Some(state.turn);
-                        },
-                    }
-                    Ok(RoundStatus::Continue)
+        let mut turns = Vec::new();
+        loop {
+            let player = state.turn;
+            let mut events = Vec::new();
+            let mut recorder = Recorder::new(human.as_mut());
+            let round_status = play_turn(state, &mut recorder, &mut events);
+            state = round_status.1;
+            for event in &events {
+                if let Some(text) = event.describe(&state.players, player) {
+                    println!("{}", text);
                 }
             }
-        } else {
-            Err(PlayError::InvalidHand)
-        }
-    };
-
-    (result, state)
-}
-
-fn play_turn(state_: State) -> (RoundStatus, State) {
-    clear_screen();
-
-    // Check if game is over
-    if state_.out.len() == state_.players.len() - 1 {
-        return (RoundStatus::End, state_);
-    }
-
-    // Check if player is out
-    if state_.out.contains(&state_.turn) {
-        return (RoundStatus::Skip, state_);
-    }
-
-    // Check if there is cards in the deck
-    if state_.deck.len() == 0 {
-        return (RoundStatus::TieBreaker, state_);
-    }
-    
-    // Play turn
-    let mut state = state_.clone();
-    println!("\n\n================= {} =================", state.players[state.turn]);
-    println!("{}'s turn", state.players[state.turn]);
-    println!("Round {}", state.round);
-    println!("Discard piles:");
-    for (i, player) in state.players.iter().enumerate() {
-        if state.discard[i].len() == 0 {
-            if state.out.contains(&i) {
-                print!("\t{}. {} (Out): ", i + 1, player);
-            } else {
-                print!("\t{}. {}: ", i + 1, player);
-            }
-        } else {
-            if state.out.contains(&i) {
-                print!("\t{}. {} (Out): {}", i + 1, player, state.discard[i][0].name());
-            } else {
-                print!("\t{}. {}: {}", i + 1, player, state.discard[i][0].name());
-            }
-            for card in state.discard[i].iter().skip(1) {
-                print!(", {}", card.name());
-            }
-        }
-        println!("");
-    }
-    print!("Tokens: {} {}", state.players[0], state.tokens[0]);
-    for (i, player) in state.players.iter().skip(1).enumerate() {
-        print!(", {} {}", player, state.tokens[i+1]);
-    }
-    println!(".\n");
-    println!("Type 1 to draw a card.");
-    let card1 = state.hands[state.turn].unwrap();
-    loop {
-        let i: i32 = read!();
-        if i == 1 {
-            break;
-        }
-    }
-    let card2 = state.deck.pop().unwrap();
-    println!("What would you like to do?");
-    println!("1. Discard/Play: {}", card1.to_string());
-    println!("2. Discard/Play: {}", card2.to_string());
-    print!(": ");
-    loop {
-        let choice: i32 = read!();
-        let turn_result = match choice {
-            1 => {
-                state.hands[state.turn] = Some(card2);
-                play_card(state, card1)
-            },
-            2 => {
-                state.hands[state.turn] = Some(card1);
-                play_card(state, card2)
-            },
-            _ => (Err(PlayError::InvalidCard), state),
-        };
-        state = turn_result.1;
-
-        if let Ok(round_status) = turn_result.0 {
-            match round_status {
-                RoundStatus::Continue => break,
-                RoundStatus::Skip => return (RoundStatus::Skip, state_),
-                RoundStatus::Retry => return play_turn(state_),
-                RoundStatus::TieBreaker => return (RoundStatus::TieBreaker, state),
-                RoundStatus::End => return (RoundStatus::End, state),
-            };
-        } else if let Err(play_error) = turn_result.0 {
-            match play_error {
-                PlayError::InvalidPlayer => {
-                    println!("Oops, can't discard if you are out of the game!");
-                    break;
-                },
-                _ => {
-                    print!("Invalid choice. Try again.");
-                    return play_turn(state_);
+            turns.extend(recorder.turn(player, events.clone()));
+            for event in &events {
+                human.observe(&state, event);
+                if let Some(spectators) = spectators.as_mut() {
+                    spectators.observe(&state, event);
                 }
             }
-        }
-
-    }
-
-    println!("==========================================\n\n");
-    (RoundStatus::Continue, state)
-}
-
-fn play_tie_breaker(state_: State) -> State {
-    println!("Tie breaker!");
-    let mut state = state_.clone();
-
-    // Check the hands of all players that are not out
-    let mut hands = Vec::new();
-    for i in 0..state.players.len() {
-        if !state.out.contains(&i) {
-            hands.push(state.hands[i].unwrap());
-        }
-    }
-
-    // Check what card has the highest value
-    let mut highest_card = hands[0];
-    for card in hands {
-        if card.value() > highest_card.value() {
-            highest_card = card;
-        }
-    }
-
-    // All players with hands lower than the highest card are out
-    for i in 0..state.players.len() {
-        if !state.out.contains(&i) {
-            if state.hands[i].unwrap().value() < highest_card.value() {
-                state.out.push(i);
-            }
-        }
-    }
 
-    state
-}
+            next_turn(&mut state);
 
-fn main() {
-    let mut state = setup(None);
-    loop {
-        loop {
-            let round_status = play_turn(state);
-            state = round_status.1;
-            
-            state.turn += 1;
-            if state.turn >= state.players.len() {
-                state.turn = 0;
-            }
-            
             match round_status.0 {
                 RoundStatus::Continue => {
+                    println!("==========================================\n\n");
                     println!("Type 1 to end turn.");
-                    loop {
-                        let i: i32 = read!();
-                        if i == 1 {
-                            break;
-                        }
-                    }
+                    wait_for_one();
                     continue;
                 },
                 RoundStatus::Skip => {
                     continue;
                 },
-                RoundStatus::Retry => {
-                    panic!("Retry should not be returned from play_turn");
-                },
                 RoundStatus::TieBreaker => {
+                    println!("Tie breaker!");
                     state = play_tie_breaker(state);
                     break;
                 },
@@ -851,16 +212,19 @@ fn main() {
         }
 
         // Check what players won
-        for i in 0..state.players.len() {
-            if !state.out.contains(&i) {
-                state.tokens[i] += 1;
-                println!("{} got a token and now has {} of them!", state.players[i], state.tokens[i])
-            }
+        let winners = award_tokens(&mut state);
+        if let Some(spectators) = spectators.as_mut() {
+            spectators.end_round(&state, &winners);
+        }
+        for i in &winners {
+            println!("{} got a token and now has {} of them!", state.players[*i], state.tokens[*i])
+        }
+        if let Some(path) = record {
+            save_rounds(path, &[RoundRecord { start, turns, end: state.clone(), winners }])?;
         }
 
         // Check if there is a Dormouse
         if let Some(player) = state.dormouse {
-            state.tokens[player] += 1;
             println!("{} was the only one to discard a Dormouse and is awarded a token and now has {} of them!", state.players[player], state.tokens[player])
         }
 
@@ -869,7 +233,7 @@ fn main() {
         for i in 0..state.players.len() {
             leaderboard.push((i, state.tokens[i]));
         }
-        leaderboard.sort_by(|a, b| b.1.cmp(&a.1));
+        leaderboard.sort_by_key(|player| std::cmp::Reverse(player.1));
         println!("Leaderboard:");
         for (i, player) in leaderboard.iter().enumerate() {
             println!("{}. {} with {} tokens", i + 1, state.players[player.0], player.1);
@@ -878,13 +242,20 @@ fn main() {
         println!("Would you like to play another round?\n1. Yes\n2. No");
         loop {
             print!(": ");
-            let choice: i32 = read!();
+            let choice: i32 = read();
             match choice {
                 1 => {
                     state = setup(Some(state));
                     break;
                 },
-                2 => return,
+                2 => {
+                    if let Some(spectators) = spectators.as_mut() {
+                        let (leader, tokens) = leaderboard[0];
+                        let tied = leaderboard.iter().filter(|player| player.1 == tokens).count() > 1;
+                        spectators.finish((!tied).then(|| state.players[leader].clone()));
+                    }
+                    return save_ratings(options, &state);
+                },
                 _ => {
                     println!("Invalid choice. Try again.");
                     continue;
@@ -894,36 +265,27 @@ fn main() {
     }
 }
 
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_no_possible_play() {
-        let mut state = State {
-            deck: create_deck(),
-            discard: vec![vec![Card::Nobody], vec![Card::Nobody]],
-            players: vec!["Alice".to_string(), "Bob".to_string()],
-            out: Vec::new(),
-            hands: vec![Some(Card::Guard), Some(Card::Guard)],
-            tokens: vec![0, 0],
-            round: 0,
-            turn: 0,
-            dormouse: None,
-        };
-
-        let card = Card::Guard;
-        assert_eq!(no_possible_play(&state, &card), true);
-        
-        state.hands[0] = Some(Card::Time);
-        assert_eq!(no_possible_play(&state, &card), true);
-        
-        state.hands[0] = Some(Card::Guard);
-        let card = Card::Wilkins;
-        assert_eq!(no_possible_play(&state, &card), true);
-
-        let card = Card::RedQueen;
-        assert_eq!(no_possible_play(&state, &card), false);
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let command = args.get(1).map(|command| command.as_str());
+    let result = Options::parse(args.get(2..).unwrap_or(&[])).and_then(|options| match command {
+        None | Some("play") => play_hot_seat(&options),
+        Some("simulate") => sim::command(&options),
+        Some("balance") => analytics::command(&options),
+        Some("seats") => seats::command(&options),
+        Some("tournament") => tournament::command(&options),
+        Some("ratings") => ratings::command(&options),
+        Some("match") => engine::command(&options),
+        Some("solve") => solver::command(&options),
+        Some("review") => review::command(&options),
+        Some("model") => model::command(&options),
+        Some("serve") => server::command(&options),
+        Some("connect") => client::command(&options),
+        Some("mail") => mail::command(&options),
+        Some(command) => Err(format!("Unknown command \"{}\". Commands: play, simulate, balance, seats, tournament, ratings, match, solve, review, model, serve, connect, mail.", command)),
+    });
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        std::process::exit(1);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,231 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+
+use crate::card::{list_cards, Card};
+use crate::game::guesses;
+use crate::options::Options;
+use crate::record::{load_rounds, RoundRecord};
+
+/// How one named player has played in the recorded games.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tendencies {
+    /// `held[a][b]`: turns they held the cards of values `a` and `b`, counted both ways.
+    pub held: [[u32; 10]; 10],
+    /// `played[a][b]`: turns they played the card of value `a` and kept `b`.
+    pub played: [[u32; 10]; 10],
+    /// How often they guessed each card with the Guard.
+    pub guesses: [u32; 10],
+}
+
+impl Tendencies {
+    /// The chance they play `card` while also holding `other`, starting from even odds for pairs
+    /// of cards they were never seen with.
+    pub fn play_rate(&self, card: Card, other: Card) -> f64 {
+        if card == other {
+            return 1.0;
+        }
+        let (card, other) = (card.value(), other.value());
+        (self.played[card][other] as f64 + 1.0) / (self.held[card][other] as f64 + 2.0)
+    }
+
+    /// The chance their Guard guesses `card`, starting from every guess being as likely.
+    pub fn guess_rate(&self, card: Card) -> f64 {
+        if card == Card::Guard {
+            return 0.0;
+        }
+        let total: u32 = self.guesses.iter().sum();
+        (self.guesses[card.value()] as f64 + 1.0) / (total as f64 + guesses().len() as f64)
+    }
+
+    fn merge(&mut self, other: &Tendencies) {
+        for a in 0..10 {
+            for b in 0..10 {
+                self.held[a][b] += other.held[a][b];
+                self.played[a][b] += other.played[a][b];
+            }
+            self.guesses[a] += other.guesses[a];
+        }
+    }
+}
+
+/// The tendencies of every player seen in the recorded games, by name.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OpponentModel {
+    pub players: BTreeMap<String, Tendencies>,
+}
+
+impl OpponentModel {
+    pub fn tendencies(&self, name: &str) -> Option<&Tendencies> {
+        self.players.get(name)
+    }
+
+    /// Adds what every player did in `rounds`.
+    pub fn learn(&mut self, rounds: &[RoundRecord]) {
+        for round in rounds {
+            for turn in &round.turns {
+                let tendencies = self.players.entry(round.start.players[turn.player].clone()).or_default();
+                let [a, b] = turn.hand.map(|card| card.value());
+                if a != b {
+                    tendencies.held[a][b] += 1;
+                    tendencies.held[b][a] += 1;
+                }
+                let kept = if turn.action.card == turn.hand[0] { turn.hand[1] } else { turn.hand[0] };
+                tendencies.played[turn.action.card.value()][kept.value()] += 1;
+                if let Some(guess) = turn.action.guess {
+                    tendencies.guesses[guess.value()] += 1;
+                }
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: &OpponentModel) {
+        for (name, tendencies) in &other.players {
+            self.players.entry(name.clone()).or_default().merge(tendencies);
+        }
+    }
+
+    /// Writes the model as one `player` block per player, listing only counts that are not 0:
+    ///
+    /// ```text
+    /// player Ann
+    /// held <a> <b> <turns>
+    /// played <a> <b> <turns>
+    /// guess <card> <times>
+    /// ```
+    pub fn write(&self) -> String {
+        let mut text = String::new();
+        for (name, tendencies) in &self.players {
+            writeln!(text, "player {}", name).unwrap();
+            for (keyword, counts) in [("held", &tendencies.held), ("played", &tendencies.played)] {
+                for (a, row) in counts.iter().enumerate() {
+                    for (b, count) in row.iter().enumerate().filter(|(_, count)| **count > 0) {
+                        writeln!(text, "{} {} {} {}", keyword, a, b, count).unwrap();
+                    }
+                }
+            }
+            for (card, count) in tendencies.guesses.iter().enumerate().filter(|(_, count)| **count > 0) {
+                writeln!(text, "guess {} {}", card, count).unwrap();
+            }
+        }
+        text
+    }
+
+    pub fn read(text: &str) -> Result<OpponentModel, String> {
+        let mut model = OpponentModel::default();
+        let mut player: Option<String> = None;
+        for (number, line) in text.lines().enumerate() {
+            let error = |message: String| format!("Line {}: {}", number + 1, message);
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let Some((keyword, rest)) = words.split_first() else {
+                continue;
+            };
+            if *keyword == "player" {
+                let name = rest.join(" ");
+                model.players.entry(name.clone()).or_default();
+                player = Some(name);
+                continue;
+            }
+            let name = player.as_ref().ok_or(error(format!("\"{}\" outside of a player.", keyword)))?;
+            let numbers: Vec<usize> = rest.iter().map(|word| word.parse().map_err(|_| error(format!("\"{}\" is not a number.", word)))).collect::<Result<Vec<usize>, String>>()?;
+            if numbers.iter().rev().skip(1).any(|card| *card >= 10) {
+                return Err(error("cards go from 0 to 9.".to_string()));
+            }
+            let tendencies = model.players.get_mut(name).unwrap();
+            match (*keyword, &numbers[..]) {
+                ("held", [a, b, count]) => tendencies.held[*a][*b] = *count as u32,
+                ("played", [a, b, count]) => tendencies.played[*a][*b] = *count as u32,
+                ("guess", [card, count]) => tendencies.guesses[*card] = *count as u32,
+                _ => return Err(error(format!("unknown line \"{}\".", line))),
+            }
+        }
+        Ok(model)
+    }
+
+    pub fn load(path: &str) -> Result<OpponentModel, String> {
+        let text = fs::read_to_string(path).map_err(|error| format!("Could not read {}: {}", path, error))?;
+        OpponentModel::read(&text).map_err(|error| format!("{}: {}", path, error))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.write()).map_err(|error| format!("Could not write {}: {}", path, error))
+    }
+}
+
+/// `model`: learns the tendencies of every player in `--record file`, adds them to the model in
+/// `--save file` if given, and prints what each player tends to play first and guess.
+pub fn command(options: &Options) -> Result<(), String> {
+    let path = options.value("record").ok_or("Give the rounds to learn from with --record file.")?;
+    let mut model = OpponentModel::default();
+    model.learn(&load_rounds(path)?);
+
+    if let Some(save) = options.value("save") {
+        let mut saved = if fs::metadata(save).is_ok() { OpponentModel::load(save)? } else { OpponentModel::default() };
+        saved.merge(&model);
+        saved.save(save)?;
+        println!("Saved {} players to {}.", saved.players.len(), save);
+        model = saved;
+    }
+
+    for (name, tendencies) in &model.players {
+        let turns: u32 = tendencies.played.iter().flatten().sum();
+        println!("{} ({} turns):", name, turns);
+
+        // How often they play each card when holding it with a different one
+        let mut first: Vec<(Card, u32, u32)> = list_cards().into_iter().map(|card| {
+            let value = card.value();
+            let played: u32 = (0..10).filter(|other| *other != value).map(|other| tendencies.played[value][other]).sum();
+            let held: u32 = tendencies.held[value].iter().sum();
+            (card, played, held)
+        }).filter(|(_, _, held)| *held > 0).collect();
+        first.sort_by(|a, b| (b.1 as f64 / b.2 as f64).total_cmp(&(a.1 as f64 / a.2 as f64)));
+        let first: Vec<String> = first.iter().map(|(card, played, held)| format!("{} {}/{}", card.name(), played, held)).collect();
+        println!("\tplays first: {}", first.join(", "));
+
+        let mut guessed: Vec<Card> = guesses().into_iter().filter(|card| tendencies.guesses[card.value()] > 0).collect();
+        guessed.sort_by_key(|card| std::cmp::Reverse(tendencies.guesses[card.value()]));
+        let guessed: Vec<String> = guessed.iter().map(|card| format!("{} {}", card.name(), tendencies.guesses[card.value()])).collect();
+        println!("\tGuard guesses: {}", if guessed.is_empty() { "none".to_string() } else { guessed.join(", ") });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Action, State};
+    use crate::record::Turn;
+
+    #[test]
+    fn test_learn_and_read() {
+        let state = State {
+            deck: Vec::new(),
+            discard: vec![vec![], vec![]],
+            players: vec!["Ann".to_string(), "Bob".to_string()],
+            out: Vec::new(),
+            hands: vec![Some(Card::Guard), Some(Card::Time)],
+            tokens: vec![0, 0],
+            round: 1,
+            turn: 0,
+            dormouse: None,
+        };
+        let turn = |player, hand, card, target, guess| Turn { player, hand, action: Action { card, target, guess }, executioner: None, events: Vec::new() };
+        let turns = vec![
+            turn(0, [Card::Guard, Card::Nobody], Card::Nobody, None, None),
+            turn(1, [Card::Time, Card::Guard], Card::Guard, Some(0), Some(Card::Alice)),
+            turn(0, [Card::Guard, Card::Nobody], Card::Nobody, None, None),
+        ];
+        let mut model = OpponentModel::default();
+        model.learn(&[RoundRecord { start: state.clone(), turns, end: state, winners: Vec::new() }]);
+
+        let ann = model.tendencies("Ann").unwrap();
+        assert_eq!(ann.play_rate(Card::Nobody, Card::Guard), 0.75);
+        assert_eq!(ann.play_rate(Card::Guard, Card::Nobody), 0.25);
+        assert_eq!(ann.play_rate(Card::Time, Card::Alice), 0.5);
+        let bob = model.tendencies("Bob").unwrap();
+        assert_eq!(bob.guess_rate(Card::Alice), 0.2);
+        assert_eq!(bob.guess_rate(Card::Time), 0.1);
+
+        assert_eq!(OpponentModel::read(&model.write()).unwrap(), model);
+    }
+}
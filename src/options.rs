@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Command line options of the form `--name value` or `--flag`.
+#[derive(Debug, Default)]
+pub struct Options {
+    values: HashMap<String, String>,
+    flags: Vec<String>,
+}
+
+impl Options {
+    pub fn parse(args: &[String]) -> Result<Options, String> {
+        let mut options = Options::default();
+        let mut i = 0;
+        while i < args.len() {
+            let Some(name) = args[i].strip_prefix("--") else {
+                return Err(format!("Unexpected argument \"{}\".", args[i]));
+            };
+            match args.get(i + 1) {
+                Some(value) if !value.starts_with("--") => {
+                    options.values.insert(name.to_string(), value.clone());
+                    i += 2;
+                },
+                _ => {
+                    options.flags.push(name.to_string());
+                    i += 1;
+                },
+            }
+        }
+        Ok(options)
+    }
+
+    pub fn get<T: FromStr>(&self, name: &str, default: T) -> Result<T, String> {
+        match self.values.get(name) {
+            Some(value) => value.parse().map_err(|_| format!("Invalid value \"{}\" for --{}.", value, name)),
+            None => Ok(default),
+        }
+    }
+
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(|value| value.as_str())
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.iter().any(|flag| flag == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let args: Vec<String> = ["--games", "10", "--quiet", "--players", "4"].iter().map(|arg| arg.to_string()).collect();
+        let options = Options::parse(&args).unwrap();
+        assert_eq!(options.get("games", 0).unwrap(), 10);
+        assert_eq!(options.get("players", 2).unwrap(), 4);
+        assert_eq!(options.get("seed", 7).unwrap(), 7);
+        assert!(options.flag("quiet"));
+        assert!(options.get::<usize>("games", 0).is_ok());
+        assert!(Options::parse(&["games".to_string()]).is_err());
+    }
+}
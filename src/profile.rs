@@ -0,0 +1,73 @@
+use std::fs;
+
+use crate::bots::Style;
+
+/// A named heuristic bot with its own play style.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub name: String,
+    pub style: Style,
+}
+
+/// Reads profiles written as one `profile` block each, leaving out what the default style does:
+///
+/// ```text
+/// # Goes for knock outs and passes Alice on
+/// profile reckless
+/// aggression 2
+/// hold-alice 0
+/// time-bluff 1
+/// dormouse 0.5
+/// ```
+pub fn read_profiles(text: &str) -> Result<Vec<Profile>, String> {
+    let mut profiles: Vec<Profile> = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let error = |message: String| format!("Line {}: {}", number + 1, message);
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (keyword, value) = match words[..] {
+            [] => continue,
+            [keyword, ..] if keyword.starts_with('#') => continue,
+            [keyword, value] => (keyword, value),
+            _ => return Err(error("expected a name and a value.".to_string())),
+        };
+        if keyword == "profile" {
+            if profiles.iter().any(|profile| profile.name == value) {
+                return Err(error(format!("profile \"{}\" is already defined.", value)));
+            }
+            profiles.push(Profile { name: value.to_string(), style: Style::default() });
+            continue;
+        }
+        let profile = profiles.last_mut().ok_or(error(format!("\"{}\" outside of a profile.", keyword)))?;
+        let number: f64 = value.parse().map_err(|_| error(format!("\"{}\" is not a number.", value)))?;
+        match keyword {
+            "aggression" => profile.style.aggression = number,
+            "hold-alice" => profile.style.hold_alice = number,
+            "time-bluff" => profile.style.time_bluff = number,
+            "dormouse" => profile.style.dormouse = number,
+            _ => return Err(error(format!("unknown setting \"{}\".", keyword))),
+        }
+    }
+    Ok(profiles)
+}
+
+pub fn load_profiles(path: &str) -> Result<Vec<Profile>, String> {
+    let text = fs::read_to_string(path).map_err(|error| format!("Could not read {}: {}", path, error))?;
+    read_profiles(&text).map_err(|error| format!("{}: {}", path, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_profiles() {
+        let text = "# Two styles\nprofile reckless\naggression 2\nhold-alice 0\n\nprofile calm\ndormouse 0.5\n";
+        let profiles = read_profiles(text).unwrap();
+        assert_eq!(profiles.len(), 2);
+        assert_eq!(profiles[0].style, Style { aggression: 2.0, hold_alice: 0.0, ..Style::default() });
+        assert_eq!(profiles[1].style, Style { dormouse: 0.5, ..Style::default() });
+
+        assert!(read_profiles("aggression 2").is_err());
+        assert!(read_profiles("profile calm\npatience 3").is_err());
+    }
+}
@@ -0,0 +1,406 @@
+use std::time::Duration;
+
+use crate::card::Card;
+use crate::engine::{action_token, write_decision, write_view};
+use crate::env::{Decision, Observation};
+use crate::game::{is_protected, Action, State};
+use crate::json::Json;
+use crate::server::PROTOCOL_VERSION;
+use crate::sim::StartPolicy;
+
+// What the server sends a player's client, written either as the lines of the protocol in
+// `server.rs` or, over a WebSocket, as one JSON object per message. The JSON schema follows the
+// lines message for message. Cards are their values, 0 for the Dormouse to 9 for Alice, and seats
+// are numbered from 0 in the order of `names`.
+//
+// server: {"type":"hello","protocol":"loveletter","version":1}
+// client: {"type":"join","name":"Ann"}
+// server: {"type":"welcome","name":"Ann"}
+//         {"type":"wait","joined":1,"players":2}
+//
+// When the game starts, the token to come back with after losing the connection:
+// server: {"type":"session","token":"..."}
+// and to come back, instead of joining:
+// client: {"type":"resume","token":"..."}
+//
+// Spectators answer `hello` with `watch` instead of `join`, asking for the omniscient view if
+// the server offers it:
+// client: {"type":"watch","omniscient":false}
+// server: {"type":"watching","omniscient":false}
+// They get the table at the start of every round and after every turn, the events as someone
+// who is not at the table sees them, the round results and the end of the game:
+// server: {"type":"table","round":1,"turn":1,"deck":11,"names":["Ann","Bob"],"tokens":[0,0],
+//          "out":[false,false],"protected":[false,false],"dormouse":null,"discard":[[4],[]]}
+// The omniscient view shows every round once it is over, or some rounds later, and adds every
+// hand and the deck, top first:
+//          ..."hands":[3,1],"order":[9,1,5,...]
+//
+// On a server with a lobby, after `welcome` and after every answer to the lobby until the
+// player is ready, the rooms and the one the player is in (see `lobby.rs`):
+// server: {"type":"lobby","room":null,"rooms":[{"id":1,"players":2,"start":"rotate","seated":1,
+//          "ready":0,"playing":false}]}
+// client: {"type":"rooms"}
+//         {"type":"create","players":3,"start":"winner"}   the start is optional
+//         {"type":"enter","room":1}
+//         {"type":"watch","room":1,"omniscient":false}     to watch the game of a room instead
+//         {"type":"leave"}
+//         {"type":"ready"}
+//
+// At the start of every round:
+// server: {"type":"round","round":1,"seat":0,"names":["Ann","Bob"]}
+//
+// For every decision, a snapshot of what the seat sees, then the decision itself:
+// server: {"type":"state","seat":0,"round":1,"deck":12,"tokens":[0,0],"out":[false,false],
+//          "protected":[false,false],"dormouse":null,"known":[null,7],"discard":[[],[4]]}
+//         {"type":"decision","decision":"play","hand":[1,3],
+//          "legal":[{"card":1,"target":1,"guess":2},{"card":3,"target":1,"guess":null}]}
+// client: {"type":"action","card":1,"target":1,"guess":2}
+// or after an Executioner:
+// server: {"type":"decision","decision":"keep","hand":3,"drawn":[1,5]}
+// client: {"type":"keep","keep":1,"hand_on_top":false}
+//
+// At a timed table, between the snapshot and the decision, the milliseconds left to decide and
+// in the bank of the player, null if it has none:
+// server: {"type":"clock","left":30000,"bank":240000}
+// and if the player does not answer in time, the default action of the table is played for them:
+// server: {"type":"timeout"}
+//
+// What happened, as the seat saw it, and answers that were not legal:
+// server: {"type":"event","text":"Bob discarded the Nobody."}
+//         {"type":"error","message":"..."}   followed by the whole decision again
+//
+// At the end of every round and of the game:
+// server: {"type":"result","tokens":[1,0],"winners":[0]}
+//         {"type":"winner","name":"Ann"}     the name is null if nobody won
+//         {"type":"quit"}
+//
+// New fields may be added to messages without changing the version. Clients must ignore the
+// fields and the messages they do not know.
+
+/// The version of the JSON schema, sent in `hello`.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A room of the lobby as the players see it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoomInfo {
+    pub id: usize,
+    pub players: usize,
+    pub start: StartPolicy,
+    pub seated: usize,
+    pub ready: usize,
+    pub playing: bool,
+}
+
+/// The table as spectators see it. Only the omniscient view has the hands and the deck.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableView {
+    pub names: Vec<String>,
+    pub round: i32,
+    pub turn: usize,
+    pub deck_size: usize,
+    pub tokens: Vec<i32>,
+    pub out: Vec<bool>,
+    pub protected: Vec<bool>,
+    pub dormouse: Option<usize>,
+    pub discard: Vec<Vec<Card>>,
+    pub hands: Option<Vec<Option<Card>>>,
+    /// The deck, top card first.
+    pub deck: Option<Vec<Card>>,
+}
+
+impl TableView {
+    pub fn new(state: &State, omniscient: bool) -> TableView {
+        let seats = 0..state.players.len();
+        TableView {
+            names: state.players.clone(),
+            round: state.round,
+            turn: state.turn,
+            deck_size: state.deck.len(),
+            tokens: state.tokens.clone(),
+            out: seats.clone().map(|seat| state.out.contains(&seat)).collect(),
+            protected: seats.map(|seat| is_protected(state, seat)).collect(),
+            dormouse: state.dormouse,
+            discard: state.discard.clone(),
+            hands: omniscient.then(|| state.hands.clone()),
+            deck: omniscient.then(|| state.deck.iter().rev().copied().collect()),
+        }
+    }
+}
+
+/// A message from the server to a player's client.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    Hello,
+    Welcome { name: String },
+    Wait { joined: usize, players: usize },
+    Session { token: String },
+    Watching { omniscient: bool },
+    Table(TableView),
+    /// The rooms of the lobby and the one the player is in, asking for the next lobby command.
+    Lobby { rooms: Vec<RoomInfo>, room: Option<usize> },
+    Round { round: i32, seat: usize, names: Vec<String> },
+    State(Observation),
+    Decision { decision: Decision, legal: Vec<Action> },
+    Clock { left: Duration, bank: Option<Duration> },
+    Timeout,
+    Event { text: String },
+    Error { message: String },
+    RoundResult { tokens: Vec<i32>, winners: Vec<usize> },
+    Winner { name: Option<String> },
+    Quit,
+}
+
+fn card(card: Card) -> Json {
+    card.value().into()
+}
+
+fn cards(cards: &[Card]) -> Json {
+    Json::array(cards.iter().map(|card| card.value()))
+}
+
+fn action(action: &Action) -> Json {
+    Json::object(vec![("card", card(action.card)), ("target", action.target.into()), ("guess", action.guess.map(|guess| guess.value()).into())])
+}
+
+impl Message {
+    /// The message in the line protocol.
+    pub fn lines(&self) -> Vec<String> {
+        let join = |values: Vec<String>| values.join(" ");
+        match self {
+            Message::Hello => vec![format!("loveletter {}", PROTOCOL_VERSION)],
+            Message::Welcome { name } => vec![format!("welcome {}", name)],
+            Message::Wait { joined, players } => vec![format!("wait {} {}", joined, players)],
+            Message::Session { token } => vec![format!("session {}", token)],
+            Message::Watching { omniscient } => vec![format!("watching {}", if *omniscient { "all" } else { "public" })],
+            Message::Table(view) => {
+                let values = |values: Vec<String>| values.join(" ");
+                let flags = |flags: &[bool]| values(flags.iter().map(|flag| (*flag as u8).to_string()).collect());
+                let optional = |card: &Option<Card>| card.map_or("-".to_string(), |card| card.value().to_string());
+                let mut lines = vec![
+                    format!("table {} {} {} {}", view.round, view.turn, view.deck_size, view.names.len()),
+                    format!("names {}", view.names.join(" ")),
+                    format!("tokens {}", values(view.tokens.iter().map(|tokens| tokens.to_string()).collect())),
+                    format!("out {}", flags(&view.out)),
+                    format!("protected {}", flags(&view.protected)),
+                    format!("dormouse {}", view.dormouse.map_or("-".to_string(), |seat| seat.to_string())),
+                ];
+                if let Some(hands) = &view.hands {
+                    lines.push(format!("hands {}", values(hands.iter().map(optional).collect())));
+                }
+                if let Some(deck) = &view.deck {
+                    lines.push(format!("order {}", values(deck.iter().map(|card| card.value().to_string()).collect())).trim_end().to_string());
+                }
+                for (seat, pile) in view.discard.iter().enumerate() {
+                    lines.push(format!("discard {} {}", seat, values(pile.iter().map(|card| card.value().to_string()).collect())).trim_end().to_string());
+                }
+                lines
+            },
+            Message::Lobby { rooms, room } => {
+                let mut lines: Vec<String> = rooms.iter().map(|info| {
+                    format!("room {} {} {} {} {} {}", info.id, info.players, info.start.name(), info.seated, info.ready, if info.playing { "playing" } else { "waiting" })
+                }).collect();
+                lines.push(format!("lobby {}", room.map_or("-".to_string(), |room| room.to_string())));
+                lines
+            },
+            Message::Round { round, seat, names } => vec![format!("round {} seat {} players {}", round, seat, names.len()), format!("names {}", names.join(" "))],
+            Message::State(observation) => write_view(observation),
+            Message::Decision { decision, legal } => write_decision(decision, legal),
+            Message::Clock { left, bank } => vec![format!("clock {} {}", left.as_millis(), bank.map_or("-".to_string(), |bank| bank.as_millis().to_string()))],
+            Message::Timeout => vec!["timeout".to_string()],
+            Message::Event { text } => text.lines().map(|line| format!("info {}", line)).collect(),
+            Message::Error { message } => vec![format!("error {}", message)],
+            Message::RoundResult { tokens, winners } => {
+                vec![format!("result {} winners {}", join(tokens.iter().map(|tokens| tokens.to_string()).collect()), join(winners.iter().map(|winner| winner.to_string()).collect()))]
+            },
+            Message::Winner { name } => vec![format!("winner {}", name.as_deref().unwrap_or("-"))],
+            Message::Quit => vec!["quit".to_string()],
+        }
+    }
+
+    /// The message in the JSON schema.
+    pub fn json(&self) -> Json {
+        let typed = |name: &str, mut fields: Vec<(&str, Json)>| {
+            fields.insert(0, ("type", name.into()));
+            Json::object(fields)
+        };
+        match self {
+            Message::Hello => typed("hello", vec![("protocol", "loveletter".into()), ("version", (SCHEMA_VERSION as usize).into())]),
+            Message::Welcome { name } => typed("welcome", vec![("name", name.as_str().into())]),
+            Message::Wait { joined, players } => typed("wait", vec![("joined", (*joined).into()), ("players", (*players).into())]),
+            Message::Session { token } => typed("session", vec![("token", token.as_str().into())]),
+            Message::Watching { omniscient } => typed("watching", vec![("omniscient", (*omniscient).into())]),
+            Message::Table(view) => {
+                let mut fields = vec![
+                    ("round", view.round.into()),
+                    ("turn", view.turn.into()),
+                    ("deck", view.deck_size.into()),
+                    ("names", Json::array(view.names.iter().map(|name| name.as_str()))),
+                    ("tokens", Json::array(view.tokens.iter().copied())),
+                    ("out", Json::array(view.out.iter().copied())),
+                    ("protected", Json::array(view.protected.iter().copied())),
+                    ("dormouse", view.dormouse.into()),
+                    ("discard", Json::Array(view.discard.iter().map(|pile| cards(pile)).collect())),
+                ];
+                if let Some(hands) = &view.hands {
+                    fields.push(("hands", Json::array(hands.iter().map(|card| card.map(|card| card.value())))));
+                }
+                if let Some(deck) = &view.deck {
+                    fields.push(("order", cards(deck)));
+                }
+                typed("table", fields)
+            },
+            Message::Lobby { rooms, room } => {
+                let rooms = rooms.iter().map(|info| Json::object(vec![
+                    ("id", info.id.into()),
+                    ("players", info.players.into()),
+                    ("start", info.start.name().into()),
+                    ("seated", info.seated.into()),
+                    ("ready", info.ready.into()),
+                    ("playing", info.playing.into()),
+                ]));
+                typed("lobby", vec![("room", (*room).into()), ("rooms", Json::Array(rooms.collect()))])
+            },
+            Message::Round { round, seat, names } => {
+                typed("round", vec![("round", (*round).into()), ("seat", (*seat).into()), ("names", Json::array(names.iter().map(|name| name.as_str())))])
+            },
+            Message::State(observation) => typed("state", vec![
+                ("seat", observation.seat.into()),
+                ("round", observation.round.into()),
+                ("deck", observation.deck_size.into()),
+                ("tokens", Json::array(observation.tokens.iter().copied())),
+                ("out", Json::array(observation.out.iter().copied())),
+                ("protected", Json::array(observation.protected.iter().copied())),
+                ("dormouse", observation.dormouse.into()),
+                ("known", Json::array(observation.known.iter().map(|known| known.map(|card| card.value())))),
+                ("discard", Json::Array(observation.discard.iter().map(|pile| cards(pile)).collect())),
+            ]),
+            Message::Decision { decision: Decision::Play { hand }, legal } => {
+                typed("decision", vec![("decision", "play".into()), ("hand", cards(hand)), ("legal", Json::Array(legal.iter().map(action).collect()))])
+            },
+            Message::Decision { decision: Decision::Executioner { hand, drawn }, .. } => {
+                typed("decision", vec![("decision", "keep".into()), ("hand", card(*hand)), ("drawn", cards(drawn))])
+            },
+            Message::Decision { decision: Decision::Done, .. } => typed("decision", vec![("decision", "done".into())]),
+            Message::Clock { left, bank } => {
+                let millis = |duration: &Duration| duration.as_millis() as u64;
+                typed("clock", vec![("left", millis(left).into()), ("bank", bank.as_ref().map(millis).into())])
+            },
+            Message::Timeout => typed("timeout", Vec::new()),
+            Message::Event { text } => typed("event", vec![("text", text.as_str().into())]),
+            Message::Error { message } => typed("error", vec![("message", message.as_str().into())]),
+            Message::RoundResult { tokens, winners } => {
+                typed("result", vec![("tokens", Json::array(tokens.iter().copied())), ("winners", Json::array(winners.iter().copied()))])
+            },
+            Message::Winner { name } => typed("winner", vec![("name", name.clone().into())]),
+            Message::Quit => typed("quit", Vec::new()),
+        }
+    }
+}
+
+/// Reads a JSON message of a client as the line it stands for in the line protocol, so both
+/// kinds of clients are answered alike. Anything else is kept as it is, to be refused.
+pub fn read_answer(text: &str) -> String {
+    let Ok(message) = Json::parse(text) else {
+        return text.to_string();
+    };
+    let number = |key: &str| message.get(key).and_then(Json::as_usize);
+    let optional = |key: &str| match message.get(key) {
+        None | Some(Json::Null) => Some(None),
+        Some(value) => value.as_usize().map(Some),
+    };
+    let answer = match message.get("type").and_then(Json::as_str) {
+        Some("join") => message.get("name").and_then(Json::as_str).map(|name| format!("join {}", name)),
+        Some("resume") => message.get("token").and_then(Json::as_str).map(|token| format!("resume {}", token)),
+        Some("action") => (|| {
+            let card = Card::from_value(number("card")?)?;
+            let guess = match optional("guess")? {
+                Some(guess) => Some(Card::from_value(guess)?),
+                None => None,
+            };
+            Some(format!("play {}", action_token(&Action { card, target: optional("target")?, guess })))
+        })(),
+        Some(command @ ("rooms" | "leave" | "ready")) => Some(command.to_string()),
+        Some("create") => number("players").map(|players| {
+            let start = message.get("start").and_then(Json::as_str).map_or(String::new(), |start| format!(" {}", start));
+            format!("create {}{}", players, start)
+        }),
+        Some("enter") => number("room").map(|room| format!("enter {}", room)),
+        Some("watch") => {
+            let omniscient = if message.get("omniscient").and_then(Json::as_bool) == Some(true) { " all" } else { "" };
+            match number("room") {
+                Some(room) => Some(format!("watch {}{}", room, omniscient)),
+                None => Some(format!("watch{}", omniscient)),
+            }
+        },
+        Some("keep") => match (number("keep"), message.get("hand_on_top").and_then(Json::as_bool)) {
+            (Some(keep), Some(on_top)) => Some(format!("keep {} {}", keep, if on_top { "top" } else { "bottom" })),
+            _ => None,
+        },
+        _ => None,
+    };
+    answer.unwrap_or(text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema() {
+        let observation = Observation {
+            player: 0,
+            seat: 0,
+            players: vec![0, 1],
+            decision: Decision::Play { hand: [Card::Guard, Card::Tweedies] },
+            mask: Vec::new(),
+            round: 1,
+            deck_size: 12,
+            discard: vec![vec![], vec![Card::Nobody]],
+            out: vec![false, false],
+            protected: vec![false, false],
+            tokens: vec![0, 0],
+            dormouse: None,
+            known: vec![None, Some(Card::Time)],
+        };
+        let legal = vec![
+            Action { card: Card::Guard, target: Some(1), guess: Some(Card::Wilkins) },
+            Action { card: Card::Tweedies, target: Some(1), guess: None },
+        ];
+        let messages = [
+            (Message::Hello, r#"{"type":"hello","protocol":"loveletter","version":1}"#),
+            (Message::Wait { joined: 1, players: 2 }, r#"{"type":"wait","joined":1,"players":2}"#),
+            (Message::Round { round: 1, seat: 0, names: vec!["Ann".to_string(), "Bob".to_string()] }, r#"{"type":"round","round":1,"seat":0,"names":["Ann","Bob"]}"#),
+            (
+                Message::State(observation.clone()),
+                r#"{"type":"state","seat":0,"round":1,"deck":12,"tokens":[0,0],"out":[false,false],"protected":[false,false],"dormouse":null,"known":[null,7],"discard":[[],[4]]}"#,
+            ),
+            (
+                Message::Decision { decision: observation.decision.clone(), legal },
+                r#"{"type":"decision","decision":"play","hand":[1,3],"legal":[{"card":1,"target":1,"guess":2},{"card":3,"target":1,"guess":null}]}"#,
+            ),
+            (Message::Decision { decision: Decision::Executioner { hand: Card::Tweedies, drawn: vec![Card::Guard, Card::KnaveOfHearts] }, legal: Vec::new() }, r#"{"type":"decision","decision":"keep","hand":3,"drawn":[1,5]}"#),
+            (Message::RoundResult { tokens: vec![1, 0], winners: vec![0] }, r#"{"type":"result","tokens":[1,0],"winners":[0]}"#),
+            (Message::Winner { name: None }, r#"{"type":"winner","name":null}"#),
+            (Message::Clock { left: Duration::from_secs(30), bank: None }, r#"{"type":"clock","left":30000,"bank":null}"#),
+            (
+                Message::Lobby { rooms: vec![RoomInfo { id: 1, players: 2, start: StartPolicy::Rotate, seated: 1, ready: 0, playing: false }], room: None },
+                r#"{"type":"lobby","room":null,"rooms":[{"id":1,"players":2,"start":"rotate","seated":1,"ready":0,"playing":false}]}"#,
+            ),
+        ];
+        for (message, json) in messages {
+            assert_eq!(message.json().to_string(), json);
+            assert!(!message.lines().is_empty());
+        }
+
+        assert_eq!(read_answer(r#"{"type":"join","name":"Ann"}"#), "join Ann");
+        assert_eq!(read_answer(r#"{"type":"action","card":1,"target":1,"guess":2}"#), "play 1:1:2");
+        assert_eq!(read_answer(r#"{"type":"action","card":4}"#), "play 4");
+        assert_eq!(read_answer(r#"{"type":"keep","keep":1,"hand_on_top":false}"#), "keep 1 bottom");
+        assert_eq!(read_answer(r#"{"type":"create","players":3,"start":"winner"}"#), "create 3 winner");
+        assert_eq!(read_answer(r#"{"type":"enter","room":2}"#), "enter 2");
+        assert_eq!(read_answer(r#"{"type":"resume","token":"00ff"}"#), "resume 00ff");
+        assert_eq!(read_answer(r#"{"type":"watch","omniscient":true}"#), "watch all");
+        assert_eq!(read_answer(r#"{"type":"watch","room":3,"omniscient":false}"#), "watch 3");
+        assert_eq!(read_answer(r#"{"type":"action","card":12}"#), r#"{"type":"action","card":12}"#);
+    }
+}
@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::options::Options;
+
+pub const INITIAL_RATING: f64 = 1500.0;
+/// How far one match can move a rating.
+const K: f64 = 32.0;
+
+/// The score of every player of a match: their tokens in a hot-seat game, or the games they won
+/// at a tournament table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match {
+    pub time: u64,
+    pub results: Vec<(String, i32)>,
+}
+
+impl Match {
+    pub fn new(results: Vec<(String, i32)>) -> Match {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+        Match { time, results }
+    }
+
+    /// Names may not contain whitespace or colons in the file, so those are replaced.
+    fn to_line(&self) -> String {
+        let results: Vec<String> = self.results.iter().map(|(name, tokens)| format!("{}:{}", name.replace([' ', ':'], "_"), tokens)).collect();
+        format!("match {} {}", self.time, results.join(" "))
+    }
+
+    fn from_line(line: &str) -> Option<Match> {
+        let mut words = line.split_whitespace();
+        if words.next()? != "match" {
+            return None;
+        }
+        let time = words.next()?.parse().ok()?;
+        let mut results = Vec::new();
+        for word in words {
+            let (name, tokens) = word.rsplit_once(':')?;
+            results.push((name.to_string(), tokens.parse().ok()?));
+        }
+        Some(Match { time, results })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rating {
+    pub rating: f64,
+    pub matches: u64,
+    pub wins: u64,
+    /// The rating after every match the player took part in.
+    pub history: Vec<(u64, f64)>,
+}
+
+/// Elo generalised to more than two players: every match counts as a duel between every pair of
+/// players, won by the one with the higher score, and each player's change is averaged over their duels.
+#[derive(Debug, Clone, Default)]
+pub struct Ratings {
+    pub players: HashMap<String, Rating>,
+}
+
+impl Ratings {
+    pub fn rating(&self, name: &str) -> f64 {
+        self.players.get(name).map(|player| player.rating).unwrap_or(INITIAL_RATING)
+    }
+
+    /// Updates the ratings of everyone in `game` and returns their changes.
+    pub fn update(&mut self, game: &Match) -> Vec<(String, f64)> {
+        let players = game.results.len();
+        let best = game.results.iter().map(|(_, tokens)| *tokens).max().unwrap_or(0);
+        // A match shared by several leaders has no winner
+        let won = game.results.iter().filter(|(_, tokens)| *tokens == best).count() == 1;
+        let mut changes = Vec::new();
+        for (seat, (name, tokens)) in game.results.iter().enumerate() {
+            let mut change = 0.0;
+            for (other_seat, (other, other_tokens)) in game.results.iter().enumerate() {
+                if other_seat == seat {
+                    continue;
+                }
+                let expected = 1.0 / (1.0 + 10f64.powf((self.rating(other) - self.rating(name)) / 400.0));
+                let score = match tokens.cmp(other_tokens) {
+                    std::cmp::Ordering::Greater => 1.0,
+                    std::cmp::Ordering::Equal => 0.5,
+                    std::cmp::Ordering::Less => 0.0,
+                };
+                change += score - expected;
+            }
+            changes.push((name.clone(), K * change / (players - 1).max(1) as f64));
+        }
+        for ((name, change), (_, tokens)) in changes.iter().zip(&game.results) {
+            let player = self.players.entry(name.clone()).or_insert(Rating { rating: INITIAL_RATING, matches: 0, wins: 0, history: Vec::new() });
+            player.rating += change;
+            player.matches += 1;
+            if won && *tokens == best {
+                player.wins += 1;
+            }
+            player.history.push((game.time, player.rating));
+        }
+        changes
+    }
+
+    /// Players from best to worst rating.
+    pub fn ranking(&self) -> Vec<(&String, &Rating)> {
+        let mut ranking: Vec<(&String, &Rating)> = self.players.iter().collect();
+        ranking.sort_by(|a, b| b.1.rating.partial_cmp(&a.1.rating).unwrap().then(a.0.cmp(b.0)));
+        ranking
+    }
+}
+
+/// Reads every match of a ratings file, which is empty if the file does not exist yet.
+pub fn load_matches(path: &str) -> Result<Vec<Match>, String> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(format!("Could not read {}: {}", path, error)),
+    };
+    text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()).map(|(number, line)| {
+        Match::from_line(line).ok_or(format!("{} line {}: not a match.", path, number + 1))
+    }).collect()
+}
+
+/// The ratings after replaying every match of a ratings file.
+pub fn load(path: &str) -> Result<(Ratings, Vec<Match>), String> {
+    let matches = load_matches(path)?;
+    let mut ratings = Ratings::default();
+    for game in &matches {
+        ratings.update(game);
+    }
+    Ok((ratings, matches))
+}
+
+/// Appends matches to a ratings file and returns the rating changes of each. A match where two
+/// players have the same name, as written to the file, is rejected before anything is written.
+pub fn record(path: &str, games: &[Match]) -> Result<Vec<Vec<(String, f64)>>, String> {
+    let games: Vec<Match> = games.iter().map(|game| Match::from_line(&game.to_line()).unwrap()).collect();
+    for game in &games {
+        let names: Vec<&String> = game.results.iter().map(|(name, _)| name).collect();
+        if let Some(name) = names.iter().enumerate().find(|(i, name)| names[..*i].contains(name)).map(|(_, name)| name) {
+            return Err(format!("{} plays more than once in a match, so it can not be rated.", name));
+        }
+    }
+    let (mut ratings, _) = load(path)?;
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(path).map_err(|error| format!("Could not open {}: {}", path, error))?;
+    let mut changes = Vec::new();
+    for game in &games {
+        writeln!(file, "{}", game.to_line()).map_err(|error| format!("Could not write {}: {}", path, error))?;
+        changes.push(ratings.update(game));
+    }
+    Ok(changes)
+}
+
+/// `ratings`: prints the rankings of a ratings file (`--file`), the last `--history` matches and
+/// the rating history of `--player`.
+pub fn command(options: &Options) -> Result<(), String> {
+    let path = options.value("file").ok_or("Give the ratings file with --file, as written by --ratings.")?;
+    let (ratings, matches) = load(path)?;
+    if matches.is_empty() {
+        println!("No matches in {} yet.", path);
+        return Ok(());
+    }
+
+    println!("Rankings:");
+    println!("{:<4}{:<20}{:>8}{:>9}{:>7}", "#", "Player", "Rating", "Matches", "Wins");
+    for (place, (name, player)) in ratings.ranking().iter().enumerate() {
+        println!("{:<4}{:<20}{:>8.0}{:>9}{:>7}", format!("{}.", place + 1), name, player.rating, player.matches, player.wins);
+    }
+
+    let history: usize = options.get("history", 10)?;
+    println!("\nLast {} matches:", history.min(matches.len()));
+    let mut replay = Ratings::default();
+    let mut lines = Vec::new();
+    for game in &matches {
+        let changes = replay.update(game);
+        let results: Vec<String> = game.results.iter().zip(changes.iter()).map(|((name, tokens), (_, change))| format!("{} {} ({:+.0})", name, tokens, change)).collect();
+        lines.push(format!("{}: {}", game.time, results.join(", ")));
+    }
+    for line in lines.iter().skip(lines.len().saturating_sub(history)) {
+        println!("\t{}", line);
+    }
+
+    if let Some(name) = options.value("player") {
+        let player = ratings.players.get(name).ok_or(format!("{} has not played any matches.", name))?;
+        println!("\nRating history of {}:", name);
+        for (time, rating) in &player.history {
+            println!("\t{}: {:.0}", time, rating);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update() {
+        let mut ratings = Ratings::default();
+        let game = Match { time: 1, results: vec![("Ann".to_string(), 3), ("Bob".to_string(), 1), ("Cid".to_string(), 1)] };
+        let changes = ratings.update(&game);
+        assert!((changes[0].1 - 16.0).abs() < 1e-9);
+        assert!((changes[1].1 + 8.0).abs() < 1e-9);
+        assert!((ratings.rating("Ann") - 1516.0).abs() < 1e-9);
+        assert_eq!(ratings.players["Ann"].wins, 1);
+        assert_eq!(ratings.ranking()[0].0, "Ann");
+
+        // Beating a stronger player is worth more
+        let game = Match { time: 2, results: vec![("Bob".to_string(), 2), ("Ann".to_string(), 0)] };
+        let changes = ratings.update(&game);
+        assert!(changes[0].1 > 16.0);
+        assert_eq!(ratings.players["Bob"].wins, 1);
+
+        // A draw is nobody's win
+        let game = Match { time: 3, results: vec![("Ann".to_string(), 2), ("Bob".to_string(), 2), ("Cid".to_string(), 0)] };
+        ratings.update(&game);
+        assert_eq!(ratings.players["Ann"].wins, 1);
+        assert_eq!(ratings.players["Bob"].wins, 1);
+        assert_eq!(ratings.players["Ann"].matches, 3);
+    }
+
+    #[test]
+    fn test_same_name_twice() {
+        let path = std::env::temp_dir().join(format!("love_letter_ratings_test_{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        let game = Match { time: 1, results: vec![("Ann".to_string(), 3), ("Bob".to_string(), 1), ("Ann".to_string(), 0)] };
+        assert!(record(path, std::slice::from_ref(&game)).is_err());
+        assert!(load_matches(path).unwrap().is_empty());
+
+        // Names that are only told apart by a space are the same name in the file
+        let spaced = Match { time: 1, results: vec![("a b".to_string(), 1), ("a_b".to_string(), 0)] };
+        assert!(record(path, &[spaced]).is_err());
+
+        // Every seat is scored on its own, even if a name comes back
+        let mut ratings = Ratings::default();
+        let changes = ratings.update(&game);
+        assert!((changes[0].1 - 16.0).abs() < 1e-9);
+        assert!((changes[2].1 + 16.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_line() {
+        let game = Match { time: 5, results: vec![("heuristic #1".to_string(), 4), ("Bob".to_string(), 2)] };
+        let line = game.to_line();
+        assert_eq!(line, "match 5 heuristic_#1:4 Bob:2");
+        assert_eq!(Match::from_line(&line).unwrap().results[0].0, "heuristic_#1");
+        assert_eq!(Match::from_line("round 1"), None);
+    }
+}
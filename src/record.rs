@@ -0,0 +1,420 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::fs;
+use std::rc::Rc;
+
+use crate::agent::Agent;
+use crate::belief::Knowledge;
+use crate::card::Card;
+use crate::game::{award_tokens, next_turn, play_tie_breaker, play_turn, Action, Event, ExecutionerChoice, PlayError, RoundStatus, State};
+
+/// One decision made during a round and what it caused.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Turn {
+    pub player: usize,
+    pub hand: [Card; 2],
+    pub action: Action,
+    pub executioner: Option<ExecutionerChoice>,
+    pub events: Vec<Event>,
+}
+
+/// Everything needed to replay a round: the deal and every decision.
+#[derive(Debug, Clone)]
+pub struct RoundRecord {
+    pub start: State,
+    pub turns: Vec<Turn>,
+    /// The state after the tie breaker with tokens awarded.
+    pub end: State,
+    pub winners: Vec<usize>,
+}
+
+/// Remembers the decisions the wrapped agent makes during one turn.
+pub struct Recorder<'a> {
+    agent: &'a mut dyn Agent,
+    hand: Option<[Card; 2]>,
+    action: Option<Action>,
+    executioner: Option<ExecutionerChoice>,
+}
+
+impl<'a> Recorder<'a> {
+    pub fn new(agent: &'a mut dyn Agent) -> Recorder<'a> {
+        Recorder { agent, hand: None, action: None, executioner: None }
+    }
+
+    /// The recorded turn of `player`, or `None` if they did not get to decide anything.
+    pub fn turn(&self, player: usize, events: Vec<Event>) -> Option<Turn> {
+        match (self.hand, self.action) {
+            (Some(hand), Some(action)) => Some(Turn { player, hand, action, executioner: self.executioner, events }),
+            _ => None,
+        }
+    }
+}
+
+impl Agent for Recorder<'_> {
+    fn choose_action(&mut self, state: &State, hand: [Card; 2], legal: &[Action]) -> Action {
+        let action = self.agent.choose_action(state, hand, legal);
+        self.hand = Some(hand);
+        self.action = Some(action);
+        self.executioner = None;
+        action
+    }
+
+    fn choose_executioner(&mut self, state: &State, hand: Card, drawn: &[Card]) -> ExecutionerChoice {
+        let choice = self.agent.choose_executioner(state, hand, drawn);
+        self.executioner = Some(choice);
+        choice
+    }
+
+    fn invalid_action(&mut self, state: &State, action: &Action, error: &PlayError) {
+        self.agent.invalid_action(state, action, error);
+    }
+
+    fn start_round(&mut self, state: &State, seat: usize) {
+        self.agent.start_round(state, seat);
+    }
+
+    fn observe(&mut self, state: &State, event: &Event) {
+        self.agent.observe(state, event);
+    }
+
+    fn end_round(&mut self, state: &State, winners: &[usize]) {
+        self.agent.end_round(state, winners);
+    }
+}
+
+/// Plays a round to the end without any input, with `agents[i]` deciding for seat `i`.
+pub fn play_round(start: State, agents: &mut [Box<dyn Agent>]) -> RoundRecord {
+    let mut state = start.clone();
+    let mut turns = Vec::new();
+    for (seat, agent) in agents.iter_mut().enumerate() {
+        agent.start_round(&start, seat);
+    }
+    loop {
+        let player = state.turn;
+        let mut events = Vec::new();
+        let mut recorder = Recorder::new(agents[player].as_mut());
+        let (round_status, next_state) = play_turn(state, &mut recorder, &mut events);
+        let turn = recorder.turn(player, events.clone());
+        for event in &events {
+            for agent in agents.iter_mut() {
+                agent.observe(&next_state, event);
+            }
+        }
+        turns.extend(turn);
+        state = next_state;
+        next_turn(&mut state);
+        match round_status {
+            RoundStatus::Continue | RoundStatus::Skip => continue,
+            RoundStatus::TieBreaker => {
+                state = play_tie_breaker(state);
+                break;
+            },
+            RoundStatus::End => break,
+        }
+    }
+    let winners = award_tokens(&mut state);
+    for agent in agents.iter_mut() {
+        agent.end_round(&state, &winners);
+    }
+    RoundRecord { start, turns, end: state, winners }
+}
+
+/// A recorded decision as the player saw it when making it.
+#[derive(Debug, Clone)]
+pub struct Position {
+    /// The state with the drawn card taken from the deck, as `Agent::choose_action` gets it.
+    pub state: State,
+    pub knowledge: Knowledge,
+    pub turn: Turn,
+}
+
+/// Repeats the recorded decisions of one seat, keeping track of what it knows. Once the record
+/// does not fit the round, it says why in `failure` and plays the first legal action to the end.
+struct Scripted {
+    turns: VecDeque<Turn>,
+    executioner: Option<ExecutionerChoice>,
+    knowledge: Option<Knowledge>,
+    positions: Rc<RefCell<Vec<Position>>>,
+    failure: Rc<RefCell<Option<String>>>,
+}
+
+impl Scripted {
+    /// Keeps the first thing wrong with the record.
+    fn fail(&self, state: &State, message: String) {
+        self.failure.borrow_mut().get_or_insert(format!("{} {}", state.players[state.turn], message));
+    }
+}
+
+impl Agent for Scripted {
+    fn start_round(&mut self, state: &State, seat: usize) {
+        self.knowledge = Some(Knowledge::new(state, seat));
+    }
+
+    fn choose_action(&mut self, state: &State, hand: [Card; 2], legal: &[Action]) -> Action {
+        if self.failure.borrow().is_some() {
+            return legal[0];
+        }
+        let Some(turn) = self.turns.pop_front() else {
+            self.fail(state, "has no more turns in the record.".to_string());
+            return legal[0];
+        };
+        if turn.hand != hand || !legal.contains(&turn.action) {
+            self.fail(state, format!("cannot play {} with {} and {}.", turn.action.describe(&state.players), hand[0].name(), hand[1].name()));
+            return legal[0];
+        }
+        if let Some(knowledge) = self.knowledge.as_mut() {
+            self.positions.borrow_mut().push(Position { state: state.clone(), knowledge: knowledge.clone(), turn: turn.clone() });
+            knowledge.hand = Some(if turn.action.card == hand[0] { hand[1] } else { hand[0] });
+        }
+        self.executioner = turn.executioner;
+        turn.action
+    }
+
+    fn choose_executioner(&mut self, state: &State, _hand: Card, drawn: &[Card]) -> ExecutionerChoice {
+        let fallback = ExecutionerChoice { keep: 0, hand_on_top: true };
+        let choice = match self.executioner.take() {
+            _ if self.failure.borrow().is_some() => fallback,
+            Some(choice) if choice.keep < drawn.len() => choice,
+            _ => {
+                self.fail(state, "has no Executioner choice for the cards drawn in the record.".to_string());
+                fallback
+            },
+        };
+        if let Some(knowledge) = self.knowledge.as_mut() {
+            knowledge.hand = drawn.get(choice.keep).copied();
+        }
+        choice
+    }
+
+    fn invalid_action(&mut self, state: &State, action: &Action, _error: &PlayError) {
+        self.fail(state, format!("cannot play {}.", action.describe(&state.players)));
+    }
+
+    fn observe(&mut self, state: &State, event: &Event) {
+        if let Some(knowledge) = self.knowledge.as_mut() {
+            knowledge.observe(state, event);
+        }
+    }
+}
+
+fn replay(start: State, turns: &[Turn]) -> Result<(RoundRecord, Vec<Position>), String> {
+    let number = start.round;
+    let positions = Rc::new(RefCell::new(Vec::new()));
+    let failure = Rc::new(RefCell::new(None));
+    let mut agents: Vec<Box<dyn Agent>> = Vec::new();
+    for player in 0..start.players.len() {
+        let turns = turns.iter().filter(|turn| turn.player == player).cloned().collect();
+        agents.push(Box::new(Scripted { turns, executioner: None, knowledge: None, positions: positions.clone(), failure: failure.clone() }));
+    }
+    let round = play_round(start, &mut agents);
+    drop(agents);
+    if let Some(failure) = failure.take() {
+        return Err(format!("round {}: {}", number, failure));
+    }
+    if round.turns.len() < turns.len() {
+        return Err(format!("round {}: the record has turns left after the round ended.", number));
+    }
+    let mut positions = Rc::try_unwrap(positions).unwrap().into_inner();
+    for (position, turn) in positions.iter_mut().zip(round.turns.iter()) {
+        position.turn = turn.clone();
+    }
+    Ok((round, positions))
+}
+
+/// Plays the decisions of `turns` again from `start`. An error if they do not fit the round.
+pub fn replay_round(start: State, turns: &[Turn]) -> Result<RoundRecord, String> {
+    replay(start, turns).map(|(round, _)| round)
+}
+
+/// Every decision of a recorded round in the order they were made, with their events.
+pub fn positions(round: &RoundRecord) -> Result<Vec<Position>, String> {
+    replay(round.start.clone(), &round.turns).map(|(_, positions)| positions)
+}
+
+fn cards_to_string(cards: &[Card]) -> String {
+    cards.iter().map(|card| card.value().to_string()).collect::<Vec<String>>().join(" ")
+}
+
+fn option_to_string(value: Option<usize>) -> String {
+    value.map(|value| value.to_string()).unwrap_or("-".to_string())
+}
+
+/// Writes rounds in the line based record format, one `round` block per round:
+///
+/// ```text
+/// round 1
+/// players Ann Bob
+/// tokens 0 0
+/// hands 9 3
+/// deck 1 0 4 ...
+/// turn <player> <held> <drawn> <card> <target|-> <guess|-> [<keep> <hand on top 0|1>]
+/// end
+/// ```
+///
+/// Cards are written as their values and the deck is listed from the bottom up.
+pub fn write_rounds(rounds: &[RoundRecord]) -> String {
+    let mut text = String::new();
+    for round in rounds {
+        let start = &round.start;
+        let hands: Vec<Card> = start.hands.iter().map(|hand| hand.unwrap()).collect();
+        writeln!(text, "round {}", start.round).unwrap();
+        writeln!(text, "players {}", start.players.join(" ")).unwrap();
+        writeln!(text, "tokens {}", start.tokens.iter().map(|tokens| tokens.to_string()).collect::<Vec<String>>().join(" ")).unwrap();
+        writeln!(text, "hands {}", cards_to_string(&hands)).unwrap();
+        writeln!(text, "deck {}", cards_to_string(&start.deck)).unwrap();
+        for turn in &round.turns {
+            let action = &turn.action;
+            write!(text, "turn {} {} {} {} {} {}", turn.player, turn.hand[0].value(), turn.hand[1].value(), action.card.value(), option_to_string(action.target), option_to_string(action.guess.map(|card| card.value()))).unwrap();
+            if let Some(choice) = turn.executioner {
+                write!(text, " {} {}", choice.keep, choice.hand_on_top as usize).unwrap();
+            }
+            writeln!(text).unwrap();
+        }
+        writeln!(text, "end").unwrap();
+    }
+    text
+}
+
+fn parse_number(word: &str) -> Result<usize, String> {
+    word.parse().map_err(|_| format!("\"{}\" is not a number.", word))
+}
+
+fn parse_card(word: &str) -> Result<Card, String> {
+    Card::from_value(parse_number(word)?).ok_or(format!("\"{}\" is not a card.", word))
+}
+
+fn parse_option<T>(word: &str, parse: fn(&str) -> Result<T, String>) -> Result<Option<T>, String> {
+    if word == "-" { Ok(None) } else { parse(word).map(Some) }
+}
+
+/// Reads rounds written by `write_rounds` and replays them to restore their events and results.
+pub fn read_rounds(text: &str) -> Result<Vec<RoundRecord>, String> {
+    let mut rounds = Vec::new();
+    let mut start: Option<State> = None;
+    let mut turns = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let error = |message: String| format!("Line {}: {}", number + 1, message);
+        let Some((keyword, rest)) = words.split_first() else {
+            continue;
+        };
+        if *keyword == "round" {
+            let round = rest.first().ok_or(error("missing round number".to_string()))?;
+            start = Some(State {
+                deck: Vec::new(),
+                discard: Vec::new(),
+                players: Vec::new(),
+                out: Vec::new(),
+                hands: Vec::new(),
+                tokens: Vec::new(),
+                round: round.parse().map_err(|_| error(format!("\"{}\" is not a number.", round)))?,
+                turn: 0,
+                dormouse: None,
+            });
+            turns.clear();
+            continue;
+        }
+        let Some(state) = start.as_mut() else {
+            return Err(error(format!("\"{}\" outside of a round.", keyword)));
+        };
+        match *keyword {
+            "players" => {
+                state.players = rest.iter().map(|name| name.to_string()).collect();
+                state.discard = vec![Vec::new(); rest.len()];
+            },
+            "tokens" => state.tokens = rest.iter().map(|tokens| tokens.parse().map_err(|_| error(format!("\"{}\" is not a number.", tokens)))).collect::<Result<Vec<i32>, String>>()?,
+            "hands" => state.hands = rest.iter().map(|card| parse_card(card).map(Some)).collect::<Result<Vec<Option<Card>>, String>>().map_err(error)?,
+            "deck" => state.deck = rest.iter().map(|card| parse_card(card)).collect::<Result<Vec<Card>, String>>().map_err(error)?,
+            "turn" => {
+                if rest.len() != 6 && rest.len() != 8 {
+                    return Err(error("a turn needs 6 or 8 values.".to_string()));
+                }
+                let executioner = if rest.len() == 8 {
+                    Some(ExecutionerChoice { keep: parse_number(rest[6]).map_err(error)?, hand_on_top: rest[7] == "1" })
+                } else {
+                    None
+                };
+                let player = parse_number(rest[0]).map_err(error)?;
+                if player >= state.players.len() {
+                    return Err(error(format!("player {} is not at the table.", player)));
+                }
+                turns.push(Turn {
+                    player,
+                    hand: [parse_card(rest[1]).map_err(error)?, parse_card(rest[2]).map_err(error)?],
+                    action: Action {
+                        card: parse_card(rest[3]).map_err(error)?,
+                        target: parse_option(rest[4], parse_number).map_err(error)?,
+                        guess: parse_option(rest[5], parse_card).map_err(error)?,
+                    },
+                    executioner,
+                    events: Vec::new(),
+                });
+            },
+            "end" => {
+                let state = start.take().unwrap();
+                let players = state.players.len();
+                if players < 2 || state.hands.len() != players || state.tokens.len() != players {
+                    return Err(error("the round does not have a hand and tokens for every player.".to_string()));
+                }
+                rounds.push(replay_round(state, &turns).map_err(error)?);
+            },
+            _ => return Err(error(format!("unknown keyword \"{}\".", keyword))),
+        }
+    }
+    Ok(rounds)
+}
+
+pub fn load_rounds(path: &str) -> Result<Vec<RoundRecord>, String> {
+    let text = fs::read_to_string(path).map_err(|error| format!("Could not read {}: {}", path, error))?;
+    read_rounds(&text)
+}
+
+/// Appends rounds to a record file, creating it if needed.
+pub fn save_rounds(path: &str, rounds: &[RoundRecord]) -> Result<(), String> {
+    use std::io::Write;
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(path).map_err(|error| format!("Could not open {}: {}", path, error))?;
+    file.write_all(write_rounds(rounds).as_bytes()).map_err(|error| format!("Could not write {}: {}", path, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::deal;
+    use crate::sim::random_bots;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_write_and_read() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut rounds = Vec::new();
+        for _ in 0..20 {
+            let mut agents = random_bots(4, &mut rng);
+            let names = vec!["Ann".to_string(), "Bob".to_string(), "Cid".to_string(), "Dee".to_string()];
+            rounds.push(play_round(deal(names, vec![0; 4], 1, &mut rng), &mut agents));
+        }
+        let read = read_rounds(&write_rounds(&rounds)).unwrap();
+        assert_eq!(read.len(), rounds.len());
+        for (round, read) in rounds.iter().zip(read.iter()) {
+            assert_eq!(round.turns, read.turns);
+            assert_eq!(round.winners, read.winners);
+            assert_eq!(round.end.tokens, read.end.tokens);
+        }
+    }
+
+    #[test]
+    fn test_malformed_record() {
+        let round = "round 1\nplayers Ann Bob\ntokens 0 0\nhands 1 9\ndeck 4 4\n";
+        let read = |turns: &str| read_rounds(&format!("{}{}end\n", round, turns));
+        // Ann plays a card she does not hold
+        assert_eq!(read("turn 0 1 4 7 1 -\n").unwrap_err(), "Line 7: round 1: Ann cannot play Time on Bob with Guard and Nobody.");
+        // Bob has no turn in the record
+        assert_eq!(read("turn 0 1 4 4 - -\n").unwrap_err(), "Line 7: round 1: Bob has no more turns in the record.");
+        assert_eq!(read("turn 0 1 4 4 - -\nturn 1 9 4 4 - -\n").err(), None);
+        // A turn of a seat nobody sits in
+        assert_eq!(read("turn 2 1 4 4 - -\n").unwrap_err(), "Line 6: player 2 is not at the table.");
+        // Ann has a turn left when the deck runs out
+        assert_eq!(read("turn 0 1 4 4 - -\nturn 1 9 4 4 - -\nturn 0 1 9 1 1 9\n").unwrap_err(), "Line 9: round 1: the record has turns left after the round ended.");
+    }
+}
@@ -0,0 +1,420 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use crate::engine::{parse_keep, parse_play};
+use crate::discovery::{rules, Beacon, Listing};
+use crate::env::{Decision, MAX_PLAYERS};
+use crate::json::Json;
+use crate::protocol::{read_answer, Message};
+use crate::server::{play_table, unique_name, Connection, Sessions, Settings};
+use crate::sim::{game_seed, StartPolicy};
+use crate::spectate::Spectators;
+use crate::websocket::{header, read_headers};
+
+// On a server started with `--http`, games are played through an HTTP API instead, for players
+// that come by now and then such as chat bots. Bodies are JSON, and errors are answered with
+// {"error":"..."} and the status that fits.
+//
+// GET  /games                  every game: {"games":[<game>]}
+// POST /games                  {"players":3,"start":"winner"}, both optional, opens a game:
+//                              {"id":1,"players":3,"start":"winner","names":[],"status":"waiting",
+//                               "winner":null}
+// GET  /games/<id>             the game, its status being waiting, playing or over
+// POST /games/<id>/players     {"name":"Ann"} takes a seat: {"name":"Ann","token":"..."}
+//
+// The game starts once every seat is taken. What a player sees is theirs only, so the other
+// requests name them with the token they got, as `Authorization: Bearer <token>`:
+//
+// GET  /games/<id>/view        {"status":"playing","state":<state>,"decision":<decision>}, the
+//                              last snapshot of the seat and the decision it has to make, or
+//                              null if it has none
+// POST /games/<id>/actions     the answer to the decision, an `action` or `keep` message of
+//                              `protocol.rs`: 202 if it is legal, 409 if there is no decision
+// GET  /games/<id>/events?since=<n>
+//                              {"events":[<message>],"next":<n>}, every message the seat was
+//                              sent from the nth on, in the JSON schema of `protocol.rs`
+//
+// Players are never lost, so the game waits for them as long as it takes unless the server
+// was started with a clock.
+
+/// The largest body read from a request.
+const MAX_BODY: usize = 16 * 1024;
+/// How long a client may take to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A player of a game and what their seat was sent.
+struct Player {
+    name: String,
+    token: String,
+    /// The connection of the seat, until the game takes it.
+    connection: Option<Connection>,
+    answers: Sender<String>,
+    messages: Receiver<Message>,
+    /// Every message the seat was sent.
+    log: Vec<Message>,
+    /// The last snapshot of the seat.
+    view: Option<Message>,
+    /// The decision the seat has to make, if any.
+    decision: Option<Message>,
+}
+
+impl Player {
+    /// Takes the messages the seat was sent since the last time.
+    fn update(&mut self) {
+        while let Ok(message) = self.messages.try_recv() {
+            match &message {
+                // There is no connection to come back with
+                Message::Session { .. } => continue,
+                Message::State(_) => self.view = Some(message.clone()),
+                Message::Decision { .. } => self.decision = Some(message.clone()),
+                Message::Timeout | Message::Quit => self.decision = None,
+                _ => {},
+            }
+            self.log.push(message);
+        }
+    }
+}
+
+struct Game {
+    id: usize,
+    players: usize,
+    start: StartPolicy,
+    seats: Vec<Player>,
+    playing: bool,
+    over: bool,
+    winner: Option<String>,
+}
+
+impl Game {
+    fn status(&self) -> &'static str {
+        match (self.playing, self.over) {
+            (_, true) => "over",
+            (true, false) => "playing",
+            (false, false) => "waiting",
+        }
+    }
+
+    fn info(&self) -> Json {
+        Json::object(vec![
+            ("id", self.id.into()),
+            ("players", self.players.into()),
+            ("start", self.start.name().into()),
+            ("names", Json::array(self.seats.iter().map(|player| player.name.as_str()))),
+            ("status", self.status().into()),
+            ("winner", self.winner.clone().into()),
+        ])
+    }
+
+    /// The player whose token `request` was sent with.
+    fn player(&mut self, request: &Request) -> Result<&mut Player, (u16, String)> {
+        let token = header(&request.headers, "Authorization").and_then(|value| value.strip_prefix("Bearer "));
+        let token = token.ok_or((401, "Send the token you got when you joined as Authorization: Bearer <token>.".to_string()))?;
+        self.seats.iter_mut().find(|player| player.token == token.trim()).ok_or((401, "Nobody plays this game with that token.".to_string()))
+    }
+}
+
+/// Every game of a server.
+#[derive(Default)]
+struct Games {
+    games: Vec<Game>,
+    next_game: usize,
+    /// Game `id` is dealt with `game_seed(seed, id)`.
+    seed: u64,
+    settings: Settings,
+    sessions: Sessions,
+}
+
+impl Games {
+    fn game(&mut self, id: &str) -> Result<&mut Game, (u16, String)> {
+        let id: Option<usize> = id.parse().ok();
+        self.games.iter_mut().find(|game| Some(game.id) == id).ok_or((404, "There is no such game.".to_string()))
+    }
+
+    /// The games still open or being played, as announced on the network.
+    fn listings(&self) -> Vec<Listing> {
+        let listing = |game: &Game| Listing { room: Some(game.id), players: game.players, seated: game.seats.len(), playing: game.playing, rules: rules(game.start, self.settings.clock) };
+        self.games.iter().filter(|game| !game.over).map(listing).collect()
+    }
+
+    fn create(&mut self, body: &Json) -> Result<&Game, (u16, String)> {
+        let players = match body.get("players") {
+            Some(players) => players.as_usize().ok_or((400, "The players must be a number.".to_string()))?,
+            None => 2,
+        };
+        if !(2..=MAX_PLAYERS).contains(&players) {
+            return Err((400, format!("Games seat 2 to {} players.", MAX_PLAYERS)));
+        }
+        let start = body.get("start").and_then(Json::as_str).unwrap_or("rotate");
+        let start = StartPolicy::from_name(start).ok_or((400, format!("\"{}\" is not a way to start rounds.", start)))?;
+        self.next_game += 1;
+        self.games.push(Game { id: self.next_game, players, start, seats: Vec::new(), playing: false, over: false, winner: None });
+        Ok(self.games.last().unwrap())
+    }
+}
+
+/// Seats `name` at game `id` and starts its game once every seat is taken.
+fn join(shared: &Arc<Mutex<Games>>, games: &mut Games, id: &str, body: &Json) -> Result<Json, (u16, String)> {
+    let name = body.get("name").and_then(Json::as_str).ok_or((400, "Give the name to play under as {\"name\":\"Ann\"}.".to_string()))?;
+    let name: Vec<&str> = name.split_whitespace().collect();
+    let game = games.game(id)?;
+    if game.seats.len() == game.players {
+        return Err((409, format!("Game {} is full.", game.id)));
+    }
+    let names: Vec<String> = game.seats.iter().map(|player| player.name.clone()).collect();
+    let name = unique_name(&name.join("_"), &names);
+    let token: String = (0..4).map(|_| format!("{:08x}", thread_rng().gen::<u32>())).collect();
+    let (connection, answers, messages) = Connection::channel();
+    game.seats.push(Player { name: name.clone(), token: token.clone(), connection: Some(connection), answers, messages, log: Vec::new(), view: None, decision: None });
+    if game.seats.len() == game.players {
+        let id = game.id;
+        start(shared, games, id);
+    }
+    Ok(Json::object(vec![("name", name.into()), ("token", token.into())]))
+}
+
+/// Plays the game `id` on a thread of its own.
+fn start(shared: &Arc<Mutex<Games>>, games: &mut Games, id: usize) {
+    let seed = game_seed(games.seed, id as u64);
+    let sessions = games.sessions.clone();
+    let mut settings = games.settings;
+    let Ok(game) = games.game(&id.to_string()) else { return };
+    game.playing = true;
+    settings.start = game.start;
+    let names: Vec<String> = game.seats.iter().map(|player| player.name.clone()).collect();
+    let connections: Vec<Connection> = game.seats.iter_mut().filter_map(|player| player.connection.take()).collect();
+    let shared = shared.clone();
+    println!("Game {} starts between {}.", id, names.join(", "));
+    thread::spawn(move || {
+        let (spectators, _) = Spectators::new(None);
+        let result = play_table(names.clone(), connections, settings, &sessions, spectators, &mut StdRng::seed_from_u64(seed), &mut |_, _| {});
+        let winner = result.winner.map(|winner| names[winner].clone());
+        println!("Game {}: {} won after {} rounds.", id, winner.as_deref().unwrap_or("nobody"), result.rounds);
+        if let Ok(game) = shared.lock().unwrap().game(&id.to_string()) {
+            game.over = true;
+            game.winner = winner;
+        }
+    });
+}
+
+/// Hands `answer`, as a line of the protocol, to the decision of the player if it is one of the
+/// legal ones.
+fn act(player: &mut Player, answer: String) -> Result<Json, (u16, String)> {
+    player.update();
+    let Some(Message::Decision { decision, legal }) = &player.decision else {
+        return Err((409, "You have no decision to make.".to_string()));
+    };
+    let legal = match decision {
+        Decision::Executioner { drawn, .. } => parse_keep(&answer, drawn.len()).is_some(),
+        _ => parse_play(&answer, legal).is_some(),
+    };
+    if !legal {
+        return Err((400, format!("\"{}\" is not one of the legal answers.", answer)));
+    }
+    player.answers.send(answer.clone()).map_err(|_| (409, "The game is over.".to_string()))?;
+    player.decision = None;
+    Ok(Json::object(vec![("answer", answer.into())]))
+}
+
+/// An HTTP request, with its path split at the slashes.
+struct Request {
+    method: String,
+    path: Vec<String>,
+    query: Vec<(String, String)>,
+    headers: Vec<String>,
+    body: String,
+}
+
+impl Request {
+    fn json(&self) -> Result<Json, (u16, String)> {
+        match self.body.trim() {
+            "" => Ok(Json::object(Vec::new())),
+            body => Json::parse(body).map_err(|error| (400, format!("The body is not JSON: {}", error))),
+        }
+    }
+
+    fn query(&self, name: &str) -> Option<&str> {
+        self.query.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+}
+
+fn read_request(stream: &mut TcpStream) -> Result<Request, String> {
+    let headers = read_headers(stream)?;
+    let words: Vec<&str> = headers.first().map_or(Vec::new(), |line| line.split_whitespace().collect());
+    let [method, target, _] = words[..] else {
+        return Err("Expected an HTTP request.".to_string());
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let length = header(&headers, "Content-Length").map_or(Ok(0), str::parse::<usize>).map_err(|_| "The length of the body is not a number.".to_string())?;
+    if length > MAX_BODY {
+        return Err("The body is too long.".to_string());
+    }
+    let mut body = vec![0u8; length];
+    stream.read_exact(&mut body).map_err(|error| format!("The body was cut short: {}", error))?;
+    Ok(Request {
+        method: method.to_string(),
+        path: path.split('/').filter(|part| !part.is_empty()).map(|part| part.to_string()).collect(),
+        query: query.split('&').filter_map(|pair| pair.split_once('=')).map(|(key, value)| (key.to_string(), value.to_string())).collect(),
+        headers: headers.clone(),
+        body: String::from_utf8(body).map_err(|_| "The body is not UTF-8.".to_string())?,
+    })
+}
+
+/// What to answer `request` with: a status and a body.
+fn route(shared: &Arc<Mutex<Games>>, request: &Request) -> Result<(u16, Json), (u16, String)> {
+    // The body is read before the games are locked, so a request cannot hold them up
+    let body = request.json();
+    let answer = read_answer(request.body.trim());
+    let mut games = shared.lock().unwrap();
+    let path: Vec<&str> = request.path.iter().map(String::as_str).collect();
+    match (request.method.as_str(), &path[..]) {
+        ("OPTIONS", _) => Ok((204, Json::Null)),
+        ("GET", ["games"]) => Ok((200, Json::object(vec![("games", Json::Array(games.games.iter().map(Game::info).collect()))]))),
+        ("POST", ["games"]) => games.create(&body?).map(|game| (201, game.info())),
+        ("GET", ["games", id]) => Ok((200, games.game(id)?.info())),
+        ("POST", ["games", id, "players"]) => join(shared, &mut games, id, &body?).map(|joined| (201, joined)),
+        ("GET", ["games", id, "view"]) => {
+            let game = games.game(id)?;
+            let status = game.status();
+            let player = game.player(request)?;
+            player.update();
+            let message = |message: &Option<Message>| message.as_ref().map_or(Json::Null, Message::json);
+            Ok((200, Json::object(vec![("status", status.into()), ("state", message(&player.view)), ("decision", message(&player.decision))])))
+        },
+        ("POST", ["games", id, "actions"]) => act(games.game(id)?.player(request)?, answer).map(|answer| (202, answer)),
+        ("GET", ["games", id, "events"]) => {
+            let player = games.game(id)?.player(request)?;
+            player.update();
+            let since = request.query("since").map_or(Ok(0), str::parse::<usize>).map_err(|_| (400, "Since must be a number.".to_string()))?;
+            let events = player.log.iter().skip(since).map(Message::json).collect();
+            Ok((200, Json::object(vec![("events", Json::Array(events)), ("next", player.log.len().into())])))
+        },
+        (_, ["games"] | ["games", _] | ["games", _, "players" | "view" | "actions" | "events"]) => Err((405, format!("{} is not allowed there.", request.method))),
+        _ => Err((404, "There is nothing there.".to_string())),
+    }
+}
+
+fn respond(stream: &mut TcpStream, status: u16, body: &Json) {
+    let reason = match status {
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Conflict",
+    };
+    let body = if status == 204 { String::new() } else { body.to_string() };
+    let cors = "Access-Control-Allow-Origin: *\r\nAccess-Control-Allow-Headers: Authorization, Content-Type\r\nAccess-Control-Allow-Methods: GET, POST, OPTIONS";
+    let response = format!("HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}\r\nConnection: close\r\n\r\n{}", status, reason, body.len(), cors, body);
+    let _ = stream.write_all(response.as_bytes()).and_then(|_| stream.flush());
+}
+
+/// Answers the requests of the API on `listener` until the server is stopped, each on a thread
+/// of its own, and plays every game on a thread of its own with `settings`. The games are
+/// announced on the network with `beacon`, if given.
+pub fn serve(listener: &TcpListener, settings: Settings, seed: u64, beacon: Option<Beacon>) -> Result<(), String> {
+    let shared = Arc::new(Mutex::new(Games { seed, settings, ..Games::default() }));
+    if let Some(beacon) = beacon {
+        let port = listener.local_addr().map_err(|error| error.to_string())?.port();
+        let games = shared.clone();
+        beacon.start(port, "http", move || Some(games.lock().unwrap().listings()))?;
+    }
+    loop {
+        let (mut stream, _) = listener.accept().map_err(|error| error.to_string())?;
+        let shared = shared.clone();
+        thread::spawn(move || {
+            let _ = stream.set_read_timeout(Some(REQUEST_TIMEOUT));
+            let reply = read_request(&mut stream).map_err(|error| (400, error)).and_then(|request| route(&shared, &request));
+            let (status, body) = reply.unwrap_or_else(|(status, message)| (status, Json::object(vec![("error", message.into())])));
+            respond(&mut stream, status, &body);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sends a request to the server on `port` and returns the status and body of its answer.
+    fn request(port: u16, method: &str, path: &str, token: Option<&str>, body: &str) -> (u16, Json) {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let authorization = token.map_or(String::new(), |token| format!("Authorization: Bearer {}\r\n", token));
+        write!(stream, "{} {} HTTP/1.1\r\nHost: localhost\r\n{}Content-Length: {}\r\n\r\n{}", method, path, authorization, body.len(), body).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+        (status, Json::parse(body).unwrap_or(Json::Null))
+    }
+
+    #[test]
+    fn test_rest_game() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || serve(&listener, Settings::default(), 3, None));
+
+        assert_eq!(request(port, "GET", "/games/1", None, "").0, 404);
+        assert_eq!(request(port, "POST", "/games", None, "{\"players\":9}").0, 400);
+        // A body nested too deeply is refused, and the server goes on
+        assert_eq!(request(port, "POST", "/games", None, &"[".repeat(MAX_BODY)).0, 400);
+        assert_eq!(request(port, "GET", "/games", None, "").0, 200);
+        let (status, game) = request(port, "POST", "/games", None, "{\"players\":2}");
+        assert_eq!((status, game.get("status")), (201, Some(&Json::from("waiting"))));
+        let tokens: Vec<String> = ["Ann", "Ann"].iter().map(|name| {
+            let (status, joined) = request(port, "POST", "/games/1/players", None, &format!("{{\"name\":\"{}\"}}", name));
+            assert_eq!(status, 201);
+            joined.get("token").and_then(Json::as_str).unwrap().to_string()
+        }).collect();
+        assert_eq!(request(port, "POST", "/games/1/players", None, "{\"name\":\"Cid\"}").0, 409);
+        assert_eq!(request(port, "GET", "/games/1/view", None, "").0, 401);
+        assert_eq!(request(port, "GET", "/games/1", None, "").1.get("names"), Some(&Json::array(["Ann", "Ann_2"])));
+
+        let mut refused = false;
+        while request(port, "GET", "/games/1", None, "").1.get("status") != Some(&Json::from("over")) {
+            let mut waiting = true;
+            for (player, token) in tokens.iter().enumerate() {
+                let decision = request(port, "GET", "/games/1/view", Some(token), "").1.get("decision").cloned().unwrap_or(Json::Null);
+                let answer = match decision.get("decision").and_then(Json::as_str) {
+                    Some("play") => {
+                        let Some(Json::Object(action)) = decision.get("legal").and_then(Json::as_array).map(|legal| legal[0].clone()) else { panic!() };
+                        Json::Object([vec![("type".to_string(), Json::from("action"))], action].concat()).to_string()
+                    },
+                    Some(_) => "{\"type\":\"keep\",\"keep\":0,\"hand_on_top\":true}".to_string(),
+                    None => continue,
+                };
+                if !refused {
+                    // Only the legal answers of the player to act are taken
+                    assert_eq!(request(port, "POST", "/games/1/actions", Some(token), "play 0:7").0, 400);
+                    assert_eq!(request(port, "POST", "/games/1/actions", Some(&tokens[1 - player]), &answer).0, 409);
+                    refused = true;
+                }
+                assert_eq!(request(port, "POST", "/games/1/actions", Some(token), &answer).0, 202);
+                waiting = false;
+            }
+            if waiting {
+                thread::sleep(Duration::from_millis(2));
+            }
+        }
+        assert!(refused);
+
+        let (status, events) = request(port, "GET", "/games/1/events?since=0", Some(&tokens[0]), "");
+        assert_eq!(status, 200);
+        let events = events.get("events").and_then(Json::as_array).unwrap().to_vec();
+        let kind = |event: &Json| event.get("type").and_then(Json::as_str).unwrap_or("").to_string();
+        assert_eq!(kind(&events[0]), "round");
+        assert_eq!(kind(events.last().unwrap()), "quit");
+        assert!(events.iter().any(|event| kind(event) == "winner"));
+        let (_, later) = request(port, "GET", &format!("/games/1/events?since={}", events.len()), Some(&tokens[0]), "");
+        assert_eq!(later.get("events"), Some(&Json::Array(Vec::new())));
+        assert_eq!(later.get("next"), Some(&Json::from(events.len())));
+    }
+}
@@ -0,0 +1,264 @@
+use std::collections::BTreeMap;
+
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use crate::agent::Agent;
+use crate::analytics::percent;
+use crate::belief::{compare, Knowledge};
+use crate::bots::HeuristicBot;
+use crate::card::{list_cards, Card};
+use crate::game::{is_protected, legal_actions, next_turn, play_card, play_tie_breaker, play_turn, Action, RoundStatus, State};
+use crate::options::Options;
+use crate::record::{load_rounds, positions, Position};
+use crate::solver::{hidden_hands, win_chances, ENDGAME_DECK, MAX_ASSIGNMENTS};
+
+/// How much lower a chance of winning the round than the best one makes a blunder.
+pub const BLUNDER: f64 = 0.15;
+
+/// Deals the cards the player to act has not seen at random, keeping what they hold and know.
+/// `None` if what they know does not add up.
+fn sample_deal(state: &State, knowledge: &Knowledge, hand: [Card; 2], rng: &mut StdRng) -> Option<State> {
+    let hidden = hidden_hands(state, knowledge);
+    let unseen = knowledge.unseen(state, &hand);
+    let mut cards: Vec<Card> = list_cards().into_iter().flat_map(|card| vec![card; unseen[card.value()]]).collect();
+    if cards.len() != hidden.len() + state.deck.len() + 1 {
+        return None;
+    }
+    cards.shuffle(rng);
+    let mut deal = state.clone();
+    for (seat, known) in knowledge.known.iter().enumerate() {
+        if known.is_some() && !state.out.contains(&seat) {
+            deal.hands[seat] = *known;
+        }
+    }
+    for (seat, revealed) in knowledge.revealed.iter().enumerate() {
+        if revealed.is_some() {
+            deal.hands[seat] = *revealed;
+        }
+    }
+    for seat in hidden {
+        deal.hands[seat] = cards.pop();
+    }
+    deal.deck = cards.split_off(1);
+    Some(deal)
+}
+
+/// Plays `action` for the player to act in `deal` and the rest of the round with heuristic
+/// bots. True if the player wins the round.
+fn rollout(mut deal: State, hand: [Card; 2], action: &Action, seed: u64) -> bool {
+    let me = deal.turn;
+    let mut bots: Vec<HeuristicBot> = (0..deal.players.len()).map(|seat| HeuristicBot::new(seed + seat as u64)).collect();
+    for (seat, bot) in bots.iter_mut().enumerate() {
+        bot.start_round(&deal, seat);
+    }
+    deal.hands[me] = Some(if action.card == hand[0] { hand[1] } else { hand[0] });
+    let mut events = Vec::new();
+    let (status, mut state) = play_card(deal, action, &mut bots[me], &mut events);
+    let mut status = status.unwrap_or(RoundStatus::Continue);
+    loop {
+        for event in &events {
+            for bot in bots.iter_mut() {
+                bot.observe(&state, event);
+            }
+        }
+        match status {
+            RoundStatus::Continue | RoundStatus::Skip => {},
+            RoundStatus::TieBreaker => {
+                state = play_tie_breaker(state);
+                break;
+            },
+            RoundStatus::End => break,
+        }
+        next_turn(&mut state);
+        let player = state.turn;
+        events.clear();
+        (status, state) = play_turn(state, &mut bots[player], &mut events);
+    }
+    !state.out.contains(&me)
+}
+
+/// The estimated chance of winning the round with each legal action in a recorded position:
+/// exact once the deck is small, otherwise over `samples` random deals of the unseen cards
+/// played out by heuristic bots.
+pub fn estimate(position: &Position, samples: usize, rng: &mut StdRng) -> Vec<(Action, f64)> {
+    let state = &position.state;
+    let hand = position.turn.hand;
+    if state.deck.len() <= ENDGAME_DECK {
+        if let Some(chances) = win_chances(state, &position.knowledge, hand, MAX_ASSIGNMENTS) {
+            return chances;
+        }
+    }
+    let legal = legal_actions(state, hand);
+    let mut wins = vec![0; legal.len()];
+    let mut deals = 0;
+    for _ in 0..samples {
+        let Some(deal) = sample_deal(state, &position.knowledge, hand, rng) else {
+            break;
+        };
+        let seed = rng.gen();
+        for (i, action) in legal.iter().enumerate() {
+            wins[i] += rollout(deal.clone(), hand, action, seed) as usize;
+        }
+        deals += 1;
+    }
+    legal.into_iter().zip(wins).map(|(action, wins)| (action, wins as f64 / deals.max(1) as f64)).collect()
+}
+
+/// What went wrong in one decision.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mistake {
+    /// The best play had a chance of winning the round this much higher.
+    Blunder { best: Action, loss: f64 },
+    /// They held a Guard and knew the card of a player they could have guessed.
+    MissedGuard { target: usize, card: Card },
+    /// They compared hands with the Tweedies while more likely to lose than to win.
+    RiskyTweedies { lose: f64 },
+}
+
+/// The mistakes of a decision that `estimate` gave `chances`, blunders being `blunder` or more
+/// below the best play.
+pub fn mistakes(position: &Position, chances: &[(Action, f64)], blunder: f64) -> Vec<Mistake> {
+    let state = &position.state;
+    let knowledge = &position.knowledge;
+    let hand = position.turn.hand;
+    let played = &position.turn.action;
+    let mut mistakes = Vec::new();
+
+    let chance = chances.iter().find(|(action, _)| action == played).map_or(0.0, |(_, chance)| *chance);
+    if let Some((best, best_chance)) = chances.iter().max_by(|a, b| a.1.total_cmp(&b.1)) {
+        if best_chance - chance >= blunder {
+            mistakes.push(Mistake::Blunder { best: *best, loss: best_chance - chance });
+        }
+    }
+
+    if hand.contains(&Card::Guard) {
+        let guessed = played.card == Card::Guard && played.guess.is_some() && played.guess == played.target.and_then(|target| knowledge.known[target]);
+        let missed = (0..state.players.len()).find(|seat| {
+            let known = knowledge.known[*seat];
+            *seat != state.turn && !state.out.contains(seat) && !is_protected(state, *seat) && known.is_some() && known != Some(Card::Guard)
+        });
+        if let (false, Some(target)) = (guessed, missed) {
+            mistakes.push(Mistake::MissedGuard { target, card: knowledge.known[target].unwrap() });
+        }
+    }
+
+    if let (Card::Tweedies, Some(target)) = (played.card, played.target) {
+        let kept = if hand[0] == Card::Tweedies { hand[1] } else { hand[0] };
+        let (win, lose) = compare(&knowledge.hand_distribution(state, &hand, target), &kept);
+        if lose > win {
+            mistakes.push(Mistake::RiskyTweedies { lose });
+        }
+    }
+    mistakes
+}
+
+/// What one player did over the reviewed rounds.
+#[derive(Debug, Default)]
+struct Report {
+    decisions: u64,
+    blunders: u64,
+    missed_guards: u64,
+    risky_tweedies: u64,
+    /// The summed chance of winning lost to worse plays.
+    lost: f64,
+    notes: Vec<String>,
+}
+
+/// `review`: goes through the decisions in `--record file` of every player, or only of
+/// `--players a,b`, and reports per player the blunders, missed Guard guesses and risky Tweedies.
+pub fn command(options: &Options) -> Result<(), String> {
+    let path = options.value("record").ok_or("Give the rounds to review with --record file.")?;
+    let rounds = load_rounds(path)?;
+    let only: Option<Vec<String>> = options.value("players").map(|players| players.split(',').map(|player| player.trim().to_string()).collect());
+    let samples: usize = options.get("samples", 100)?;
+    let blunder: f64 = options.get("blunder", BLUNDER)?;
+    let mut rng = StdRng::seed_from_u64(options.get("seed", 0)?);
+
+    let mut reports: BTreeMap<String, Report> = BTreeMap::new();
+    for (index, round) in rounds.iter().enumerate() {
+        for position in positions(round)? {
+            let state = &position.state;
+            let name = &state.players[position.turn.player];
+            if only.as_ref().is_some_and(|only| !only.contains(name)) {
+                continue;
+            }
+            let chances = estimate(&position, samples, &mut rng);
+            let found = mistakes(&position, &chances, blunder);
+            let report = reports.entry(name.clone()).or_default();
+            report.decisions += 1;
+            let chance = chances.iter().find(|(action, _)| *action == position.turn.action).map_or(0.0, |(_, chance)| *chance);
+            let best = chances.iter().map(|(_, chance)| *chance).fold(0.0, f64::max);
+            report.lost += best - chance;
+            for mistake in found {
+                let played = position.turn.action.describe(&state.players);
+                let note = match mistake {
+                    Mistake::Blunder { best, loss } => {
+                        report.blunders += 1;
+                        format!("played {} instead of {}, {:.0}% less likely to win the round", played, best.describe(&state.players), 100.0 * loss)
+                    },
+                    Mistake::MissedGuard { target, card } => {
+                        report.missed_guards += 1;
+                        format!("played {} while knowing {} holds {}", played, state.players[target], card.name())
+                    },
+                    Mistake::RiskyTweedies { lose } => {
+                        report.risky_tweedies += 1;
+                        format!("played {} with a {:.0}% chance of losing the comparison", played, 100.0 * lose)
+                    },
+                };
+                report.notes.push(format!("Round {}, {} cards left: {}.", index + 1, state.deck.len(), note));
+            }
+        }
+    }
+
+    for (name, report) in &reports {
+        println!("{}: {} decisions, {} blunders, {} missed Guard guesses, {} risky Tweedies, {} of a round lost per decision", name, report.decisions, report.blunders, report.missed_guards, report.risky_tweedies, percent((report.lost * 1000.0).round() as u64, report.decisions * 1000));
+        for note in &report.notes {
+            println!("\t{}", note);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::create_deck;
+    use crate::game::Event;
+    use crate::record::Turn;
+
+    #[test]
+    fn test_mistakes() {
+        let mut deck = create_deck();
+        for card in [Card::Guard, Card::Tweedies, Card::Time, Card::Dormouse] {
+            deck.remove(deck.iter().position(|other| *other == card).unwrap());
+        }
+        let state = State {
+            deck,
+            discard: vec![vec![], vec![]],
+            players: vec!["Ann".to_string(), "Bob".to_string()],
+            out: Vec::new(),
+            hands: vec![Some(Card::Guard), Some(Card::Time)],
+            tokens: vec![0, 0],
+            round: 1,
+            turn: 0,
+            dormouse: None,
+        };
+        let mut knowledge = Knowledge::new(&state, 0);
+        knowledge.observe(&state, &Event::Revealed { player: 0, target: 1, card: Card::Time });
+
+        // Ann compares her Guard with the Time she knows Bob holds instead of guessing it
+        let hand = [Card::Guard, Card::Tweedies];
+        let action = Action { card: Card::Tweedies, target: Some(1), guess: None };
+        let position = Position { state: state.clone(), knowledge, turn: Turn { player: 0, hand, action, executioner: None, events: Vec::new() } };
+        let chances = estimate(&position, 20, &mut StdRng::seed_from_u64(1));
+        assert_eq!(chances.len(), legal_actions(&state, hand).len());
+        let guess = Action { card: Card::Guard, target: Some(1), guess: Some(Card::Time) };
+        assert!(chances.iter().any(|(action, chance)| *action == guess && *chance == 1.0));
+
+        let found = mistakes(&position, &chances, BLUNDER);
+        assert_eq!(found[0], Mistake::Blunder { best: guess, loss: 1.0 });
+        assert!(found.contains(&Mistake::MissedGuard { target: 1, card: Card::Time }));
+        assert!(found.contains(&Mistake::RiskyTweedies { lose: 1.0 }));
+    }
+}
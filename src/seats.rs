@@ -0,0 +1,135 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::analytics::percent;
+use crate::options::Options;
+use crate::record::RoundRecord;
+use crate::sim::{game_seed, random_bots, simulate_game, BatchConfig, GameResult, Merge, StartPolicy};
+
+/// How much it matters where a player sits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeatStats {
+    pub rounds: u64,
+    /// Rounds won by the player in each position of the turn order, where 0 starts the round.
+    pub round_wins: Vec<u64>,
+    pub games: u64,
+    /// Games won by the player in each seat of the first round.
+    pub game_wins: Vec<u64>,
+}
+
+impl SeatStats {
+    pub fn new(players: usize) -> SeatStats {
+        SeatStats { rounds: 0, round_wins: vec![0; players], games: 0, game_wins: vec![0; players] }
+    }
+
+    pub fn add_round(&mut self, round: &RoundRecord) {
+        self.rounds += 1;
+        for winner in &round.winners {
+            self.round_wins[*winner] += 1;
+        }
+    }
+
+    pub fn add_game(&mut self, result: &GameResult) {
+        self.games += 1;
+        if let Some(winner) = result.winner {
+            self.game_wins[winner] += 1;
+        }
+    }
+
+    /// Prints one row of the round and game win rates per seat.
+    pub fn print_row(&self, label: &str) {
+        let mut row = format!("{:<10}", label);
+        for wins in &self.round_wins {
+            row += &format!("{:>8}", percent(*wins, self.rounds));
+        }
+        row += "  |";
+        for wins in &self.game_wins {
+            row += &format!("{:>8}", percent(*wins, self.games));
+        }
+        println!("{}", row);
+    }
+}
+
+impl Merge for SeatStats {
+    fn merge(&mut self, other: SeatStats) {
+        self.rounds += other.rounds;
+        self.games += other.games;
+        for seat in 0..self.round_wins.len() {
+            self.round_wins[seat] += other.round_wins[seat];
+            self.game_wins[seat] += other.game_wins[seat];
+        }
+    }
+}
+
+/// Simulates a batch of games with the given start policy.
+pub fn seat_stats(config: &BatchConfig, policy: StartPolicy) -> SeatStats {
+    config.run(|| SeatStats::new(config.players), |game, stats: &mut SeatStats| {
+        let mut rng = StdRng::seed_from_u64(game_seed(config.seed, game));
+        let agents = random_bots(config.players, &mut rng);
+        let result = simulate_game(agents, policy, &mut rng, &mut |round, _| stats.add_round(round));
+        stats.add_game(&result);
+    })
+}
+
+/// `seats`: compares the win rate of each seat for every player count (or `--players`) and
+/// start policy (or `--policy rotate|winner|loser|random`).
+pub fn command(options: &Options) -> Result<(), String> {
+    let config = BatchConfig::from_options(options)?;
+    let counts: Vec<usize> = if options.value("players").is_some() { vec![config.players] } else { (2..=6).collect() };
+    let policies = match options.value("policy") {
+        Some(name) => vec![StartPolicy::from_name(name).ok_or(format!("Unknown start policy \"{}\". Policies: rotate, winner, loser, random.", name))?],
+        None => StartPolicy::all(),
+    };
+
+    let mut results = Vec::new();
+    for players in &counts {
+        for policy in &policies {
+            results.push((*players, *policy, seat_stats(&config.with_players(*players), *policy)));
+        }
+    }
+
+    println!("\nRound wins by position in the turn order | game wins by seat in the first round.");
+    for players in &counts {
+        println!("\n{} players:", players);
+        let mut header = format!("{:<10}", "Policy");
+        for seat in 0..*players {
+            header += &format!("{:>8}", format!("#{}", seat + 1));
+        }
+        header += "  |";
+        for seat in 0..*players {
+            header += &format!("{:>8}", format!("#{}", seat + 1));
+        }
+        println!("{}", header);
+        for (_, policy, stats) in results.iter().filter(|(count, _, _)| count == players) {
+            stats.print_row(&policy.name());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policies_keep_turn_order() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for policy in StartPolicy::all() {
+            let agents = random_bots(4, &mut rng);
+            let mut last: Option<Vec<usize>> = None;
+            simulate_game(agents, policy, &mut rng, &mut |_, seats| {
+                // Every seating is a rotation of the first one
+                let start = seats.iter().position(|player| *player == 0).unwrap();
+                for (i, player) in seats.iter().enumerate() {
+                    assert_eq!(*player, (i + 4 - start) % 4);
+                }
+                if policy == StartPolicy::Rotate {
+                    if let Some(last) = &last {
+                        assert_eq!(seats[0], last[1]);
+                    }
+                }
+                last = Some(seats.to_vec());
+            });
+        }
+    }
+}
@@ -0,0 +1,837 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use crate::agent::Agent;
+use crate::belief::Knowledge;
+use crate::bots::HeuristicBot;
+use crate::card::Card;
+use crate::clock::{Clock, Timed};
+use crate::discovery::{default_name, rules, Beacon, Listing};
+use crate::engine::{parse_keep, parse_play};
+use crate::env::{observe, Decision};
+use crate::game::{Action, Event, ExecutionerChoice, State};
+use crate::lobby;
+use crate::options::Options;
+use crate::protocol::{read_answer, Message};
+use crate::rest;
+use crate::sim::{play_game, GameResult, RoundObserver, StartPolicy};
+use crate::spectate::{Spectators, Watched, Watchers};
+use crate::websocket::{self, WebSocket};
+
+// The protocol between the server and a player's client is the engine protocol of `engine.rs`
+// with names, round results and answers that may be corrected. The server owns the game, so a
+// client only ever gets its own cards, what its seat knows and the public information.
+//
+// server: loveletter 1
+// client: join <name>
+// server: welcome <name>               the name it got, made unique and without spaces
+//         wait <joined> <players>      every time a player joins, until the table is full
+//
+// When the game starts:
+// server: session <token>              what the client answers `loveletter` with to come back
+//
+// At the start of every round, after the `round` line of the engine protocol:
+// server: names <name of every seat>
+//
+// `info` lines and decisions are as in the engine protocol. An answer that is not one of the
+// legal ones is not a forfeit:
+// server: error <why>
+// followed by the whole decision again.
+//
+// On a server started with `--turn-time` or `--bank`, a decision is preceded by
+// server: clock <milliseconds left to decide> <milliseconds left in the bank, or ->
+// and a client that does not answer in time gets
+// server: timeout
+// while the default action of the server is played for it.
+//
+// At the end of every round:
+// server: result <tokens of every seat> winners <winning seats>
+//
+// When the game is over:
+// server: winner <name, or - if nobody won>
+//         quit
+//
+// A client that loses its connection may come back during the game:
+// server: loveletter 1
+// client: resume <token>
+// server: welcome <name>
+//         session <token>
+//         the `round` and `names` lines of the round, and the view of its seat
+// and the game goes on, with the decision it was asked again if it still has to make it. Until
+// it is back, it is waited for during the grace period of the server, after which a heuristic
+// bot plays its seat until it returns.
+//
+// Spectators may connect at any time to watch the game, as described in `spectate.rs`.
+//
+// Clients of a server started with `--websocket` speak the same protocol over a WebSocket, in
+// the JSON messages of `protocol.rs`, and those of a server started with `--http` play through
+// the HTTP API of `rest.rs`. Servers started with `--announce` can be found on the local network,
+// as described in `discovery.rs`.
+
+pub const PROTOCOL_VERSION: u32 = 1;
+pub const DEFAULT_PORT: u16 = 4444;
+
+/// How the clients of a server talk to it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transport {
+    /// The lines of the protocol over plain TCP.
+    Lines,
+    /// JSON messages over a WebSocket.
+    WebSocket,
+}
+
+impl Transport {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Transport::Lines => "lines",
+            Transport::WebSocket => "websocket",
+        }
+    }
+}
+
+enum Writer {
+    Lines(TcpStream),
+    WebSocket(WebSocket),
+    Channel(Sender<Message>),
+}
+
+/// A connection to a client. A thread reads its answers, in the form of the line protocol, so
+/// they can be waited for.
+pub struct Connection {
+    writer: Writer,
+    lines: Receiver<String>,
+    closed: bool,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream, transport: Transport) -> Result<Connection, String> {
+        let (sender, lines) = mpsc::channel();
+        let writer = match transport {
+            Transport::Lines => {
+                let reader = stream.try_clone().map_err(|error| error.to_string())?;
+                thread::spawn(move || {
+                    for line in BufReader::new(reader).lines() {
+                        let Ok(line) = line else { break };
+                        if sender.send(line).is_err() {
+                            break;
+                        }
+                    }
+                });
+                Writer::Lines(stream)
+            },
+            Transport::WebSocket => {
+                let socket = websocket::accept(stream)?;
+                let mut reader = socket.try_clone().map_err(|error| error.to_string())?;
+                thread::spawn(move || {
+                    while let Some(text) = reader.receive() {
+                        if sender.send(read_answer(&text)).is_err() {
+                            break;
+                        }
+                    }
+                });
+                Writer::WebSocket(socket)
+            },
+        };
+        Ok(Connection { writer, lines, closed: false })
+    }
+
+    /// A connection to a client in this process, such as a player of the HTTP API: it is sent
+    /// the messages of the receiver and answers with lines on the sender.
+    pub fn channel() -> (Connection, Sender<String>, Receiver<Message>) {
+        let (answers, lines) = mpsc::channel();
+        let (sender, messages) = mpsc::channel();
+        (Connection { writer: Writer::Channel(sender), lines, closed: false }, answers, messages)
+    }
+
+    /// Sends `messages`. False once the client is gone.
+    pub fn send(&mut self, messages: &[Message]) -> bool {
+        if !self.closed {
+            self.closed = match &mut self.writer {
+                Writer::Lines(stream) => {
+                    let text: String = messages.iter().flat_map(Message::lines).map(|line| format!("{}\n", line)).collect();
+                    stream.write_all(text.as_bytes()).and_then(|_| stream.flush()).is_err()
+                },
+                Writer::WebSocket(socket) => {
+                    let sent = messages.iter().all(|message| socket.send(&message.json().to_string()).is_ok());
+                    if messages.contains(&Message::Quit) {
+                        socket.close();
+                    }
+                    !sent
+                },
+                Writer::Channel(sender) => messages.iter().any(|message| sender.send(message.clone()).is_err()),
+            };
+        }
+        !self.closed
+    }
+
+    /// Waits for the next line of the client, or `None` once it is gone.
+    pub fn receive(&mut self) -> Option<String> {
+        if self.closed {
+            return None;
+        }
+        match self.lines.recv() {
+            Ok(line) => Some(line.trim().to_string()),
+            Err(_) => {
+                self.closed = true;
+                None
+            },
+        }
+    }
+
+    /// Waits at most `timeout` for the next line of the client.
+    pub fn receive_timeout(&mut self, timeout: Duration) -> Result<String, RecvTimeoutError> {
+        if self.closed {
+            return Err(RecvTimeoutError::Disconnected);
+        }
+        let line = self.lines.recv_timeout(timeout);
+        self.closed = line == Err(RecvTimeoutError::Disconnected);
+        line.map(|line| line.trim().to_string())
+    }
+
+    pub fn closed(&self) -> bool {
+        self.closed
+    }
+}
+
+/// How long to wait for a lost client before a bot plays its seat, unless set with `--grace`.
+pub const DEFAULT_GRACE: Duration = Duration::from_secs(30);
+/// How often a client that is connected is checked for having come back on a new connection.
+const RETURN_CHECK: Duration = Duration::from_millis(200);
+
+/// The seats of the running games by session token, to hand them the connections of clients
+/// that come back.
+#[derive(Clone, Default)]
+pub struct Sessions {
+    seats: Arc<Mutex<HashMap<String, Sender<Connection>>>>,
+}
+
+impl Sessions {
+    /// A new token and where its seat receives the connections coming back with it.
+    fn open(&self) -> (String, Receiver<Connection>) {
+        let token: String = (0..4).map(|_| format!("{:08x}", thread_rng().gen::<u32>())).collect();
+        let (sender, returns) = mpsc::channel();
+        self.seats.lock().unwrap().insert(token.clone(), sender);
+        (token, returns)
+    }
+
+    fn close(&self, token: &str) {
+        self.seats.lock().unwrap().remove(token);
+    }
+
+    /// Hands `connection` back to the seat of `token`, or tells the client why it cannot.
+    pub fn resume(&self, token: &str, mut connection: Connection) -> Result<(), String> {
+        let refused = match self.seats.lock().unwrap().get(token) {
+            Some(seat) => match seat.send(connection) {
+                Ok(()) => return Ok(()),
+                Err(error) => {
+                    connection = error.0;
+                    "That game is over."
+                },
+            },
+            None => "There is no game with that session.",
+        };
+        connection.send(&[Message::Error { message: refused.to_string() }, Message::Quit]);
+        Err(refused.to_string())
+    }
+}
+
+/// What a client answered to `loveletter`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Greeting {
+    /// A new player with the name they asked for, without spaces.
+    Join(String),
+    /// A player coming back to the seat of the session token.
+    Resume(String),
+    /// A spectator, who wants the omniscient view if true.
+    Watch(bool),
+}
+
+/// How the games of a server are played.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Settings {
+    pub start: StartPolicy,
+    /// How long to wait for a lost client before a bot plays its seat.
+    pub grace: Duration,
+    /// How many rounds after a round is over spectators are shown its hands, if at all.
+    pub omniscient: Option<usize>,
+    /// The time limits of the players, if any.
+    pub clock: Option<Clock>,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings { start: StartPolicy::Rotate, grace: DEFAULT_GRACE, omniscient: None, clock: None }
+    }
+}
+
+/// A seat played by a client over the network. While the client is gone, it waits for it to
+/// come back for the grace period and then lets a heuristic bot play for it.
+pub struct RemotePlayer {
+    pub name: String,
+    connection: Rc<RefCell<Connection>>,
+    knowledge: Option<Knowledge>,
+    /// The last state seen, to show a client that comes back where the round is.
+    state: Option<State>,
+    token: String,
+    returns: Receiver<Connection>,
+    /// When the client was lost, if it is gone.
+    lost: Option<Instant>,
+    grace: Duration,
+    bot: HeuristicBot,
+    /// When the decision being asked is due and what is left of the bank, at a timed table.
+    clock: Option<(Instant, Option<Duration>)>,
+    /// The hand of the last decision, to know which card the seat kept once its play is seen.
+    holding: Option<[Card; 2]>,
+}
+
+impl RemotePlayer {
+    fn send(&mut self, messages: &[Message]) {
+        if !self.connection.borrow_mut().send(messages) && self.lost.is_none() {
+            println!("{} lost the connection.", self.name);
+            self.lost = Some(Instant::now());
+        }
+    }
+
+    /// Takes the connection of the client if it came back, waiting at most `timeout` for it.
+    /// True if it did.
+    fn take_return(&mut self, timeout: Duration) -> bool {
+        let Ok(connection) = self.returns.recv_timeout(timeout) else {
+            return false;
+        };
+        println!("{} is back.", self.name);
+        *self.connection.borrow_mut() = connection;
+        self.lost = None;
+        let mut messages = vec![Message::Welcome { name: self.name.clone() }, Message::Session { token: self.token.clone() }];
+        if let (Some(state), Some(knowledge)) = (&self.state, &self.knowledge) {
+            let mut state = state.clone();
+            state.turn = knowledge.seat;
+            let seats: Vec<usize> = (0..state.players.len()).collect();
+            messages.push(Message::Round { round: state.round, seat: knowledge.seat, names: state.players.clone() });
+            messages.push(Message::State(observe(&state, knowledge, &seats, &Decision::Done)));
+        }
+        self.send(&messages);
+        true
+    }
+
+    /// The snapshot of what the seat sees and the decision it has to make.
+    fn request(&self, state: &State, decision: Decision, legal: &[Action]) -> Vec<Message> {
+        let knowledge = self.knowledge.clone().unwrap_or(Knowledge::new(state, state.turn));
+        let seats: Vec<usize> = (0..state.players.len()).collect();
+        vec![Message::State(observe(state, &knowledge, &seats, &decision)), Message::Decision { decision, legal: legal.to_vec() }]
+    }
+
+    /// Sends `request`, with the time left to answer it at a timed table.
+    fn send_request(&mut self, request: &[Message]) {
+        let mut messages = request.to_vec();
+        if let Some((deadline, bank)) = self.clock {
+            messages.insert(1, Message::Clock { left: deadline.saturating_duration_since(Instant::now()), bank });
+        }
+        self.send(&messages);
+    }
+
+    /// Sends `request` until the client answers something `parse` accepts, or `None` if the
+    /// client is gone for longer than the grace period or runs out of time.
+    fn ask<T>(&mut self, request: &[Message], parse: impl Fn(&str) -> Option<T>) -> Option<T> {
+        // Answers that came too late for an earlier decision
+        while self.connection.borrow_mut().receive_timeout(Duration::ZERO).is_ok() {}
+        self.send_request(request);
+        loop {
+            let left = self.clock.map(|(deadline, _)| deadline.saturating_duration_since(Instant::now()));
+            if left == Some(Duration::ZERO) {
+                return None;
+            }
+            if let Some(lost) = self.lost {
+                let grace = self.grace.saturating_sub(lost.elapsed());
+                if !self.take_return(left.map_or(grace, |left| left.min(grace))) {
+                    return None;
+                }
+                self.send_request(request);
+                continue;
+            }
+            let answer = self.connection.borrow_mut().receive_timeout(left.map_or(RETURN_CHECK, |left| left.min(RETURN_CHECK)));
+            match answer {
+                Ok(answer) => match parse(&answer) {
+                    Some(value) => return Some(value),
+                    None => {
+                        let message = format!("\"{}\" is not one of the legal answers", answer);
+                        self.send(&[Message::Error { message }]);
+                        self.send_request(request);
+                    },
+                },
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.take_return(Duration::ZERO) {
+                        self.send_request(request);
+                    }
+                },
+                Err(RecvTimeoutError::Disconnected) => {
+                    println!("{} lost the connection.", self.name);
+                    self.lost = Some(Instant::now());
+                },
+            }
+        }
+    }
+
+    /// The bot, knowing what the seat knows.
+    fn bot(&mut self, state: &State) -> &mut HeuristicBot {
+        self.bot.set_knowledge(self.knowledge.clone().unwrap_or(Knowledge::new(state, state.turn)));
+        &mut self.bot
+    }
+}
+
+impl Agent for RemotePlayer {
+    fn start_round(&mut self, state: &State, seat: usize) {
+        self.knowledge = Some(Knowledge::new(state, seat));
+        self.state = Some(state.clone());
+        self.send(&[Message::Round { round: state.round, seat, names: state.players.clone() }]);
+    }
+
+    fn choose_action(&mut self, state: &State, hand: [Card; 2], legal: &[Action]) -> Action {
+        let request = self.request(state, Decision::Play { hand }, legal);
+        let action = match self.ask(&request, |answer| parse_play(answer, legal)) {
+            Some(action) => action,
+            None => self.bot(state).choose_action(state, hand, legal),
+        };
+        self.clock = None;
+        self.holding = Some(hand);
+        action
+    }
+
+    fn choose_executioner(&mut self, state: &State, hand: Card, drawn: &[Card]) -> ExecutionerChoice {
+        let request = self.request(state, Decision::Executioner { hand, drawn: drawn.to_vec() }, &[]);
+        let choice = match self.ask(&request, |answer| parse_keep(answer, drawn.len())) {
+            Some(choice) => choice,
+            None => self.bot(state).choose_executioner(state, hand, drawn),
+        };
+        self.clock = None;
+        if let Some(knowledge) = self.knowledge.as_mut() {
+            knowledge.hand = Some(drawn[choice.keep]);
+        }
+        choice
+    }
+
+    fn observe(&mut self, state: &State, event: &Event) {
+        self.state = Some(state.clone());
+        if self.lost.is_some() {
+            self.take_return(Duration::ZERO);
+        }
+        let Some(knowledge) = self.knowledge.as_mut() else { return };
+        // The card the seat kept is the one it did not play, whoever chose the play
+        if let Some(hand) = self.holding.take().filter(|_| event.player() == knowledge.seat) {
+            knowledge.hand = Some(if event.card() == hand[0] { hand[1] } else { hand[0] });
+        }
+        knowledge.observe(state, event);
+        if let Some(text) = event.describe(&state.players, knowledge.seat) {
+            self.send(&[Message::Event { text }]);
+        }
+    }
+
+    fn start_clock(&mut self, left: Duration, bank: Option<Duration>) {
+        self.clock = Instant::now().checked_add(left).map(|deadline| (deadline, bank));
+    }
+
+    fn out_of_time(&mut self, _state: &State) {
+        self.send(&[Message::Timeout]);
+    }
+
+    fn end_round(&mut self, state: &State, winners: &[usize]) {
+        self.send(&[Message::RoundResult { tokens: state.tokens.clone(), winners: winners.to_vec() }]);
+    }
+}
+
+/// Greets a new client and reads whether it joins or comes back.
+pub fn read_greeting(connection: &mut Connection) -> Result<Greeting, String> {
+    connection.send(&[Message::Hello]);
+    let answer = connection.receive().ok_or("The client left before joining.")?;
+    let words: Vec<&str> = answer.split_whitespace().collect();
+    match words[..] {
+        ["join", ..] => Ok(Greeting::Join(words[1..].join("_"))),
+        ["resume", token] => Ok(Greeting::Resume(token.to_string())),
+        ["watch"] => Ok(Greeting::Watch(false)),
+        ["watch", "all"] => Ok(Greeting::Watch(true)),
+        _ => Err(format!("Expected join, resume or watch, got \"{}\".", answer)),
+    }
+}
+
+/// `name`, numbered if it is among `taken`.
+pub fn unique_name(name: &str, taken: &[String]) -> String {
+    let mut unique = name.to_string();
+    let mut number = 1;
+    while unique.is_empty() || taken.contains(&unique) {
+        number += 1;
+        unique = format!("{}_{}", name, number);
+    }
+    unique
+}
+
+/// A new player with the name they asked for, where they joined from and their connection.
+type Joined = (String, SocketAddr, Connection);
+
+/// Greets a new client from `address`. New players are sent to `joined` while the table is not
+/// full, clients that come back are handed to their seat and spectators to `watchers`.
+fn greet(stream: TcpStream, address: SocketAddr, transport: Transport, joined: &Sender<Joined>, sessions: &Sessions, watchers: &Watchers) -> Result<(), String> {
+    let mut connection = Connection::new(stream, transport)?;
+    let refused = match read_greeting(&mut connection)? {
+        Greeting::Join(name) => match joined.send((name, address, connection)) {
+            Ok(()) => return Ok(()),
+            Err(error) => {
+                connection = error.0 .2;
+                "The game has already started."
+            },
+        },
+        Greeting::Resume(token) => return sessions.resume(&token, connection),
+        Greeting::Watch(omniscient) => {
+            watchers.admit(connection, omniscient)?;
+            println!("A spectator is watching from {}.", address);
+            return Ok(());
+        },
+    };
+    connection.send(&[Message::Error { message: refused.to_string() }, Message::Quit]);
+    Err(refused.to_string())
+}
+
+/// Plays a game between the clients of `connections`, `names[i]` being the name of the client
+/// of `connections[i]`, shows it to `spectators` and tells everyone who won. Clients that are
+/// lost may come back through `sessions`, and are played by a bot after the grace period.
+pub fn play_table(names: Vec<String>, connections: Vec<Connection>, settings: Settings, sessions: &Sessions, spectators: Spectators, rng: &mut StdRng, observe: &mut RoundObserver) -> GameResult {
+    let connections: Vec<Rc<RefCell<Connection>>> = connections.into_iter().map(|connection| Rc::new(RefCell::new(connection))).collect();
+    let spectators = Rc::new(RefCell::new(spectators));
+    let mut tokens = Vec::new();
+    let agents: Vec<Box<dyn Agent>> = names.iter().zip(connections.iter()).enumerate().map(|(seat, (name, connection))| {
+        let (token, returns) = sessions.open();
+        connection.borrow_mut().send(&[Message::Session { token: token.clone() }]);
+        tokens.push(token.clone());
+        let bot = HeuristicBot::new(rng.gen());
+        let player = Box::new(RemotePlayer { name: name.clone(), connection: connection.clone(), knowledge: None, state: None, token, returns, lost: None, grace: settings.grace, bot, clock: None, holding: None });
+        let player: Box<dyn Agent> = match settings.clock {
+            Some(clock) => Box::new(Timed::new(player, clock)),
+            None => player,
+        };
+        match seat {
+            0 => Box::new(Watched { agent: player, spectators: spectators.clone() }),
+            _ => player,
+        }
+    }).collect();
+    let result = play_game(names.clone(), agents, settings.start, rng, observe);
+    for token in &tokens {
+        sessions.close(token);
+    }
+    let winner = result.winner.map(|winner| names[winner].clone());
+    for connection in &connections {
+        connection.borrow_mut().send(&[Message::Winner { name: winner.clone() }, Message::Quit]);
+    }
+    spectators.borrow_mut().finish(winner);
+    result
+}
+
+/// Waits until `players` clients have joined on `listener` and plays a game between them, which
+/// spectators may watch. The table is announced on the network with `beacon`, if given.
+pub fn host(listener: &TcpListener, players: usize, transport: Transport, settings: Settings, beacon: Option<Beacon>, rng: &mut StdRng) -> Result<GameResult, String> {
+    let (spectators, watchers) = Spectators::new(settings.omniscient);
+    let listing = Arc::new(Mutex::new(Some(Listing { room: None, players, seated: 0, playing: false, rules: rules(settings.start, settings.clock) })));
+    if let Some(beacon) = beacon {
+        let port = listener.local_addr().map_err(|error| error.to_string())?.port();
+        let listing = listing.clone();
+        beacon.start(port, transport.name(), move || listing.lock().unwrap().clone().map(|listing| vec![listing]))?;
+    }
+
+    // Every client is greeted on a thread of its own, so one that says nothing holds up nobody
+    // else. Clients coming back and spectators keep connecting during the game.
+    let sessions = Sessions::default();
+    let (joined, joining) = mpsc::channel();
+    let accepting = listener.try_clone().map_err(|error| error.to_string())?;
+    let seats = sessions.clone();
+    thread::spawn(move || {
+        for stream in accepting.incoming().flatten() {
+            let Ok(address) = stream.peer_addr() else { continue };
+            let (joined, seats, watchers) = (joined.clone(), seats.clone(), watchers.clone());
+            thread::spawn(move || {
+                if let Err(error) = greet(stream, address, transport, &joined, &seats, &watchers) {
+                    println!("{} could not join: {}", address, error);
+                }
+            });
+        }
+    });
+
+    let mut names: Vec<String> = Vec::new();
+    let mut connections: Vec<Connection> = Vec::new();
+    while names.len() < players {
+        let (name, address, mut connection) = joining.recv().map_err(|error| error.to_string())?;
+        let name = unique_name(&name, &names);
+        connection.send(&[Message::Welcome { name: name.clone() }]);
+        println!("{} joined from {}.", name, address);
+        names.push(name);
+        connections.push(connection);
+        if let Some(listing) = listing.lock().unwrap().as_mut() {
+            listing.seated = names.len();
+            listing.playing = names.len() == players;
+        }
+        for connection in &mut connections {
+            connection.send(&[Message::Wait { joined: names.len(), players }]);
+        }
+    }
+
+    // Players who joined as the table filled up are turned away
+    for (_, _, mut connection) in joining.try_iter() {
+        connection.send(&[Message::Error { message: "The game has already started.".to_string() }, Message::Quit]);
+    }
+    drop(joining);
+
+    let result = play_table(names, connections, settings, &sessions, spectators, rng, &mut |round, _| {
+        let winners: Vec<String> = round.winners.iter().map(|winner| round.end.players[*winner].clone()).collect();
+        println!("Round {} won by {}.", round.end.round, winners.join(", "));
+    });
+    *listing.lock().unwrap() = None;
+    Ok(result)
+}
+
+/// `serve`: waits for `--players` clients on `--port` and plays a game between them, over
+/// WebSockets with `--websocket`. With `--lobby` it hosts rooms the players open themselves, and
+/// with `--http` games played through the HTTP API.
+/// Lost clients are waited for `--grace` seconds before a bot plays for them. Spectators are
+/// shown the hands of a round `--omniscient` rounds after it is over if it is given. Players are timed with
+/// `--turn-time` and `--bank`, as in `clock.rs`. With `--announce` the games are announced on
+/// the local network under `--server-name`, as in `discovery.rs`.
+pub fn command(options: &Options) -> Result<(), String> {
+    let port: u16 = options.get("port", DEFAULT_PORT)?;
+    let transport = if options.flag("websocket") { Transport::WebSocket } else { Transport::Lines };
+    let grace = Duration::from_secs(options.get("grace", DEFAULT_GRACE.as_secs())?);
+    let omniscient = match options.value("omniscient") {
+        Some(_) => Some(options.get("omniscient", 0)?),
+        None => None,
+    };
+    let settings = Settings { grace, omniscient, clock: Clock::from_options(options)?, ..Settings::default() };
+    let beacon = options.flag("announce").then(|| Beacon::broadcast(options.value("server-name").map_or(default_name(), |name| name.to_string()).as_str()));
+    if options.flag("lobby") || options.flag("http") {
+        let seed = match options.value("seed") {
+            Some(_) => options.get("seed", 0)?,
+            None => random(),
+        };
+        let listener = TcpListener::bind(("0.0.0.0", port)).map_err(|error| format!("Could not listen on port {}: {}", port, error))?;
+        if options.flag("http") {
+            println!("HTTP API on port {}.", port);
+            return rest::serve(&listener, settings, seed, beacon);
+        }
+        println!("Lobby open on port {}.", port);
+        return lobby::serve(&listener, transport, settings, seed, beacon);
+    }
+    let players: usize = options.get("players", 2)?;
+    if !(2..=6).contains(&players) {
+        return Err(format!("{} is not a valid number of players. Must be between 2 and 6.", players));
+    }
+    let mut rng = match options.value("seed") {
+        Some(_) => StdRng::seed_from_u64(options.get("seed", 0)?),
+        None => StdRng::from_entropy(),
+    };
+    let listener = TcpListener::bind(("0.0.0.0", port)).map_err(|error| format!("Could not listen on port {}: {}", port, error))?;
+    println!("Waiting for {} players on port {}.", players, port);
+    let result = host(&listener, players, transport, settings, beacon, &mut rng)?;
+    match result.forfeit {
+        Some(_) => println!("The game ended early after {} rounds.", result.rounds),
+        None => println!("The game is over after {} rounds.", result.rounds),
+    }
+    Ok(())
+}
+
+/// A client to test servers with.
+#[cfg(test)]
+pub mod testing {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpStream;
+
+    /// What a scripted client does after a line, instead of what it does by default.
+    pub enum Answer {
+        Send(String),
+        Nothing,
+        HangUp,
+    }
+
+    /// Connects to `port`, joins as `name` and plays the first legal action, unless `answer`
+    /// answers a line another way. Returns every line the server sent, up to `quit` or until
+    /// `answer` hangs up.
+    pub fn scripted_client(port: u16, name: &str, mut answer: impl FnMut(&str) -> Option<Answer>) -> Vec<String> {
+        let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let mut writer = stream.try_clone().unwrap();
+        let mut received = Vec::new();
+        let mut first = String::new();
+        for line in BufReader::new(stream).lines() {
+            let line = line.unwrap();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if let ["legal", action, ..] = words[..] {
+                first = action.to_string();
+            }
+            let reply = answer(&line).unwrap_or_else(|| match words[..] {
+                ["loveletter", _] => Answer::Send(format!("join {}", name)),
+                ["go", "play"] => Answer::Send(format!("play {}", first)),
+                ["go", "keep"] => Answer::Send("keep 0 top".to_string()),
+                _ => Answer::Nothing,
+            });
+            let quit = line == "quit";
+            received.push(line);
+            match reply {
+                Answer::Send(text) => writeln!(writer, "{}", text).unwrap(),
+                Answer::Nothing => {},
+                Answer::HangUp => break,
+            }
+            if quit {
+                break;
+            }
+        }
+        received
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{scripted_client, Answer};
+    use super::*;
+    use crate::clock::TimeoutAction;
+    use crate::json::Json;
+
+    /// Joins as `name` and plays the first legal action, after one illegal answer. Returns
+    /// every line the server sent.
+    fn first_legal_client(port: u16, name: &str) -> Vec<String> {
+        let mut tried = false;
+        scripted_client(port, name, |line| (line == "go play" && !tried).then(|| {
+            tried = true;
+            Answer::Send("play 0:7".to_string())
+        }))
+    }
+
+    /// Joins over a WebSocket as `name` and plays the first legal action. Returns every message
+    /// the server sent.
+    fn first_legal_websocket_client(port: u16, name: &str) -> Vec<Json> {
+        let mut socket = websocket::connect(TcpStream::connect(("127.0.0.1", port)).unwrap(), "localhost", "/").unwrap();
+        let mut received = Vec::new();
+        while let Some(text) = socket.receive() {
+            let message = Json::parse(&text).unwrap();
+            let answer = match (message.get("type").and_then(Json::as_str).unwrap(), message.get("decision").and_then(Json::as_str)) {
+                ("hello", _) => Some(Json::object(vec![("type", "join".into()), ("name", name.into())])),
+                ("decision", Some("play")) => {
+                    let Some(Json::Object(fields)) = message.get("legal").and_then(Json::as_array).map(|legal| legal[0].clone()) else { panic!() };
+                    Some(Json::Object([("type".to_string(), "action".into())].into_iter().chain(fields).collect()))
+                },
+                ("decision", Some("keep")) => Some(Json::object(vec![("type", "keep".into()), ("keep", 0usize.into()), ("hand_on_top", true.into())])),
+                _ => None,
+            };
+            received.push(message);
+            if let Some(answer) = answer {
+                socket.send(&answer.to_string()).unwrap();
+            }
+        }
+        received
+    }
+
+    #[test]
+    fn test_host_websocket() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let clients: Vec<_> = ["Ann", "Bob"].iter().map(|name| thread::spawn(move || first_legal_websocket_client(port, name))).collect();
+        let result = host(&listener, 2, Transport::WebSocket, Settings::default(), None, &mut StdRng::seed_from_u64(2)).unwrap();
+        assert_eq!(result.forfeit, None);
+
+        for client in clients {
+            let types: Vec<String> = client.join().unwrap().iter().map(|message| message.get("type").and_then(Json::as_str).unwrap().to_string()).collect();
+            for expected in ["hello", "welcome", "round", "state", "decision", "event", "result", "winner", "quit"] {
+                assert!(types.iter().any(|found| found == expected), "{}", expected);
+            }
+            assert!(!types.iter().any(|found| found == "error"));
+        }
+    }
+
+    /// Joins as `name` and plays the first legal action, except that it drops the connection when
+    /// asked to play for the first time, and comes back with its session if `back`. Returns
+    /// every line the server sent after it came back.
+    fn leaving_client(port: u16, name: &str, back: bool) -> Vec<String> {
+        let mut token = String::new();
+        let received = scripted_client(port, name, |line| match line.split_whitespace().collect::<Vec<&str>>()[..] {
+            ["session", session] => {
+                token = session.to_string();
+                None
+            },
+            ["go", "play"] => Some(Answer::HangUp),
+            _ => None,
+        });
+        if !back {
+            return received;
+        }
+        scripted_client(port, name, |line| line.starts_with("loveletter").then(|| Answer::Send(format!("resume {}", token))))
+    }
+
+    #[test]
+    fn test_reconnect() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let ann = thread::spawn(move || leaving_client(port, "Ann", true));
+        thread::sleep(Duration::from_millis(100));
+        let bob = thread::spawn(move || leaving_client(port, "Bob", false));
+        let result = host(&listener, 2, Transport::Lines, Settings { grace: Duration::from_millis(500), ..Settings::default() }, None, &mut StdRng::seed_from_u64(3)).unwrap();
+        assert_eq!(result.forfeit, None);
+        bob.join().unwrap();
+
+        // Ann came back to her seat and played on against the bot that took over Bob's
+        let received = ann.join().unwrap();
+        assert_eq!(received[..2], ["loveletter 1", "welcome Ann"]);
+        assert!(received.iter().any(|line| line.starts_with("view")));
+        assert!(received.iter().any(|line| line.starts_with("winner")));
+    }
+
+    /// Joins as `name` and plays the first legal action, except that it lets its first decision
+    /// run out of time. Returns every line the server sent.
+    fn slow_client(port: u16, name: &str) -> Vec<String> {
+        let mut timed_out = false;
+        scripted_client(port, name, |line| {
+            timed_out |= line == "timeout";
+            (line.starts_with("go") && !timed_out).then_some(Answer::Nothing)
+        })
+    }
+
+    #[test]
+    fn test_clock() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let clients: Vec<_> = ["Ann", "Bob"].iter().map(|name| thread::spawn(move || slow_client(port, name))).collect();
+        let clock = Clock { decision: Some(Duration::from_millis(200)), bank: Some(Duration::from_secs(60)), timeout: TimeoutAction::Lower };
+        let result = host(&listener, 2, Transport::Lines, Settings { clock: Some(clock), ..Settings::default() }, None, &mut StdRng::seed_from_u64(4)).unwrap();
+        assert!(result.winner.is_some());
+
+        for client in clients {
+            let received = client.join().unwrap();
+            let clock = received.iter().position(|line| line.starts_with("clock")).unwrap();
+            assert!(received[clock + 1].starts_with("hand"));
+            assert!(received.iter().any(|line| line == "timeout"));
+            assert!(!received.iter().any(|line| line.starts_with("error")));
+        }
+    }
+
+    #[test]
+    fn test_host() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // A client that never answers does not keep the others from joining
+        let _silent = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let clients: Vec<_> = ["Ann", "Ann"].iter().map(|name| thread::spawn(move || first_legal_client(port, name))).collect();
+        let result = host(&listener, 2, Transport::Lines, Settings::default(), None, &mut StdRng::seed_from_u64(1)).unwrap();
+        assert_eq!(result.forfeit, None);
+        assert!(result.winner.is_some());
+
+        let received: Vec<Vec<String>> = clients.into_iter().map(|client| client.join().unwrap()).collect();
+        let welcomes: Vec<&String> = received.iter().flatten().filter(|line| line.starts_with("welcome")).collect();
+        assert_eq!(welcomes.len(), 2);
+        assert_ne!(welcomes[0], welcomes[1]);
+        for lines in &received {
+            assert!(lines.iter().any(|line| line.starts_with("error")));
+            assert!(lines.iter().any(|line| line.starts_with("result")));
+            assert!(lines.iter().any(|line| line.starts_with("winner")));
+        }
+    }
+}
@@ -0,0 +1,362 @@
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use crate::agent::Agent;
+use crate::bots::RandomBot;
+use crate::game::{deal, deal_next_round};
+use crate::options::Options;
+use crate::record::{play_round, RoundRecord};
+
+/// Rounds after which a simulated game is abandoned without a winner.
+pub const MAX_ROUNDS: u32 = 100;
+
+/// Tokens needed to win a game with the given number of players.
+pub fn tokens_to_win(players: usize) -> i32 {
+    match players {
+        2 => 6,
+        3 => 5,
+        4 => 4,
+        _ => 3,
+    }
+}
+
+/// The seed of game number `game` in a batch, independent of which thread plays it.
+pub fn game_seed(seed: u64, game: u64) -> u64 {
+    // SplitMix64
+    let mut z = seed.wrapping_add(game.wrapping_add(1).wrapping_mul(0x9E3779B97F4A7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+/// Who begins the next round of a simulated game.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StartPolicy {
+    /// The second player of the last round starts, like `setup` does.
+    Rotate,
+    /// The winner of the last round starts. The first one in turn order if there were several.
+    WinnerStarts,
+    /// The first player knocked out of the last round starts.
+    LoserStarts,
+    Random,
+}
+
+impl StartPolicy {
+    pub fn all() -> Vec<StartPolicy> {
+        vec![StartPolicy::Rotate, StartPolicy::WinnerStarts, StartPolicy::LoserStarts, StartPolicy::Random]
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            StartPolicy::Rotate => "rotate".to_string(),
+            StartPolicy::WinnerStarts => "winner".to_string(),
+            StartPolicy::LoserStarts => "loser".to_string(),
+            StartPolicy::Random => "random".to_string(),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<StartPolicy> {
+        StartPolicy::all().into_iter().find(|policy| policy.name() == name)
+    }
+
+    /// The seat of `round` that starts the next round.
+    pub fn next_start(&self, round: &RoundRecord, rng: &mut StdRng) -> usize {
+        let players = round.end.players.len();
+        match self {
+            StartPolicy::Rotate => 1,
+            StartPolicy::WinnerStarts => round.winners[0],
+            StartPolicy::LoserStarts => {
+                let first_out = round.turns.iter().flat_map(|turn| turn.events.iter().filter_map(|event| event.eliminated())).next();
+                first_out.unwrap_or_else(|| (0..players).find(|player| !round.winners.contains(player)).unwrap_or(0))
+            },
+            StartPolicy::Random => rng.gen_range(0..players),
+        }
+    }
+}
+
+/// Called with every simulated round and the seat every player of the round had in the first round.
+pub type RoundObserver<'a> = dyn FnMut(&RoundRecord, &[usize]) + 'a;
+
+/// The outcome of one simulated game, indexed by the players' seats in the first round.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameResult {
+    pub winner: Option<usize>,
+    pub tokens: Vec<i32>,
+    pub rounds: u32,
+    /// The player who forfeited the game, who loses it whatever their tokens.
+    pub forfeit: Option<usize>,
+}
+
+/// Plays a whole game between `agents`, rotating the seats after every round according to `policy`.
+pub fn simulate_game(agents: Vec<Box<dyn Agent>>, policy: StartPolicy, rng: &mut StdRng, observe: &mut RoundObserver) -> GameResult {
+    let names: Vec<String> = (0..agents.len()).map(|i| format!("Bot {}", i + 1)).collect();
+    play_game(names, agents, policy, rng, observe)
+}
+
+/// Like `simulate_game`, with `names[i]` the name of `agents[i]`.
+pub fn play_game(names: Vec<String>, mut agents: Vec<Box<dyn Agent>>, policy: StartPolicy, rng: &mut StdRng, observe: &mut RoundObserver) -> GameResult {
+    let players = agents.len();
+    let target = tokens_to_win(players);
+    let mut seats: Vec<usize> = (0..players).collect();
+    let mut state = deal(names, vec![0; players], 1, rng);
+    let mut rounds = 0;
+    loop {
+        let round = play_round(state, &mut agents);
+        let finished = &round.end;
+        rounds += 1;
+        observe(&round, &seats);
+
+        let mut tokens = vec![0; players];
+        for (seat, player) in seats.iter().enumerate() {
+            tokens[*player] = finished.tokens[seat];
+        }
+        // A forfeit ends the game, the one with the most tokens among the others wins
+        let forfeit = agents.iter().position(|agent| agent.forfeited().is_some()).map(|seat| seats[seat]);
+        let best = (0..players).filter(|player| Some(*player) != forfeit).map(|player| tokens[player]).max().unwrap();
+        let leaders: Vec<usize> = (0..players).filter(|player| tokens[*player] == best && Some(*player) != forfeit).collect();
+        if (best >= target && leaders.len() == 1) || rounds >= MAX_ROUNDS || forfeit.is_some() {
+            let winner = if leaders.len() == 1 { Some(leaders[0]) } else { None };
+            return GameResult { winner, tokens, rounds, forfeit };
+        }
+
+        // Rotate the players
+        let start = policy.next_start(&round, rng);
+        seats.rotate_left(start);
+        agents.rotate_left(start);
+        state = deal_next_round(finished, start, rng);
+    }
+}
+
+/// Partial results of a batch that can be combined in any order.
+pub trait Merge {
+    fn merge(&mut self, other: Self);
+}
+
+/// Plays games `0..games` spread over `threads` threads, stopping early once `stop` is set.
+/// `play` is given the game number and the tally of the thread that plays it, and `progress`
+/// is called regularly with the number of finished games. Results only depend on which games
+/// finished, never on the number of threads.
+pub fn run_batch<S, N, P>(games: u64, threads: usize, stop: &AtomicBool, progress: &dyn Fn(u64), new: N, play: P) -> S
+where
+    S: Merge + Send,
+    N: Fn() -> S + Sync,
+    P: Fn(u64, &mut S) + Sync,
+{
+    let next = AtomicU64::new(0);
+    let finished = AtomicU64::new(0);
+    let working = AtomicU64::new(threads.max(1) as u64);
+    thread::scope(|scope| {
+        let mut handles = Vec::new();
+        for _ in 0..threads.max(1) {
+            handles.push(scope.spawn(|| {
+                let mut tally = new();
+                while !stop.load(Ordering::Relaxed) {
+                    let game = next.fetch_add(1, Ordering::Relaxed);
+                    if game >= games {
+                        break;
+                    }
+                    play(game, &mut tally);
+                    finished.fetch_add(1, Ordering::Relaxed);
+                }
+                working.fetch_sub(1, Ordering::Relaxed);
+                tally
+            }));
+        }
+
+        while working.load(Ordering::Relaxed) > 0 {
+            progress(finished.load(Ordering::Relaxed));
+            thread::sleep(Duration::from_millis(100));
+        }
+        progress(finished.load(Ordering::Relaxed));
+
+        let mut total = new();
+        for handle in handles {
+            total.merge(handle.join().unwrap());
+        }
+        total
+    })
+}
+
+/// Creates the bots for one game, each with its own seed drawn from the game's generator.
+pub fn random_bots(players: usize, rng: &mut StdRng) -> Vec<Box<dyn Agent>> {
+    (0..players).map(|_| Box::new(RandomBot::new(rng.gen())) as Box<dyn Agent>).collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    pub games: u64,
+    pub rounds: u64,
+    pub unfinished: u64,
+    pub wins: Vec<u64>,
+    pub tokens: Vec<u64>,
+}
+
+impl Stats {
+    pub fn new(players: usize) -> Stats {
+        Stats { games: 0, rounds: 0, unfinished: 0, wins: vec![0; players], tokens: vec![0; players] }
+    }
+
+    pub fn add(&mut self, result: &GameResult) {
+        self.games += 1;
+        self.rounds += result.rounds as u64;
+        match result.winner {
+            Some(winner) => self.wins[winner] += 1,
+            None => self.unfinished += 1,
+        }
+        for (player, tokens) in result.tokens.iter().enumerate() {
+            self.tokens[player] += *tokens as u64;
+        }
+    }
+
+    pub fn print(&self) {
+        println!("Games: {}", self.games);
+        if self.games == 0 {
+            return;
+        }
+        println!("Average rounds per game: {:.2}", self.rounds as f64 / self.games as f64);
+        println!("Games without a winner: {}", self.unfinished);
+        println!("Seat in first round:");
+        for player in 0..self.wins.len() {
+            println!("\t{}. {:.2}% wins, {:.2} tokens per game", player + 1, 100.0 * self.wins[player] as f64 / self.games as f64, self.tokens[player] as f64 / self.games as f64);
+        }
+    }
+}
+
+impl Merge for Stats {
+    fn merge(&mut self, other: Stats) {
+        self.games += other.games;
+        self.rounds += other.rounds;
+        self.unfinished += other.unfinished;
+        for player in 0..self.wins.len() {
+            self.wins[player] += other.wins[player];
+            self.tokens[player] += other.tokens[player];
+        }
+    }
+}
+
+/// Common settings of every command that runs a batch of games.
+#[derive(Clone)]
+pub struct BatchConfig {
+    pub players: usize,
+    pub games: u64,
+    pub seed: u64,
+    pub threads: usize,
+    pub time_limit: Option<Duration>,
+    /// Set once the user pressed Enter or the time limit passed. Shared by every batch of a command.
+    stop: Arc<AtomicBool>,
+    listening: Arc<AtomicBool>,
+    started: Instant,
+}
+
+impl BatchConfig {
+    pub fn from_options(options: &Options) -> Result<BatchConfig, String> {
+        let threads = thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1);
+        let players = options.get("players", 4)?;
+        if !(2..=6).contains(&players) {
+            return Err(format!("{} is not a valid number of players. Must be between 2 and 6.", players));
+        }
+        let time_limit: u64 = options.get("time-limit", 0)?;
+        Ok(BatchConfig {
+            players,
+            games: options.get("games", 10000)?,
+            seed: options.get("seed", 0)?,
+            threads: options.get("threads", threads)?,
+            time_limit: if time_limit > 0 { Some(Duration::from_secs(time_limit)) } else { None },
+            stop: Arc::new(AtomicBool::new(false)),
+            listening: Arc::new(AtomicBool::new(false)),
+            started: Instant::now(),
+        })
+    }
+
+    /// The same settings for another number of players.
+    pub fn with_players(&self, players: usize) -> BatchConfig {
+        BatchConfig { players, ..self.clone() }
+    }
+
+    /// The same settings with another number of games.
+    pub fn with_games(&self, games: u64) -> BatchConfig {
+        BatchConfig { games, ..self.clone() }
+    }
+
+    /// Runs the batch while printing progress. Pressing Enter or reaching the time limit stops
+    /// it and every later batch of the command early, keeping the results of the games that finished.
+    pub fn run<S, N, P>(&self, new: N, play: P) -> S
+    where
+        S: Merge + Send,
+        N: Fn() -> S + Sync,
+        P: Fn(u64, &mut S) + Sync,
+    {
+        println!("Simulating {} games with {} players on {} threads. Press Enter to stop early.", self.games, self.players, self.threads);
+        if !self.listening.swap(true, Ordering::Relaxed) {
+            let enter = self.stop.clone();
+            thread::spawn(move || {
+                let mut line = String::new();
+                if io::stdin().lock().read_line(&mut line).is_ok_and(|read| read > 0) {
+                    enter.store(true, Ordering::Relaxed);
+                }
+            });
+        }
+        let start = Instant::now();
+        let progress = |finished: u64| {
+            if self.time_limit.is_some_and(|limit| self.started.elapsed() >= limit) {
+                self.stop.store(true, Ordering::Relaxed);
+            }
+            print!("\rSimulated {}/{} games", finished, self.games);
+            io::stdout().flush().unwrap();
+        };
+        let tally = run_batch(self.games, self.threads, &self.stop, &progress, new, play);
+        println!();
+        if self.stop.load(Ordering::Relaxed) {
+            println!("Stopped early after {:.1}s.", start.elapsed().as_secs_f64());
+        } else {
+            println!("Finished in {:.1}s.", start.elapsed().as_secs_f64());
+        }
+        tally
+    }
+}
+
+/// `simulate`: plays many games between random bots and prints how each seat fared.
+pub fn command(options: &Options) -> Result<(), String> {
+    let config = BatchConfig::from_options(options)?;
+    let stats = config.run(|| Stats::new(config.players), |game, stats: &mut Stats| {
+        let mut rng = StdRng::seed_from_u64(game_seed(config.seed, game));
+        let agents = random_bots(config.players, &mut rng);
+        stats.add(&simulate_game(agents, StartPolicy::Rotate, &mut rng, &mut |_, _| {}));
+    });
+    stats.print();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn batch(threads: usize) -> Stats {
+        let stop = AtomicBool::new(false);
+        run_batch(40, threads, &stop, &|_| {}, || Stats::new(3), |game, stats: &mut Stats| {
+            let mut rng = StdRng::seed_from_u64(game_seed(7, game));
+            let agents = random_bots(3, &mut rng);
+            stats.add(&simulate_game(agents, StartPolicy::Rotate, &mut rng, &mut |_, _| {}));
+        })
+    }
+
+    #[test]
+    fn test_same_results_on_any_number_of_threads() {
+        let single = batch(1);
+        assert_eq!(single.games, 40);
+        assert_eq!(single, batch(4));
+    }
+
+    #[test]
+    fn test_stop_early() {
+        let stop = AtomicBool::new(true);
+        let stats = run_batch(40, 2, &stop, &|_| {}, || Stats::new(2), |_, stats: &mut Stats| stats.games += 1);
+        assert_eq!(stats.games, 0);
+    }
+}
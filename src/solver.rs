@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+
+use crate::agent::Agent;
+use crate::analytics::percent;
+use crate::belief::Knowledge;
+use crate::card::{list_cards, Card};
+use crate::game::{legal_actions, next_turn, play_card, play_tie_breaker, Action, ExecutionerChoice, State};
+use crate::options::Options;
+use crate::record::{load_rounds, positions};
+
+/// Bots use the solver once the deck has this many cards or fewer.
+pub const ENDGAME_DECK: usize = 2;
+/// The most hidden-card assignments the solver goes through.
+pub const MAX_ASSIGNMENTS: u64 = 5000;
+
+/// Answers the Executioner with a choice made in advance.
+struct Fixed(ExecutionerChoice);
+
+impl Agent for Fixed {
+    fn choose_action(&mut self, _state: &State, _hand: [Card; 2], _legal: &[Action]) -> Action {
+        unreachable!()
+    }
+
+    fn choose_executioner(&mut self, _state: &State, _hand: Card, _drawn: &[Card]) -> ExecutionerChoice {
+        self.0
+    }
+}
+
+const CHOICES: [ExecutionerChoice; 4] = [
+    ExecutionerChoice { keep: 0, hand_on_top: true },
+    ExecutionerChoice { keep: 0, hand_on_top: false },
+    ExecutionerChoice { keep: 1, hand_on_top: true },
+    ExecutionerChoice { keep: 1, hand_on_top: false },
+];
+
+/// The best of every Executioner choice for the player whose turn it is, or the one result of
+/// any other card. `state` holds the card they keep.
+fn play(state: &State, action: &Action, best: &mut dyn FnMut(State) -> Vec<f64>) -> Option<Vec<f64>> {
+    let player = state.turn;
+    let choices = if action.card == Card::Executioner { &CHOICES[..] } else { &CHOICES[..1] };
+    let mut result: Option<Vec<f64>> = None;
+    for choice in choices {
+        let (status, mut next) = play_card(state.clone(), action, &mut Fixed(*choice), &mut Vec::new());
+        if status.is_err() {
+            continue;
+        }
+        next_turn(&mut next);
+        let value = best(next);
+        if result.as_ref().is_none_or(|result| value[player] > result[player]) {
+            result = Some(value);
+        }
+    }
+    result
+}
+
+/// Who wins the round from `state` at the start of a turn if everyone sees every card and plays
+/// to win it: 1 for every winner, 0 for everyone else.
+fn search(mut state: State) -> Vec<f64> {
+    let players = state.players.len();
+    if state.out.len() == players - 1 || state.deck.is_empty() {
+        if state.out.len() < players - 1 {
+            state = play_tie_breaker(state);
+        }
+        return (0..players).map(|player| if state.out.contains(&player) { 0.0 } else { 1.0 }).collect();
+    }
+    if state.out.contains(&state.turn) {
+        next_turn(&mut state);
+        return search(state);
+    }
+
+    let held = state.hands[state.turn].unwrap();
+    let drawn = state.deck.pop().unwrap();
+    let mut best: Option<Vec<f64>> = None;
+    let mut seen_wilkins = false;
+    for action in legal_actions(&state, [held, drawn]) {
+        // Seeing every card, every Wilkins ends the same way and so does every wrong guess
+        if action.card == Card::Wilkins {
+            if seen_wilkins {
+                continue;
+            }
+            seen_wilkins = true;
+        }
+        if let (Some(target), Some(guess)) = (action.target, action.guess) {
+            let correct = state.hands[target] == Some(guess);
+            let first_wrong = guesses_other_than(state.hands[target]).first() == Some(&guess);
+            if !correct && !first_wrong {
+                continue;
+            }
+        }
+        let mut turn_state = state.clone();
+        turn_state.hands[turn_state.turn] = Some(if action.card == held { drawn } else { held });
+        if let Some(value) = play(&turn_state, &action, &mut search) {
+            if best.as_ref().is_none_or(|best| value[state.turn] > best[state.turn]) {
+                best = Some(value);
+            }
+        }
+    }
+    best.unwrap()
+}
+
+fn guesses_other_than(card: Option<Card>) -> Vec<Card> {
+    crate::game::guesses().into_iter().filter(|guess| Some(*guess) != card).collect()
+}
+
+/// How many distinct ways there are to deal `counts` copies of each card to `slots` places.
+fn arrangements(counts: &[usize; 10]) -> u64 {
+    let mut total = 1u64;
+    let mut placed = 0u64;
+    for count in counts {
+        for i in 1..=*count as u64 {
+            placed += 1;
+            total = total.saturating_mul(placed) / i;
+        }
+    }
+    total
+}
+
+/// Calls `visit` with every distinct order of the cards in `counts`.
+fn each_arrangement(counts: &mut [usize; 10], cards: &mut Vec<Card>, slots: usize, visit: &mut dyn FnMut(&[Card])) {
+    if cards.len() == slots {
+        visit(cards);
+        return;
+    }
+    for card in list_cards() {
+        if counts[card.value()] > 0 {
+            counts[card.value()] -= 1;
+            cards.push(card);
+            each_arrangement(counts, cards, slots, visit);
+            cards.pop();
+            counts[card.value()] += 1;
+        }
+    }
+}
+
+/// The other players whose card the player to act has not seen. Players who are out still hold
+/// a card, unless it was discarded, which nobody may have seen.
+pub fn hidden_hands(state: &State, knowledge: &Knowledge) -> Vec<usize> {
+    let seen = |seat: usize| knowledge.known[seat].is_some() || knowledge.revealed[seat].is_some();
+    (0..state.players.len()).filter(|seat| *seat != state.turn && state.hands[*seat].is_some() && !seen(*seat)).collect()
+}
+
+/// The exact chance of winning the round with each legal action for the player whose turn it
+/// is in `state`, holding `hand`, over every deal of the cards they have not seen that agrees
+/// with what they know. Assumes everyone plays perfectly from the next turn on, as if they could
+/// see every card. `None` if there are more than `max_assignments` deals to go through or what
+/// they know does not add up.
+pub fn win_chances(state: &State, knowledge: &Knowledge, hand: [Card; 2], max_assignments: u64) -> Option<Vec<(Action, f64)>> {
+    let me = state.turn;
+    let hidden = hidden_hands(state, knowledge);
+    let mut unseen = knowledge.unseen(state, &hand);
+    // The hidden hands, the deck and the card removed at the start
+    let slots = hidden.len() + state.deck.len() + 1;
+    if unseen.iter().sum::<usize>() != slots || arrangements(&unseen) > max_assignments {
+        return None;
+    }
+
+    let mut base = state.clone();
+    base.hands[me] = Some(hand[0]);
+    for (seat, known) in knowledge.known.iter().enumerate() {
+        if known.is_some() && !state.out.contains(&seat) {
+            base.hands[seat] = *known;
+        }
+    }
+    for (seat, revealed) in knowledge.revealed.iter().enumerate() {
+        if revealed.is_some() {
+            base.hands[seat] = *revealed;
+        }
+    }
+    let legal = legal_actions(&base, hand);
+    let mut wins = vec![0.0; legal.len()];
+    let mut deals = 0;
+    each_arrangement(&mut unseen, &mut Vec::new(), slots, &mut |cards| {
+        let mut deal = base.clone();
+        for (seat, card) in hidden.iter().zip(cards.iter()) {
+            deal.hands[*seat] = Some(*card);
+        }
+        deal.deck = cards[hidden.len()..hidden.len() + state.deck.len()].to_vec();
+
+        // Wrong guesses at the same player all end the same way
+        let mut outcomes: HashMap<(usize, Option<usize>, bool), f64> = HashMap::new();
+        for (i, action) in legal.iter().enumerate() {
+            let correct = action.guess.is_some_and(|guess| deal.hands[action.target.unwrap()] == Some(guess));
+            let key = (action.card.value(), action.target, correct);
+            let cached = if action.card == Card::Guard { outcomes.get(&key).copied() } else { None };
+            let outcome = cached.unwrap_or_else(|| {
+                let mut turn_state = deal.clone();
+                turn_state.hands[me] = Some(if action.card == hand[0] { hand[1] } else { hand[0] });
+                play(&turn_state, action, &mut search).map_or(0.0, |value| value[me])
+            });
+            outcomes.insert(key, outcome);
+            wins[i] += outcome;
+        }
+        deals += 1;
+    });
+    Some(legal.into_iter().zip(wins).map(|(action, wins)| (action, wins / deals as f64)).collect())
+}
+
+/// `solve`: goes through the rounds of `--record file`, or only round `--round n` of it, and
+/// prints the exact chance of winning each legal action had at every decision made with
+/// `--max-deck` or fewer cards left in the deck.
+pub fn command(options: &Options) -> Result<(), String> {
+    let path = options.value("record").ok_or("Give the rounds to analyse with --record file.")?;
+    let rounds = load_rounds(path)?;
+    let max_deck: usize = options.get("max-deck", 3)?;
+    let max_assignments: u64 = options.get("max-assignments", MAX_ASSIGNMENTS * 10)?;
+    let only: Option<usize> = options.value("round").map(|_| options.get("round", 0)).transpose()?;
+    for (index, round) in rounds.iter().enumerate() {
+        if only.is_some_and(|only| only != index + 1) {
+            continue;
+        }
+        println!("Round {} of {} ({}):", index + 1, rounds.len(), round.start.players.join(", "));
+        for position in positions(round)? {
+            let state = &position.state;
+            if state.deck.len() > max_deck {
+                continue;
+            }
+            let turn = &position.turn;
+            println!("\t{} holds {} and {}, {} cards left:", state.players[turn.player], turn.hand[0].name(), turn.hand[1].name(), state.deck.len());
+            let Some(mut chances) = win_chances(state, &position.knowledge, turn.hand, max_assignments) else {
+                println!("\t\ttoo many possible deals.");
+                continue;
+            };
+            chances.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+            for (action, chance) in chances {
+                let played = if action == turn.action { " <- played" } else { "" };
+                println!("\t\t{:>8}  {}{}", percent((chance * 1000.0).round() as u64, 1000), action.describe(&state.players), played);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::create_deck;
+    use crate::game::Event;
+
+    #[test]
+    fn test_last_card() {
+        // Ann holds a Guard and draws the last card, a Tweedies. Cid was knocked out by a Knave on
+        // their Alice and everything else but the Wilkins and the Time has been discarded, so Bob
+        // holds one of them and the other one was removed at the start.
+        let mut state = State {
+            deck: Vec::new(),
+            discard: vec![vec![], vec![], vec![Card::Alice]],
+            players: vec!["Ann".to_string(), "Bob".to_string(), "Cid".to_string()],
+            out: vec![2],
+            hands: vec![Some(Card::Guard), Some(Card::Wilkins), None],
+            tokens: vec![0, 0, 0],
+            round: 1,
+            turn: 0,
+            dormouse: None,
+        };
+        let mut rest = create_deck();
+        for card in [Card::Guard, Card::Tweedies, Card::Wilkins, Card::Time, Card::Alice] {
+            rest.remove(rest.iter().position(|other| *other == card).unwrap());
+        }
+        state.discard[1] = rest;
+        let mut knowledge = Knowledge::new(&state, 0);
+        let chances = |knowledge: &Knowledge| -> HashMap<String, f64> {
+            win_chances(&state, knowledge, [Card::Guard, Card::Tweedies], MAX_ASSIGNMENTS).unwrap()
+                .into_iter().map(|(action, chance)| (action.describe(&state.players), chance)).collect()
+        };
+
+        // Guessing Time wins either way, as The Tweedies beat Wilkins in the tie breaker
+        let unknown = chances(&knowledge);
+        assert_eq!(unknown["Guard on Bob, guessing Time"], 1.0);
+        assert_eq!(unknown["Guard on Bob, guessing Wilkins"], 0.5);
+        assert_eq!(unknown["Guard on Bob, guessing Alice"], 0.5);
+        assert_eq!(unknown["The Tweedies on Bob"], 0.0);
+
+        knowledge.known[1] = Some(Card::Wilkins);
+        assert_eq!(chances(&knowledge)["Guard on Bob, guessing Wilkins"], 1.0);
+    }
+
+    #[test]
+    fn test_wilkins_after_another_card() {
+        // Ann holds the Dormouse and a Guard and Bob is known to hold Alice. The last card is a
+        // Wilkins, which Bob plays to keep Alice and win, unless Ann guesses Alice first.
+        let mut state = State {
+            deck: vec![Card::Wilkins],
+            discard: vec![vec![], vec![]],
+            players: vec!["Ann".to_string(), "Bob".to_string()],
+            out: Vec::new(),
+            hands: vec![Some(Card::Guard), Some(Card::Alice)],
+            tokens: vec![0, 0],
+            round: 1,
+            turn: 0,
+            dormouse: None,
+        };
+        let mut rest = create_deck();
+        for card in [Card::Dormouse, Card::Guard, Card::Alice, Card::Wilkins, Card::Wilkins] {
+            rest.remove(rest.iter().position(|other| *other == card).unwrap());
+        }
+        state.discard[1] = rest;
+        let mut knowledge = Knowledge::new(&state, 0);
+        knowledge.known[1] = Some(Card::Alice);
+        let chances = win_chances(&state, &knowledge, [Card::Dormouse, Card::Guard], MAX_ASSIGNMENTS).unwrap();
+        for (action, chance) in chances {
+            let description = action.describe(&state.players);
+            assert_eq!(chance, if description == "Guard on Bob, guessing Alice" { 1.0 } else { 0.0 }, "{}", description);
+        }
+    }
+
+    #[test]
+    fn test_guessed_hand_is_not_hidden() {
+        // Ann knocked Cid out by guessing their Time, so Bob holds a Wilkins or the Nobody
+        let mut state = State {
+            deck: vec![Card::Wilkins],
+            discard: vec![vec![Card::Guard], vec![], vec![]],
+            players: vec!["Ann".to_string(), "Bob".to_string(), "Cid".to_string()],
+            out: vec![2],
+            hands: vec![Some(Card::Guard), Some(Card::Nobody), Some(Card::Time)],
+            tokens: vec![0, 0, 0],
+            round: 1,
+            turn: 0,
+            dormouse: None,
+        };
+        let mut rest = create_deck();
+        for card in [Card::Guard, Card::Guard, Card::Tweedies, Card::Time, Card::Wilkins, Card::Wilkins, Card::Nobody] {
+            rest.remove(rest.iter().position(|other| *other == card).unwrap());
+        }
+        state.discard[1] = rest;
+        let mut knowledge = Knowledge::new(&state, 0);
+        knowledge.observe(&state, &Event::Guessed { player: 0, target: 2, guess: Card::Time, correct: true });
+        assert_eq!(hidden_hands(&state, &knowledge), vec![1]);
+        let chances: HashMap<String, f64> = win_chances(&state, &knowledge, [Card::Guard, Card::Tweedies], MAX_ASSIGNMENTS).unwrap()
+            .into_iter().map(|(action, chance)| (action.describe(&state.players), chance)).collect();
+        // Guessing Time is as hopeless as guessing the Alice that was discarded
+        assert_eq!(chances["Guard on Bob, guessing Time"], chances["Guard on Bob, guessing Alice"]);
+    }
+}
@@ -0,0 +1,354 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::net::TcpListener;
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use crate::agent::Agent;
+use crate::card::Card;
+use crate::game::{Action, Event, ExecutionerChoice, PlayError, State};
+use crate::protocol::{Message, TableView};
+use crate::server::{read_greeting, Connection, Greeting, Transport};
+
+// Spectators answer `loveletter` with `watch`, or `watch all` for the omniscient view:
+//
+// server: watching <public|all>
+//
+// and are then sent the table at the start of every round and after every turn:
+// server: table <round> <turn> <deck size> <players>
+//         names <name of every seat>
+//         tokens <tokens of every seat>
+//         out <1 or 0 for every seat>
+//         protected <1 or 0 for every seat>
+//         dormouse <seat, or ->
+//         hands <card of every seat, or ->    omniscient view only
+//         order <the deck, top card first>     omniscient view only
+//         discard <seat> <cards>               for every seat
+// followed by `info` lines with the events as someone who is not at the table sees them, the
+// `result` line of every round and the `winner` and `quit` lines of the engine protocol.
+//
+// The omniscient view is only offered when the server is started with `--omniscient <rounds>`.
+// It is sent every round only once the round is over, and that many more rounds late, so a
+// player cannot watch it to learn the hands or the deck of the table they are playing at.
+
+/// The seat spectators watch from: none, so they only see what is public.
+pub const SPECTATOR: usize = usize::MAX;
+
+/// Where spectators of a game are handed over, from any thread.
+#[derive(Clone)]
+pub struct Watchers {
+    joining: Sender<(Connection, bool)>,
+    /// Whether the omniscient view is offered.
+    omniscient: bool,
+}
+
+impl Watchers {
+    /// Lets `connection` watch the game, or tells the client why it cannot.
+    pub fn admit(&self, mut connection: Connection, omniscient: bool) -> Result<(), String> {
+        let refused = if omniscient && !self.omniscient {
+            "This server does not show the hands to spectators."
+        } else {
+            connection.send(&[Message::Watching { omniscient }]);
+            match self.joining.send((connection, omniscient)) {
+                Ok(()) => return Ok(()),
+                Err(error) => {
+                    connection = error.0 .0;
+                    "That game is over."
+                },
+            }
+        };
+        connection.send(&[Message::Error { message: refused.to_string() }, Message::Quit]);
+        Err(refused.to_string())
+    }
+}
+
+/// The spectators of a game. The public view is sent as the game goes, the omniscient view is
+/// held back until a round is over and `delay` more rounds have ended.
+pub struct Spectators {
+    public: Vec<Connection>,
+    omniscient: Vec<Connection>,
+    joining: Receiver<(Connection, bool)>,
+    delay: usize,
+    /// What the omniscient view has not been sent yet, a batch of messages per round.
+    delayed: VecDeque<Vec<Message>>,
+    /// The last state shown, to know when a turn starts.
+    last: Option<State>,
+}
+
+impl Spectators {
+    /// Spectators of a game that shows them the hands once a round is over and `delay` more
+    /// rounds have ended, or never if `None`.
+    pub fn new(delay: Option<usize>) -> (Spectators, Watchers) {
+        let (sender, joining) = mpsc::channel();
+        let spectators = Spectators { public: Vec::new(), omniscient: Vec::new(), joining, delay: delay.unwrap_or(0), delayed: VecDeque::new(), last: None };
+        (spectators, Watchers { joining: sender, omniscient: delay.is_some() })
+    }
+
+    /// Sends `public` to the public view and queues `omniscient` for the omniscient view. `turn`
+    /// is true if they start a turn.
+    fn show(&mut self, public: Vec<Message>, omniscient: Vec<Message>, turn: bool) {
+        while let Ok((mut connection, all)) = self.joining.try_recv() {
+            if all {
+                self.omniscient.push(connection);
+            } else if let Some(state) = self.last.as_ref().filter(|_| !turn) {
+                // Catch up with the table before the events of the turn
+                if connection.send(&[Message::Table(TableView::new(state, false))]) {
+                    self.public.push(connection);
+                }
+            } else {
+                self.public.push(connection);
+            }
+        }
+        self.public.retain_mut(|connection| connection.send(&public));
+        if self.delayed.is_empty() {
+            self.delayed.push_back(Vec::new());
+        }
+        self.delayed.back_mut().unwrap().extend(omniscient);
+    }
+
+    /// Sends the oldest batch held back to the omniscient view.
+    fn release(&mut self) {
+        if let Some(batch) = self.delayed.pop_front() {
+            self.omniscient.retain_mut(|connection| connection.send(&batch));
+        }
+    }
+
+    fn show_table(&mut self, state: &State) {
+        self.last = Some(state.clone());
+        let public = vec![Message::Table(TableView::new(state, false))];
+        self.show(public, vec![Message::Table(TableView::new(state, true))], true);
+    }
+
+    pub fn start_round(&mut self, state: &State) {
+        self.delayed.push_back(Vec::new());
+        self.show_table(state);
+    }
+
+    pub fn observe(&mut self, state: &State, event: &Event) {
+        if self.last.as_ref() != Some(state) {
+            self.show_table(state);
+        }
+        if let Some(text) = event.describe(&state.players, SPECTATOR) {
+            let message = Message::Event { text };
+            self.show(vec![message.clone()], vec![message], false);
+        }
+    }
+
+    pub fn end_round(&mut self, state: &State, winners: &[usize]) {
+        let message = Message::RoundResult { tokens: state.tokens.clone(), winners: winners.to_vec() };
+        self.show(vec![message.clone()], vec![message], false);
+        while self.delayed.len() > self.delay {
+            self.release();
+        }
+    }
+
+    /// Shows the omniscient view what it has not seen yet and tells everyone who won.
+    pub fn finish(&mut self, winner: Option<String>) {
+        while !self.delayed.is_empty() {
+            self.release();
+        }
+        let messages = [Message::Winner { name: winner }, Message::Quit];
+        for connection in self.public.iter_mut().chain(self.omniscient.iter_mut()) {
+            connection.send(&messages);
+        }
+    }
+}
+
+/// An agent whose game is shown to spectators. Only one agent of a table is wrapped, so every
+/// event is shown once.
+pub struct Watched {
+    pub agent: Box<dyn Agent>,
+    pub spectators: Rc<RefCell<Spectators>>,
+}
+
+impl Agent for Watched {
+    fn start_round(&mut self, state: &State, seat: usize) {
+        self.spectators.borrow_mut().start_round(state);
+        self.agent.start_round(state, seat);
+    }
+
+    fn choose_action(&mut self, state: &State, hand: [Card; 2], legal: &[Action]) -> Action {
+        self.agent.choose_action(state, hand, legal)
+    }
+
+    fn choose_executioner(&mut self, state: &State, hand: Card, drawn: &[Card]) -> ExecutionerChoice {
+        self.agent.choose_executioner(state, hand, drawn)
+    }
+
+    fn invalid_action(&mut self, state: &State, action: &Action, error: &PlayError) {
+        self.agent.invalid_action(state, action, error);
+    }
+
+    fn observe(&mut self, state: &State, event: &Event) {
+        self.spectators.borrow_mut().observe(state, event);
+        self.agent.observe(state, event);
+    }
+
+    fn end_round(&mut self, state: &State, winners: &[usize]) {
+        self.spectators.borrow_mut().end_round(state, winners);
+        self.agent.end_round(state, winners);
+    }
+
+    fn forfeited(&self) -> Option<String> {
+        self.agent.forfeited()
+    }
+}
+
+/// Lets spectators connect to `listener` to watch a game played somewhere else, such as at this
+/// terminal. Players cannot join there.
+pub fn listen(listener: TcpListener, watchers: Watchers) {
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let watchers = watchers.clone();
+            thread::spawn(move || {
+                let Ok(mut connection) = Connection::new(stream, Transport::Lines) else { return };
+                match read_greeting(&mut connection) {
+                    Ok(Greeting::Watch(omniscient)) => {
+                        let _ = watchers.admit(connection, omniscient);
+                    },
+                    Ok(_) => {
+                        connection.send(&[Message::Error { message: "This game can only be watched.".to_string() }, Message::Quit]);
+                    },
+                    Err(_) => {},
+                }
+            });
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bots::HeuristicBot;
+    use crate::game::deal;
+    use crate::record::play_round;
+    use crate::server::read_greeting;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::cell::Cell;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpStream;
+
+    /// Connects a spectator that answers `greeting` and returns every line it is sent.
+    fn spectator(port: u16, greeting: &'static str) -> thread::JoinHandle<Vec<String>> {
+        let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        thread::spawn(move || {
+            let mut writer = stream.try_clone().unwrap();
+            let mut received = Vec::new();
+            for line in BufReader::new(stream).lines() {
+                let line = line.unwrap();
+                if line.starts_with("loveletter") {
+                    writeln!(writer, "{}", greeting).unwrap();
+                }
+                let quit = line == "quit";
+                received.push(line);
+                if quit {
+                    break;
+                }
+            }
+            received
+        })
+    }
+
+    #[test]
+    fn test_spectators() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (spectators, watchers) = Spectators::new(Some(1));
+        let clients = [spectator(port, "watch"), spectator(port, "watch all")];
+        for _ in 0..2 {
+            let mut connection = Connection::new(listener.accept().unwrap().0, Transport::Lines).unwrap();
+            let Ok(Greeting::Watch(omniscient)) = read_greeting(&mut connection) else { panic!() };
+            watchers.admit(connection, omniscient).unwrap();
+        }
+
+        let spectators = Rc::new(RefCell::new(spectators));
+        let mut agents: Vec<Box<dyn Agent>> = vec![
+            Box::new(Watched { agent: Box::new(HeuristicBot::new(1)), spectators: spectators.clone() }),
+            Box::new(HeuristicBot::new(2)),
+        ];
+        let start = deal(vec!["Ann".to_string(), "Bob".to_string()], vec![0, 0], 1, &mut StdRng::seed_from_u64(4));
+        play_round(start, &mut agents);
+        // The round is still held back from the omniscient view, one round late
+        assert_eq!(spectators.borrow().delayed.len(), 1);
+        spectators.borrow_mut().finish(Some("Ann".to_string()));
+
+        let [public, omniscient] = clients.map(|client| client.join().unwrap());
+        assert_eq!(public[1], "watching public");
+        assert_eq!(omniscient[1], "watching all");
+        let count = |lines: &[String], keyword: &str| lines.iter().filter(|line| line.split_whitespace().next() == Some(keyword)).count();
+        assert!(count(&public, "table") > 1);
+        assert_eq!(count(&public, "hands") + count(&public, "order"), 0);
+        assert_eq!(count(&omniscient, "table"), count(&public, "table"));
+        assert_eq!(count(&omniscient, "hands"), count(&omniscient, "table"));
+        assert_eq!(count(&omniscient, "info"), count(&public, "info"));
+        assert_eq!(public.last().unwrap(), "quit");
+        assert_eq!(omniscient.last().unwrap(), "quit");
+
+        // A server that does not offer the hands turns the omniscient view away
+        let (_, watchers) = Spectators::new(None);
+        let client = spectator(port, "watch all");
+        let mut connection = Connection::new(listener.accept().unwrap().0, Transport::Lines).unwrap();
+        let Ok(Greeting::Watch(omniscient)) = read_greeting(&mut connection) else { panic!() };
+        assert!(watchers.admit(connection, omniscient).is_err());
+        assert!(client.join().unwrap().iter().any(|line| line.starts_with("error")));
+    }
+
+    /// Plays like `agent` and checks that no deck the omniscient view was sent is the deck of
+    /// the round being played.
+    struct Checked {
+        agent: HeuristicBot,
+        watcher: Receiver<Message>,
+        /// How many tables of the omniscient view it was sent.
+        shown: Rc<Cell<usize>>,
+    }
+
+    impl Agent for Checked {
+        fn start_round(&mut self, state: &State, seat: usize) {
+            self.agent.start_round(state, seat);
+        }
+
+        fn choose_action(&mut self, state: &State, hand: [Card; 2], legal: &[Action]) -> Action {
+            let order: Vec<Card> = state.deck.iter().rev().copied().collect();
+            for message in self.watcher.try_iter() {
+                if let Message::Table(view) = message {
+                    assert!(view.round < state.round);
+                    assert_ne!(view.deck, Some(order.clone()));
+                    self.shown.set(self.shown.get() + 1);
+                }
+            }
+            self.agent.choose_action(state, hand, legal)
+        }
+
+        fn choose_executioner(&mut self, state: &State, hand: Card, drawn: &[Card]) -> ExecutionerChoice {
+            self.agent.choose_executioner(state, hand, drawn)
+        }
+
+        fn observe(&mut self, state: &State, event: &Event) {
+            self.agent.observe(state, event);
+        }
+    }
+
+    #[test]
+    fn test_omniscient_view_waits_for_the_round() {
+        let (spectators, watchers) = Spectators::new(Some(0));
+        let (connection, _, watcher) = Connection::channel();
+        watchers.admit(connection, true).unwrap();
+        assert_eq!(watcher.recv().unwrap(), Message::Watching { omniscient: true });
+
+        let spectators = Rc::new(RefCell::new(spectators));
+        let shown = Rc::new(Cell::new(0));
+        let mut agents: Vec<Box<dyn Agent>> = vec![
+            Box::new(Watched { agent: Box::new(HeuristicBot::new(1)), spectators: spectators.clone() }),
+            Box::new(Checked { agent: HeuristicBot::new(2), watcher, shown: shown.clone() }),
+        ];
+        let players = vec!["Ann".to_string(), "Bob".to_string()];
+        let mut rng = StdRng::seed_from_u64(4);
+        let first = play_round(deal(players.clone(), vec![0, 0], 1, &mut rng), &mut agents);
+        play_round(deal(players, first.end.tokens.clone(), 2, &mut rng), &mut agents);
+        // The first round was shown while the second was played
+        assert!(shown.get() > 0);
+        assert!(spectators.borrow().delayed.is_empty());
+    }
+}
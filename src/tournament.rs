@@ -0,0 +1,385 @@
+use std::collections::BTreeMap;
+
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use crate::agent::Agent;
+use crate::analytics::percent;
+use crate::bots::{bot_names, make_profile_bot};
+use crate::model::OpponentModel;
+use crate::options::Options;
+use crate::profile::{load_profiles, Profile};
+use crate::ratings::{self, Match};
+use crate::sim::{game_seed, simulate_game, BatchConfig, GameResult, Merge, StartPolicy};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    RoundRobin,
+    Swiss { rounds: usize },
+}
+
+/// Every way to seat `size` of `entrants` entrants at a table.
+pub fn round_robin_tables(entrants: usize, size: usize) -> Vec<Vec<usize>> {
+    let mut tables = Vec::new();
+    let mut table: Vec<usize> = (0..size).collect();
+    if size > entrants {
+        return tables;
+    }
+    loop {
+        tables.push(table.clone());
+        // Next combination in lexicographic order
+        let Some(i) = (0..size).rev().find(|i| table[*i] < entrants - size + i) else {
+            return tables;
+        };
+        table[i] += 1;
+        for j in i + 1..size {
+            table[j] = table[j - 1] + 1;
+        }
+    }
+}
+
+/// Seats entrants with similar results together, best first, sitting with the entrants they have
+/// not met yet where they can. When one entrant is left over it sits the round out, picking
+/// whoever has played the most games so byes go around. Other leftovers are spread so table sizes
+/// differ by at most one.
+pub fn swiss_tables(stats: &TournamentStats, size: usize) -> Vec<Vec<usize>> {
+    let mut order: Vec<usize> = (0..stats.wins.len()).collect();
+    order.sort_by(|a, b| stats.win_rate(*b).partial_cmp(&stats.win_rate(*a)).unwrap().then(a.cmp(b)));
+    if order.len() % size == 1 {
+        let bye = order.iter().rev().max_by_key(|entrant| stats.games[**entrant]).copied().unwrap();
+        order.retain(|entrant| *entrant != bye);
+    }
+    let count = order.len().div_ceil(size);
+    let mut tables = Vec::new();
+    for number in 0..count {
+        // The first tables take the extra players
+        let seats = order.len().div_ceil(count - number);
+        let mut table = vec![order.remove(0)];
+        while table.len() < seats {
+            let rematches = |entrant: usize| table.iter().filter(|seated| stats.met[**seated][entrant] > 0).count();
+            let next = (0..order.len()).min_by_key(|i| (rematches(order[*i]), *i)).unwrap();
+            table.push(order.remove(next));
+        }
+        tables.push(table);
+    }
+    tables
+}
+
+/// Standings and head-to-head results, indexed by entrant.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TournamentStats {
+    pub games: Vec<u64>,
+    pub wins: Vec<u64>,
+    /// Wins expected from an entrant no better than the others, in sixtieths of a game so that
+    /// every table size from 2 to 6 adds a whole number.
+    pub expected: Vec<u64>,
+    /// `ahead[a][b]` is the number of games where `a` finished ahead of `b`.
+    pub ahead: Vec<Vec<u64>>,
+    /// `met[a][b]` is the number of games `a` and `b` played at the same table.
+    pub met: Vec<Vec<u64>>,
+    /// Games won by each entrant of every table, keyed by the entrants in ascending order.
+    pub table_wins: BTreeMap<Vec<usize>, Vec<u64>>,
+}
+
+impl TournamentStats {
+    pub fn new(entrants: usize) -> TournamentStats {
+        TournamentStats {
+            games: vec![0; entrants],
+            wins: vec![0; entrants],
+            expected: vec![0; entrants],
+            ahead: vec![vec![0; entrants]; entrants],
+            met: vec![vec![0; entrants]; entrants],
+            table_wins: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a game where `table[i]` sat in seat `i` of the first round.
+    pub fn add(&mut self, table: &[usize], result: &GameResult) {
+        // The winner finishes ahead of everyone, the others are ranked by tokens
+        let rank = |seat: usize| (result.winner == Some(seat), result.tokens[seat]);
+        for (seat, entrant) in table.iter().enumerate() {
+            self.games[*entrant] += 1;
+            self.expected[*entrant] += 60 / table.len() as u64;
+            if result.winner == Some(seat) {
+                self.wins[*entrant] += 1;
+            }
+            for (other_seat, other) in table.iter().enumerate() {
+                if other_seat == seat {
+                    continue;
+                }
+                self.met[*entrant][*other] += 1;
+                if rank(seat) > rank(other_seat) {
+                    self.ahead[*entrant][*other] += 1;
+                }
+            }
+        }
+
+        let mut entrants = table.to_vec();
+        entrants.sort();
+        let wins = self.table_wins.entry(entrants.clone()).or_insert(vec![0; table.len()]);
+        if let Some(winner) = result.winner {
+            wins[entrants.iter().position(|entrant| *entrant == table[winner]).unwrap()] += 1;
+        }
+    }
+
+    /// One match per table with the games each entrant won there, for the ratings file.
+    pub fn matches(&self, names: &[String]) -> Vec<Match> {
+        self.table_wins.iter().map(|(table, wins)| {
+            Match::new(table.iter().zip(wins.iter()).map(|(entrant, wins)| (names[*entrant].clone(), *wins as i32)).collect())
+        }).collect()
+    }
+
+    pub fn win_rate(&self, entrant: usize) -> f64 {
+        if self.games[entrant] == 0 {
+            0.0
+        } else {
+            self.wins[entrant] as f64 / self.games[entrant] as f64
+        }
+    }
+
+    pub fn print(&self, names: &[String]) {
+        let mut order: Vec<usize> = (0..names.len()).collect();
+        order.sort_by(|a, b| self.win_rate(*b).partial_cmp(&self.win_rate(*a)).unwrap().then(a.cmp(b)));
+
+        println!("\nStandings:");
+        println!("{:<4}{:<16}{:>8}{:>8}{:>10}{:>18}{:>10}", "#", "Bot", "Games", "Wins", "Win rate", "95% interval", "Expected");
+        for (place, entrant) in order.iter().enumerate() {
+            let (low, high) = wilson_interval(self.wins[*entrant], self.games[*entrant]);
+            println!(
+                "{:<4}{:<16}{:>8}{:>8}{:>10}{:>18}{:>10}",
+                format!("{}.", place + 1),
+                names[*entrant],
+                self.games[*entrant],
+                self.wins[*entrant],
+                percent(self.wins[*entrant], self.games[*entrant]),
+                format!("{:.1}% - {:.1}%", 100.0 * low, 100.0 * high),
+                percent(self.expected[*entrant], 60 * self.games[*entrant]),
+            );
+        }
+
+        println!("\nHead-to-head, how often the row finished ahead of the column (95% interval):");
+        let mut header = format!("{:<16}", "");
+        for entrant in &order {
+            header += &format!("{:>22}", names[*entrant]);
+        }
+        println!("{}", header);
+        for a in &order {
+            let mut row = format!("{:<16}", names[*a]);
+            for b in &order {
+                if a == b || self.met[*a][*b] == 0 {
+                    row += &format!("{:>22}", "-");
+                } else {
+                    let (low, high) = wilson_interval(self.ahead[*a][*b], self.met[*a][*b]);
+                    row += &format!("{:>22}", format!("{} ({:.0}-{:.0}%)", percent(self.ahead[*a][*b], self.met[*a][*b]), 100.0 * low, 100.0 * high));
+                }
+            }
+            println!("{}", row);
+        }
+    }
+}
+
+impl Merge for TournamentStats {
+    fn merge(&mut self, other: TournamentStats) {
+        for a in 0..self.games.len() {
+            self.games[a] += other.games[a];
+            self.wins[a] += other.wins[a];
+            self.expected[a] += other.expected[a];
+            for b in 0..self.games.len() {
+                self.ahead[a][b] += other.ahead[a][b];
+                self.met[a][b] += other.met[a][b];
+            }
+        }
+        for (table, wins) in other.table_wins {
+            let total = self.table_wins.entry(table).or_insert(vec![0; wins.len()]);
+            for (total, wins) in total.iter_mut().zip(wins) {
+                *total += wins;
+            }
+        }
+    }
+}
+
+/// The 95% Wilson score interval of a proportion.
+pub fn wilson_interval(successes: u64, trials: u64) -> (f64, f64) {
+    if trials == 0 {
+        return (0.0, 1.0);
+    }
+    let z = 1.96;
+    let n = trials as f64;
+    let p = successes as f64 / n;
+    let center = (p + z * z / (2.0 * n)) / (1.0 + z * z / n);
+    let margin = z / (1.0 + z * z / n) * (p * (1.0 - p) / n + z * z / (4.0 * n * n)).sqrt();
+    ((center - margin).max(0.0), (center + margin).min(1.0))
+}
+
+pub struct Tournament {
+    /// The bot of each entrant, as understood by `make_bot`.
+    pub bots: Vec<String>,
+    /// Heuristic bots with their own style, entered by their names.
+    pub profiles: Vec<Profile>,
+    /// What the heuristic bots know about the players they meet, if anything.
+    pub model: Option<OpponentModel>,
+    pub table: usize,
+    pub format: Format,
+}
+
+impl Tournament {
+    /// Unique display names, numbering bots that were entered more than once.
+    pub fn names(&self) -> Vec<String> {
+        self.bots.iter().enumerate().map(|(i, bot)| {
+            let copies = self.bots.iter().filter(|other| *other == bot).count();
+            if copies > 1 {
+                let number = self.bots[..i].iter().filter(|other| *other == bot).count() + 1;
+                format!("{} #{}", bot, number)
+            } else {
+                bot.clone()
+            }
+        }).collect()
+    }
+
+    /// Plays `config.games` games at every table of a stage, numbering the games from `first`.
+    /// Seats rotate from game to game so nobody always starts.
+    fn play_stage(&self, config: &BatchConfig, tables: &[Vec<usize>], first: u64) -> TournamentStats {
+        let games = config.games * tables.len() as u64;
+        let stage = config.with_players(self.table).with_games(games);
+        stage.run(|| TournamentStats::new(self.bots.len()), |game, stats: &mut TournamentStats| {
+            let mut table = tables[(game / config.games) as usize].clone();
+            let start = (game % config.games) as usize % table.len();
+            table.rotate_left(start);
+            let mut rng = StdRng::seed_from_u64(game_seed(config.seed, first + game));
+            let agents: Vec<Box<dyn Agent>> = table.iter().map(|entrant| make_profile_bot(&self.bots[*entrant], rng.gen(), &self.profiles, self.model.as_ref()).unwrap()).collect();
+            let result = simulate_game(agents, StartPolicy::Rotate, &mut rng, &mut |_, _| {});
+            stats.add(&table, &result);
+        })
+    }
+
+    pub fn run(&self, config: &BatchConfig) -> TournamentStats {
+        match self.format {
+            Format::RoundRobin => self.play_stage(config, &round_robin_tables(self.bots.len(), self.table), 0),
+            Format::Swiss { rounds } => {
+                let mut stats = TournamentStats::new(self.bots.len());
+                let mut first = 0;
+                for round in 0..rounds {
+                    let tables = swiss_tables(&stats, self.table);
+                    println!("Swiss round {} of {}, {} tables.", round + 1, rounds, tables.len());
+                    stats.merge(self.play_stage(config, &tables, first));
+                    first += config.games * tables.len() as u64;
+                }
+                stats
+            },
+        }
+    }
+}
+
+/// `tournament`: plays the bots of `--bots a,b,c` against each other at tables of `--table`
+/// players, in every combination (`--format round-robin`) or in `--rounds` Swiss rounds
+/// (`--format swiss`). `--games` is the number of games per table. With `--ratings file` every
+/// table counts as a match for the ratings, scored by games won. `--profiles file` enters the
+/// play styles of the file by their names, and with `--model file` the heuristic bots use what
+/// the opponent model learned about the players they meet.
+pub fn command(options: &Options) -> Result<(), String> {
+    let bots: Vec<String> = options.value("bots").unwrap_or("heuristic,random").split(',').map(|bot| bot.trim().to_string()).collect();
+    let profiles = match options.value("profiles") {
+        Some(path) => load_profiles(path)?,
+        None => Vec::new(),
+    };
+    let mut names = bot_names();
+    names.extend(profiles.iter().map(|profile| profile.name.clone()));
+    for bot in &bots {
+        if !names.contains(bot) {
+            return Err(format!("Unknown bot \"{}\". Bots: {}.", bot, names.join(", ")));
+        }
+    }
+    let table: usize = options.get("table", 2)?;
+    if !(2..=6).contains(&table) || table > bots.len() {
+        return Err(format!("Tables must have between 2 and 6 players and no more than the {} bots.", bots.len()));
+    }
+    let format = match options.value("format").unwrap_or("round-robin") {
+        "round-robin" => Format::RoundRobin,
+        "swiss" => Format::Swiss { rounds: options.get("rounds", 5)? },
+        format => return Err(format!("Unknown format \"{}\". Formats: round-robin, swiss.", format)),
+    };
+    let mut config = BatchConfig::from_options(options)?;
+    if options.value("games").is_none() {
+        config.games = 1000;
+    }
+
+    let model = options.value("model").map(OpponentModel::load).transpose()?;
+    let tournament = Tournament { bots, profiles, model, table, format };
+    let stats = tournament.run(&config);
+    stats.print(&tournament.names());
+    if let Some(path) = options.value("ratings") {
+        ratings::record(path, &stats.matches(&tournament.names()))?;
+        println!("\nRatings updated in {}.", path);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_robin_tables() {
+        assert_eq!(round_robin_tables(4, 2).len(), 6);
+        assert_eq!(round_robin_tables(6, 3).len(), 20);
+        assert_eq!(round_robin_tables(3, 3), vec![vec![0, 1, 2]]);
+        assert!(round_robin_tables(2, 3).is_empty());
+    }
+
+    #[test]
+    fn test_swiss_byes_go_around() {
+        let mut stats = TournamentStats::new(4);
+        let mut sat_out = Vec::new();
+        for _ in 0..4 {
+            let tables = swiss_tables(&stats, 3);
+            assert_eq!(tables.len(), 1);
+            let bye = (0..4).find(|entrant| !tables[0].contains(entrant)).unwrap();
+            sat_out.push(bye);
+            for entrant in &tables[0] {
+                stats.games[*entrant] += 1;
+            }
+        }
+        sat_out.sort();
+        assert_eq!(sat_out, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_swiss_leftovers_and_rematches() {
+        let sizes = |entrants: usize, size: usize| swiss_tables(&TournamentStats::new(entrants), size).iter().map(Vec::len).collect::<Vec<usize>>();
+        assert_eq!(sizes(5, 3), vec![3, 2]);
+        assert_eq!(sizes(10, 4), vec![4, 3, 3]);
+        assert_eq!(sizes(7, 3), vec![3, 3]);
+        assert_eq!(sizes(8, 6), vec![4, 4]);
+
+        // Ann and Bob met, and so did Cid and Dee: the leaders do not play each other again
+        let mut stats = TournamentStats::new(4);
+        let results = [(0, [3, 0]), (2, [1, 0])];
+        for (first, tokens) in results {
+            stats.add(&[first, first + 1], &GameResult { winner: Some(0), tokens: tokens.to_vec(), rounds: 3, forfeit: None });
+        }
+        stats.wins[2] = 0;
+        assert_eq!(swiss_tables(&stats, 2), vec![vec![0, 2], vec![1, 3]]);
+    }
+
+    #[test]
+    fn test_wilson_interval() {
+        let (low, high) = wilson_interval(50, 100);
+        assert!((low - 0.404).abs() < 0.001 && (high - 0.596).abs() < 0.001);
+        assert_eq!(wilson_interval(0, 0), (0.0, 1.0));
+    }
+
+    #[test]
+    fn test_heuristic_beats_random() {
+        let tournament = Tournament { bots: vec!["heuristic".to_string(), "random".to_string()], profiles: Vec::new(), model: None, table: 2, format: Format::RoundRobin };
+        let tables = round_robin_tables(2, 2);
+        let mut stats = TournamentStats::new(2);
+        for game in 0..100 {
+            let mut table = tables[0].clone();
+            table.rotate_left(game % 2);
+            let mut rng = StdRng::seed_from_u64(game as u64);
+            let agents: Vec<Box<dyn Agent>> = table.iter().map(|entrant| make_profile_bot(&tournament.bots[*entrant], rng.gen(), &tournament.profiles, None).unwrap()).collect();
+            stats.add(&table, &simulate_game(agents, StartPolicy::Rotate, &mut rng, &mut |_, _| {}));
+        }
+        assert!(stats.wins[0] > stats.wins[1]);
+    }
+}
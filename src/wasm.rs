@@ -0,0 +1,198 @@
+use wasmi::{Config, Engine, Instance, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc};
+
+use crate::agent::Agent;
+use crate::belief::Knowledge;
+use crate::card::Card;
+use crate::encoding::{encode, FEATURE_COUNT};
+use crate::env::{action_index, decode_action, executioner_index, observe, Decision, EXECUTIONER};
+use crate::game::{Action, Event, ExecutionerChoice, State};
+
+// What a bot compiled to WebAssembly must look like. It may not import anything, so all it ever
+// sees is what the host writes into its memory:
+//
+// export "memory"                  its linear memory
+// export "buffer": () -> i32       where the host writes the input, at least
+//                                  `4 * (FEATURE_COUNT + ACTION_COUNT)` bytes
+// export "act": (i32) -> i32       called for every decision with the number of legal actions
+//
+// Before every call to `act` the host writes the encoded observation of the bot's seat, as
+// `FEATURE_COUNT` little-endian f32, followed by the legal action indices as little-endian i32.
+// `act` returns the action index it takes, which must be one of them. The instance lives for the
+// whole game, so a bot may remember things between decisions.
+//
+// Every decision may use up to the fuel limit of instructions and the memory never grows past the
+// memory limit. A bot that traps, runs out of fuel or returns an illegal action forfeits.
+
+pub const DEFAULT_FUEL: u64 = 10_000_000;
+pub const DEFAULT_MEMORY: usize = 16 << 20;
+
+struct Host {
+    limits: StoreLimits,
+}
+
+/// A seat played by a sandboxed WebAssembly bot. After forfeiting it plays the first legal
+/// action until the round is over.
+pub struct WasmBot {
+    store: Store<Host>,
+    memory: Memory,
+    buffer: usize,
+    act: TypedFunc<i32, i32>,
+    fuel: u64,
+    knowledge: Option<Knowledge>,
+    forfeit: Option<String>,
+}
+
+impl WasmBot {
+    /// Loads a bot from WebAssembly bytes, giving it `fuel` per decision and at most `memory` bytes.
+    pub fn new(bytes: &[u8], fuel: u64, memory: usize) -> Result<WasmBot, String> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, bytes).map_err(|error| format!("Invalid bot: {}", error))?;
+        let limits = StoreLimitsBuilder::new().memory_size(memory).instances(1).memories(1).tables(1).build();
+        let mut store = Store::new(&engine, Host { limits });
+        store.limiter(|host| &mut host.limits);
+        store.set_fuel(fuel).map_err(|error| error.to_string())?;
+
+        // Nothing is linked, so a bot that imports anything does not load
+        let linker = Linker::<Host>::new(&engine);
+        let instance: Instance = linker.instantiate(&mut store, &module).and_then(|instance| instance.start(&mut store)).map_err(|error| format!("Could not start the bot: {}", error))?;
+        let memory = instance.get_memory(&store, "memory").ok_or("The bot does not export its memory.")?;
+        let buffer = instance.get_typed_func::<(), i32>(&store, "buffer").map_err(|error| format!("buffer: {}", error))?;
+        let act = instance.get_typed_func::<i32, i32>(&store, "act").map_err(|error| format!("act: {}", error))?;
+        store.set_fuel(fuel).map_err(|error| error.to_string())?;
+        let buffer = buffer.call(&mut store, ()).map_err(|error| format!("buffer: {}", error))? as u32 as usize;
+        Ok(WasmBot { store, memory, buffer, act, fuel, knowledge: None, forfeit: None })
+    }
+
+    pub fn load(path: &str, fuel: u64, memory: usize) -> Result<WasmBot, String> {
+        let bytes = std::fs::read(path).map_err(|error| format!("Could not read {}: {}", path, error))?;
+        WasmBot::new(&bytes, fuel, memory).map_err(|error| format!("{}: {}", path, error))
+    }
+
+    /// Asks the bot to pick one of `legal`, or `None` if it forfeits.
+    fn ask(&mut self, state: &State, decision: Decision, legal: &[usize]) -> Option<usize> {
+        if self.forfeit.is_some() {
+            return None;
+        }
+        let knowledge = self.knowledge.clone().unwrap_or(Knowledge::new(state, state.turn));
+        let seats: Vec<usize> = (0..state.players.len()).collect();
+        let features = encode(&observe(state, &knowledge, &seats, &decision));
+        let mut input = Vec::with_capacity(4 * (FEATURE_COUNT + legal.len()));
+        for feature in features {
+            input.extend_from_slice(&feature.to_le_bytes());
+        }
+        for action in legal {
+            input.extend_from_slice(&(*action as i32).to_le_bytes());
+        }
+
+        let result = self.memory.write(&mut self.store, self.buffer, &input).map_err(|error| error.to_string())
+            .and_then(|_| self.store.set_fuel(self.fuel).map_err(|error| error.to_string()))
+            .and_then(|_| self.act.call(&mut self.store, legal.len() as i32).map_err(|error| error.to_string()));
+        match result {
+            Ok(action) if legal.contains(&(action as usize)) => Some(action as usize),
+            Ok(action) => {
+                self.forfeit = Some(format!("illegal action {}", action));
+                None
+            },
+            Err(error) => {
+                self.forfeit = Some(error);
+                None
+            },
+        }
+    }
+}
+
+impl Agent for WasmBot {
+    fn start_round(&mut self, state: &State, seat: usize) {
+        self.knowledge = Some(Knowledge::new(state, seat));
+    }
+
+    fn choose_action(&mut self, state: &State, hand: [Card; 2], legal: &[Action]) -> Action {
+        let players = state.players.len();
+        let indices: Vec<usize> = legal.iter().map(|action| action_index(action, state.turn, players)).collect();
+        let action = match self.ask(state, Decision::Play { hand }, &indices) {
+            Some(index) => decode_action(index, state.turn, players).unwrap(),
+            None => legal[0],
+        };
+        if let Some(knowledge) = self.knowledge.as_mut() {
+            knowledge.hand = Some(if action.card == hand[0] { hand[1] } else { hand[0] });
+        }
+        action
+    }
+
+    fn choose_executioner(&mut self, state: &State, hand: Card, drawn: &[Card]) -> ExecutionerChoice {
+        let choices: Vec<ExecutionerChoice> = (0..drawn.len()).flat_map(|keep| [true, false].map(|hand_on_top| ExecutionerChoice { keep, hand_on_top })).collect();
+        let indices: Vec<usize> = choices.iter().map(executioner_index).collect();
+        let choice = match self.ask(state, Decision::Executioner { hand, drawn: drawn.to_vec() }, &indices) {
+            Some(index) => ExecutionerChoice { keep: (index - EXECUTIONER) / 2, hand_on_top: (index - EXECUTIONER) % 2 == 1 },
+            None => choices[0],
+        };
+        if let Some(knowledge) = self.knowledge.as_mut() {
+            knowledge.hand = Some(drawn[choice.keep]);
+        }
+        choice
+    }
+
+    fn observe(&mut self, state: &State, event: &Event) {
+        if let Some(knowledge) = self.knowledge.as_mut() {
+            knowledge.observe(state, event);
+        }
+    }
+
+    fn forfeited(&self) -> Option<String> {
+        self.forfeit.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand::rngs::StdRng;
+
+    use super::*;
+    use crate::bots::RandomBot;
+    use crate::sim::{simulate_game, StartPolicy};
+
+    fn play(bot: &str) -> crate::sim::GameResult {
+        let bytes = wat::parse_str(bot).unwrap();
+        let agents: Vec<Box<dyn Agent>> = vec![Box::new(WasmBot::new(&bytes, DEFAULT_FUEL, DEFAULT_MEMORY).unwrap()), Box::new(RandomBot::new(1))];
+        simulate_game(agents, StartPolicy::Rotate, &mut StdRng::seed_from_u64(1), &mut |_, _| {})
+    }
+
+    #[test]
+    fn test_first_legal_action() {
+        // The first legal action comes right after the features
+        let bot = format!(r#"(module
+            (memory (export "memory") 1)
+            (func (export "buffer") (result i32) i32.const 0)
+            (func (export "act") (param i32) (result i32) i32.const {} i32.load))"#, 4 * FEATURE_COUNT);
+        let result = play(&bot);
+        assert_eq!(result.forfeit, None);
+        assert!(result.winner.is_some());
+    }
+
+    #[test]
+    fn test_limits() {
+        let endless = r#"(module
+            (memory (export "memory") 1)
+            (func (export "buffer") (result i32) i32.const 0)
+            (func (export "act") (param i32) (result i32) (loop br 0) i32.const 0))"#;
+        assert_eq!(play(endless).forfeit, Some(0));
+
+        let greedy = r#"(module
+            (memory (export "memory") 1)
+            (func (export "buffer") (result i32) i32.const 0)
+            (func (export "act") (param i32) (result i32)
+                (if (i32.eq (memory.grow (i32.const 1000)) (i32.const -1)) (then unreachable))
+                i32.const 0))"#;
+        assert_eq!(play(greedy).forfeit, Some(0));
+
+        let importing = r#"(module
+            (import "env" "hands" (func (result i32)))
+            (memory (export "memory") 1)
+            (func (export "buffer") (result i32) i32.const 0)
+            (func (export "act") (param i32) (result i32) i32.const 0))"#;
+        assert!(WasmBot::new(&wat::parse_str(importing).unwrap(), DEFAULT_FUEL, DEFAULT_MEMORY).is_err());
+    }
+}
@@ -0,0 +1,264 @@
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+
+use rand::prelude::*;
+
+// Just enough of WebSocket (RFC 6455) for text messages: the opening handshake on both sides,
+// text frames, fragmented messages, ping and close. Binary messages are ignored.
+
+/// The GUID every server appends to the client's key to prove it speaks WebSocket.
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// The biggest message read, far above anything the game sends.
+const MAX_MESSAGE: usize = 1 << 20;
+
+const CONTINUATION: u8 = 0x0;
+const TEXT: u8 = 0x1;
+const CLOSE: u8 = 0x8;
+const PING: u8 = 0x9;
+const PONG: u8 = 0xa;
+
+/// One end of a WebSocket connection. Clients mask what they send, servers do not.
+pub struct WebSocket {
+    stream: TcpStream,
+    client: bool,
+}
+
+impl WebSocket {
+    pub fn try_clone(&self) -> io::Result<WebSocket> {
+        Ok(WebSocket { stream: self.stream.try_clone()?, client: self.client })
+    }
+
+    pub fn send(&mut self, text: &str) -> io::Result<()> {
+        self.send_frame(TEXT, text.as_bytes())
+    }
+
+    /// Waits for the next text message, or `None` once the connection is closed.
+    pub fn receive(&mut self) -> Option<String> {
+        let mut message = Vec::new();
+        let mut text = false;
+        loop {
+            let (last, opcode, payload) = self.read_frame().ok()?;
+            match opcode {
+                CLOSE => {
+                    let _ = self.send_frame(CLOSE, &payload[..payload.len().min(2)]);
+                    return None;
+                },
+                PING => self.send_frame(PONG, &payload).ok()?,
+                PONG => {},
+                TEXT | CONTINUATION => {
+                    if opcode == TEXT {
+                        message.clear();
+                        text = true;
+                    }
+                    message.extend(payload);
+                    if message.len() > MAX_MESSAGE {
+                        return None;
+                    }
+                    if last && text {
+                        return Some(String::from_utf8_lossy(&message).to_string());
+                    }
+                },
+                _ => text = false,
+            }
+        }
+    }
+
+    /// Tells the other end the connection is over.
+    pub fn close(&mut self) {
+        let _ = self.send_frame(CLOSE, &1000u16.to_be_bytes());
+    }
+
+    fn send_frame(&mut self, opcode: u8, payload: &[u8]) -> io::Result<()> {
+        let mut frame = vec![0x80 | opcode];
+        let mask_bit = if self.client { 0x80 } else { 0 };
+        match payload.len() {
+            length if length < 126 => frame.push(mask_bit | length as u8),
+            length if length < 1 << 16 => {
+                frame.push(mask_bit | 126);
+                frame.extend((length as u16).to_be_bytes());
+            },
+            length => {
+                frame.push(mask_bit | 127);
+                frame.extend((length as u64).to_be_bytes());
+            },
+        }
+        if self.client {
+            let mask: [u8; 4] = thread_rng().gen();
+            frame.extend(mask);
+            frame.extend(payload.iter().enumerate().map(|(i, byte)| byte ^ mask[i % 4]));
+        } else {
+            frame.extend(payload);
+        }
+        self.stream.write_all(&frame)?;
+        self.stream.flush()
+    }
+
+    /// Reads a frame: whether it is the last of its message, its opcode and its unmasked payload.
+    fn read_frame(&mut self) -> io::Result<(bool, u8, Vec<u8>)> {
+        let mut head = [0u8; 2];
+        self.stream.read_exact(&mut head)?;
+        let length = match head[1] & 0x7f {
+            126 => {
+                let mut bytes = [0u8; 2];
+                self.stream.read_exact(&mut bytes)?;
+                u16::from_be_bytes(bytes) as usize
+            },
+            127 => {
+                let mut bytes = [0u8; 8];
+                self.stream.read_exact(&mut bytes)?;
+                u64::from_be_bytes(bytes) as usize
+            },
+            length => length as usize,
+        };
+        if length > MAX_MESSAGE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "frame too big"));
+        }
+        let mut mask = [0u8; 4];
+        if head[1] & 0x80 != 0 {
+            self.stream.read_exact(&mut mask)?;
+        }
+        let mut payload = vec![0u8; length];
+        self.stream.read_exact(&mut payload)?;
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+        Ok((head[0] & 0x80 != 0, head[0] & 0x0f, payload))
+    }
+}
+
+/// Reads the header lines of an HTTP request or response, up to the empty line. Reads a byte
+/// at a time so nothing after the headers is taken from the stream.
+pub fn read_headers(stream: &mut TcpStream) -> Result<Vec<String>, String> {
+    let mut bytes = Vec::new();
+    let mut byte = [0u8];
+    while !bytes.ends_with(b"\r\n\r\n") {
+        stream.read_exact(&mut byte).map_err(|error| format!("The headers were cut short: {}", error))?;
+        bytes.push(byte[0]);
+        if bytes.len() > 16 * 1024 {
+            return Err("The headers are too long.".to_string());
+        }
+    }
+    Ok(String::from_utf8_lossy(&bytes).lines().filter(|line| !line.is_empty()).map(|line| line.to_string()).collect())
+}
+
+/// The value of the header `name` among `headers`.
+pub fn header<'a>(headers: &'a [String], name: &str) -> Option<&'a str> {
+    headers.iter().filter_map(|line| line.split_once(':')).find(|(key, _)| key.trim().eq_ignore_ascii_case(name)).map(|(_, value)| value.trim())
+}
+
+/// What the server answers to the client's `key`.
+pub fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key, GUID).as_bytes()))
+}
+
+/// Answers the opening handshake of a client that connected to a server.
+pub fn accept(mut stream: TcpStream) -> Result<WebSocket, String> {
+    let headers = read_headers(&mut stream)?;
+    if !headers.first().is_some_and(|line| line.starts_with("GET ")) {
+        return Err("Expected a WebSocket handshake.".to_string());
+    }
+    let Some(key) = header(&headers, "Sec-WebSocket-Key") else {
+        let _ = stream.write_all(b"HTTP/1.1 426 Upgrade Required\r\nSec-WebSocket-Version: 13\r\nContent-Length: 0\r\n\r\n");
+        return Err("The request is not a WebSocket upgrade.".to_string());
+    };
+    let response = format!("HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n", accept_key(key));
+    stream.write_all(response.as_bytes()).map_err(|error| error.to_string())?;
+    Ok(WebSocket { stream, client: false })
+}
+
+/// Opens a WebSocket to `path` on the server at the other end of `stream`.
+pub fn connect(mut stream: TcpStream, host: &str, path: &str) -> Result<WebSocket, String> {
+    let key = base64(&thread_rng().gen::<[u8; 16]>());
+    let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n", path, host, key);
+    stream.write_all(request.as_bytes()).map_err(|error| error.to_string())?;
+    let headers = read_headers(&mut stream)?;
+    if !headers.first().is_some_and(|line| line.contains(" 101 ")) {
+        return Err(format!("The server refused the WebSocket: {}", headers.first().map_or("", |line| line.as_str())));
+    }
+    if header(&headers, "Sec-WebSocket-Accept") != Some(accept_key(&key).as_str()) {
+        return Err("The server answered the handshake with the wrong key.".to_string());
+    }
+    Ok(WebSocket { stream, client: true })
+}
+
+pub fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut text = String::new();
+    for chunk in bytes.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, byte)| bits | (*byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+pub fn sha1(bytes: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+    let mut message = bytes.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend((bytes.len() as u64 * 8).to_be_bytes());
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for i in 0..16 {
+            w[i] = u32::from_be_bytes([block[4 * i], block[4 * i + 1], block[4 * i + 2], block[4 * i + 3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a827999),
+                20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let next = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*word);
+            (a, b, c, d, e) = (next, a, b.rotate_left(30), c, d);
+        }
+        for (h, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(value);
+        }
+    }
+    let mut digest = [0u8; 20];
+    for (i, word) in h.iter().enumerate() {
+        digest[4 * i..4 * i + 4].copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_handshake_and_messages() {
+        // The example of RFC 6455
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+        assert_eq!(base64(b"love"), "bG92ZQ==");
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let mut socket = accept(listener.accept().unwrap().0).unwrap();
+            let message = socket.receive().unwrap();
+            socket.send(&message.repeat(100)).unwrap();
+            socket.receive()
+        });
+        let mut client = connect(TcpStream::connect(("127.0.0.1", port)).unwrap(), "localhost", "/").unwrap();
+        client.send("Alice").unwrap();
+        assert_eq!(client.receive().unwrap(), "Alice".repeat(100));
+        client.close();
+        assert_eq!(server.join().unwrap(), None);
+    }
+}